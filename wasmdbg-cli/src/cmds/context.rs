@@ -1,34 +1,36 @@
-use bwasm::Instruction;
 use colored::*;
 use wasmdbg::vm::CodePosition;
-use wasmdbg::Debugger;
+use wasmdbg::{format_instr, Debugger, Frame, Instruction, VariableKind};
 
+use super::source::print_source_context;
+use super::utils::resolve_function;
 use super::{CmdArg, CmdResult, Command, Commands};
 use crate::utils::{print_header, print_line};
 
 const DISASSEMBLY_DEFAULT_MAX_LINES: u32 = 18;
+const SOURCE_CONTEXT_LINES: u32 = 4;
 
 pub fn add_cmds(commands: &mut Commands) {
     commands.add(
         Command::new("locals", cmd_locals)
             .takes_args("[all|COUNT:usize]")
             .description("Print locals")
-            .help("Print the values of the locals of the current function")
+            .help("Print the values of the locals of the function of the selected frame")
             .requires_running(),
     );
     commands.add(
         Command::new("nearpc", cmd_nearpc)
             .takes_args("[FORWARDS:u32 [BACKWARDS:u32]]")
-            .description("Disassemble around current instruction")
+            .description("Disassemble around the current instruction of the selected frame")
             .requires_running(),
     );
     commands.add(
         Command::new("disassemble", cmd_disassemble)
             .alias("disas")
             .alias("disass")
-            .takes_args("[FUNC_INDEX:u32]")
+            .takes_args("[FUNCTION:func]")
             .description("Disassemble code")
-            .help("Disassemble the current function or the one with the specified index.")
+            .help("Disassemble the function of the selected frame or the specified one. FUNCTION can be a function index, a function name or an export name.")
             .requires_file(),
     );
     commands.add(Command::new("stack", cmd_stack).description("Print the current value stack"));
@@ -40,10 +42,35 @@ pub fn add_cmds(commands: &mut Commands) {
     );
     commands.add(
         Command::new("backtrace", cmd_backtrace)
+            .alias("bt")
             .takes_args("[all|COUNT:usize]")
             .description("Print a function backtrace")
             .requires_running(),
     );
+    commands.add(
+        Command::new("frame", cmd_frame)
+            .alias("f")
+            .takes_args("[LEVEL:usize]")
+            .description("Select a frame or print the selected one")
+            .help(
+                "Select the frame at LEVEL of the backtrace so that \"locals\", \"set local\", \"print\", \
+                 \"info locals\", \"disassemble\" and \"context\" inspect it, or print the selected frame if no \
+                 LEVEL is given. The selection is reset to the innermost frame whenever execution continues.",
+            )
+            .requires_running(),
+    );
+    commands.add(
+        Command::new("up", cmd_up)
+            .takes_args("[COUNT:usize]")
+            .description("Select the caller of the selected frame")
+            .requires_running(),
+    );
+    commands.add(
+        Command::new("down", cmd_down)
+            .takes_args("[COUNT:usize]")
+            .description("Select the frame called by the selected frame")
+            .requires_running(),
+    );
     commands.add(
         Command::new("context", cmd_context)
             .description("Show current execution context")
@@ -59,13 +86,13 @@ pub fn add_cmds(commands: &mut Commands) {
 }
 
 fn cmd_locals(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
-    let max_count = match args.get(0) {
-        Some(CmdArg::Const("all")) => usize::max_value(),
+    let max_count = match args.first() {
+        Some(CmdArg::Const("all")) => usize::MAX,
         Some(CmdArg::Usize(count)) => *count,
         None => 17,
         _ => unreachable!(),
     };
-    let locals = dbg.get_vm()?.locals()?;
+    let locals = dbg.selected_frame_state()?.locals;
     if locals.is_empty() {
         println!("<no locals>");
     } else {
@@ -86,7 +113,7 @@ fn cmd_locals(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
 }
 
 fn cmd_nearpc(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
-    let (forward, back) = match args.get(0) {
+    let (forward, back) = match args.first() {
         Some(count) => {
             let count = count.as_u32();
             match args.get(1) {
@@ -96,7 +123,7 @@ fn cmd_nearpc(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
         }
         None => (DISASSEMBLY_DEFAULT_MAX_LINES, 2),
     };
-    let ip = dbg.get_vm()?.ip();
+    let ip = dbg.selected_frame()?.pos;
     let code = dbg.get_file()?.module().get_func(ip.func_index).unwrap().instructions();
     if forward + back >= code.len() as u32 {
         print_disassembly(dbg, CodePosition::new(ip.func_index, 0), None)
@@ -108,8 +135,11 @@ fn cmd_nearpc(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
 }
 
 fn cmd_disassemble(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
-    match args.get(0) {
-        Some(func_index) => print_disassembly(dbg, CodePosition::new(func_index.as_u32(), 0), None),
+    match args.first() {
+        Some(function) => {
+            let func_index = resolve_function(dbg, &function.as_string())?;
+            print_disassembly(dbg, CodePosition::new(func_index, 0), None)
+        }
 
         None => cmd_nearpc(dbg, &[]),
     }
@@ -129,8 +159,8 @@ fn cmd_stack(dbg: &mut Debugger, _args: &[CmdArg]) -> CmdResult {
 }
 
 fn cmd_labels(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
-    let mut max_count = match args.get(0) {
-        Some(CmdArg::Const("all")) => usize::max_value(),
+    let mut max_count = match args.first() {
+        Some(CmdArg::Const("all")) => usize::MAX,
         Some(CmdArg::Usize(count)) => *count,
         None => 5,
         _ => unreachable!(),
@@ -147,27 +177,87 @@ fn cmd_labels(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
 }
 
 fn cmd_backtrace(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
-    let mut max_count = match args.get(0) {
-        Some(CmdArg::Const("all")) => usize::max_value(),
+    let max_count = match args.first() {
+        Some(CmdArg::Const("all")) => usize::MAX,
         Some(CmdArg::Usize(count)) => *count,
         None => 5,
         _ => unreachable!(),
     };
     let backtrace = dbg.backtrace()?;
-    if backtrace.len() < max_count {
-        max_count = backtrace.len();
+    let selected = dbg.selected_frame_index();
+    for (index, frame) in backtrace.iter().enumerate().take(max_count) {
+        let marker = if index == selected { "=>" } else { "  " };
+        println!("{} {}", marker, format_frame(dbg, index, frame)?);
     }
-    if let Some(curr_func) = backtrace.first() {
-        println!("=> f {:<10}{}", curr_func.func_index, curr_func.instr_index);
-        for func in &backtrace[1..max_count] {
-            println!("   f {:<10}{}", func.func_index, func.instr_index);
-        }
-    } else {
-        println!("WTF? No function backtrace...");
+    if backtrace.len() > max_count {
+        println!("...");
     }
     Ok(())
 }
 
+fn cmd_frame(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
+    let index = match args.first() {
+        Some(level) => {
+            dbg.select_frame(level.as_usize())?;
+            level.as_usize()
+        }
+        None => dbg.selected_frame_index(),
+    };
+    println!("{}", format_frame(dbg, index, &dbg.selected_frame()?)?);
+    Ok(())
+}
+
+fn cmd_up(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
+    let count = args.first().map_or(1, CmdArg::as_usize);
+    let outermost = dbg.backtrace()?.len() - 1;
+    let selected = dbg.selected_frame_index();
+    ensure!(selected < outermost, "Initial frame selected; you cannot go up");
+    cmd_frame(dbg, &[CmdArg::Usize((selected + count).min(outermost))])
+}
+
+fn cmd_down(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
+    let count = args.first().map_or(1, CmdArg::as_usize);
+    let selected = dbg.selected_frame_index();
+    ensure!(selected > 0, "Bottom (innermost) frame selected; you cannot go down");
+    cmd_frame(dbg, &[CmdArg::Usize(selected.saturating_sub(count))])
+}
+
+// Formats a frame as "#LEVEL POSITION <FUNCTION> (ARGS) at FILE:LINE". Source information is only available with
+// DWARF debug info.
+fn format_frame(dbg: &Debugger, index: usize, frame: &Frame) -> anyhow::Result<String> {
+    let debug_info = dbg.file().and_then(|file| file.debug_info());
+    let source_name =
+        debug_info.and_then(|debug_info| debug_info.frame_function(frame.pos, frame.inline_depth).ok().flatten());
+    let mut result = format!("#{:<2} {} <", index, frame.pos);
+    match &source_name {
+        Some(name) => result.push_str(name),
+        None => result.push_str(&dbg.function_name(frame.pos.func_index)?),
+    }
+    result.push('>');
+    let debug_info = match debug_info {
+        Some(debug_info) if source_name.is_some() => debug_info,
+        _ => return Ok(result),
+    };
+    if frame.inline_depth < debug_info.inline_depth(frame.pos)? {
+        result.push_str(" [inlined]");
+    }
+    let state = dbg.frame_state(frame)?;
+    let args: Vec<String> = debug_info
+        .variables(&state)?
+        .iter()
+        .filter(|variable| variable.kind() == VariableKind::Parameter)
+        .map(|variable| match debug_info.format_variable(&state, variable) {
+            Ok(value) => format!("{}={}", variable.name(), value),
+            Err(error) => format!("{}=<{}>", variable.name(), error),
+        })
+        .collect();
+    result.push_str(&format!(" ({})", args.join(", ")));
+    if let Some(location) = debug_info.frame_location(frame.pos, frame.inline_depth)? {
+        result.push_str(&format!(" at {}", location));
+    }
+    Ok(result)
+}
+
 fn cmd_globals(dbg: &mut Debugger, _args: &[CmdArg]) -> CmdResult {
     let globals = dbg.globals()?;
     if globals.is_empty() {
@@ -186,13 +276,10 @@ fn cmd_context(dbg: &mut Debugger, _args: &[CmdArg]) -> CmdResult {
 }
 
 fn print_disassembly(dbg: &Debugger, start: CodePosition, len: Option<u32>) -> CmdResult {
-    let curr_instr_index = dbg.vm().and_then(|vm| {
-        if vm.ip().func_index == start.func_index {
-            Some(vm.ip().instr_index)
-        } else {
-            None
-        }
-    });
+    let curr_instr_index = match dbg.selected_frame() {
+        Ok(frame) if frame.pos.func_index == start.func_index => Some(frame.pos.instr_index),
+        _ => None,
+    };
     let code = match dbg.get_file()?.module().get_func(start.func_index) {
         Some(func) => {
             ensure!(!func.is_imported(), "Cannot show disassembly of imported function");
@@ -208,25 +295,27 @@ fn print_disassembly(dbg: &Debugger, start: CodePosition, len: Option<u32>) -> C
     };
     let max_index_len = (start.instr_index as usize + code.len()).to_string().len();
     let breakpoints = dbg.breakpoints().ok();
+    let coverage = dbg.coverage();
     let mut indent = calc_start_indent(code);
     for (i, instr) in code.iter().enumerate() {
         let instr_index = start.instr_index + i as u32;
         let addr_str = format!("{}:{:>02$}", start.func_index, instr_index, max_index_len);
+        let pos = CodePosition::new(start.func_index, instr_index);
         let breakpoint = match breakpoints {
-            Some(ref breakpoints) => breakpoints.find_code(CodePosition::new(start.func_index, instr_index)),
+            Some(ref breakpoints) => breakpoints.find_code(pos),
             None => None,
         };
         let breakpoint_str = match breakpoint {
             Some(_) => "*".red().to_string(),
             None => " ".to_string(),
         };
-        let instr_str = format_instr(dbg, instr)?;
+        let instr_str = format_instr(dbg.get_file()?.module(), instr, dbg.demangle_style());
         match instr {
             Instruction::Else => indent -= 1,
             Instruction::End => indent -= 1,
             _ => (),
         }
-        if curr_instr_index.map_or(false, |i| i == instr_index) {
+        if curr_instr_index == Some(instr_index) {
             // TODO: if instr is call: print args
             println!(
                 "=> {}{}   {: >4$}{}",
@@ -236,6 +325,9 @@ fn print_disassembly(dbg: &Debugger, start: CodePosition, len: Option<u32>) -> C
                 instr_str,
                 indent
             );
+        } else if coverage.as_ref().is_some_and(|coverage| !coverage.is_covered(pos)) {
+            let line = format!("{}   {: >3$}{}", addr_str, "", instr_str, indent);
+            println!("   {}{}", breakpoint_str, line.dimmed());
         } else {
             println!("   {}{}   {: >4$}{}", breakpoint_str, addr_str, "", instr_str, indent);
         }
@@ -250,18 +342,6 @@ fn print_disassembly(dbg: &Debugger, start: CodePosition, len: Option<u32>) -> C
     Ok(())
 }
 
-fn format_instr(dbg: &Debugger, instr: &Instruction) -> anyhow::Result<String> {
-    let result = match instr {
-        Instruction::Call(index) => format!(
-            "{} <{}>",
-            instr,
-            dbg.get_file()?.module().get_func(*index).unwrap().name()
-        ),
-        _ => instr.to_string(),
-    };
-    Ok(result)
-}
-
 fn calc_start_indent(code: &[Instruction]) -> usize {
     let mut indent: isize = 0;
     let mut min_indent: isize = 0;
@@ -270,10 +350,8 @@ fn calc_start_indent(code: &[Instruction]) -> usize {
             Instruction::Block(_) => indent += 1,
             Instruction::Loop(_) => indent += 1,
             Instruction::If(_) => indent += 1,
-            Instruction::Else => {
-                if indent == min_indent {
-                    min_indent -= 1;
-                }
+            Instruction::Else if indent == min_indent => {
+                min_indent -= 1;
             }
             Instruction::End => {
                 indent -= 1;
@@ -294,6 +372,13 @@ fn calc_start_indent(code: &[Instruction]) -> usize {
 pub fn print_context(dbg: &mut Debugger) -> CmdResult {
     print_header("LOCALS");
     cmd_locals(dbg, &[])?;
+    if dbg.file().is_some_and(|file| file.debug_info().is_some()) {
+        print_header("SOURCE");
+        // Missing source files shouldn't hide the rest of the context
+        if let Err(error) = print_source_context(dbg, SOURCE_CONTEXT_LINES) {
+            println!("{}", error);
+        }
+    }
     print_header("DISASM");
     cmd_nearpc(dbg, &[])?;
     print_header("VALUE STACK");
@@ -1,54 +1,158 @@
+use regex::Regex;
 use wasmdbg::vm::{CodePosition, Trap};
-use wasmdbg::Debugger;
-use wasmdbg::{Breakpoint, BreakpointTrigger, Value};
+use wasmdbg::{Breakpoint, BreakpointTrigger, Expr, Value};
+use wasmdbg::{Debugger, DebuggerResult};
 
 use super::context;
+use super::parse::parse_launch_args;
+use super::utils::{
+    self, glob_to_regex, parse_source_location, resolve_code_location, resolve_function, resolve_source_location,
+    split_offset,
+};
 use super::{CmdArg, CmdArgOptionExt, CmdResult, Command, Commands};
 
 pub fn add_cmds(commands: &mut Commands) {
     commands.add(
         Command::new("run", cmd_run)
             .alias("r")
+            .takes_args("[ARGS:line]")
             .description("Run the currently loaded binary")
+            .help(
+                "Run the currently loaded binary.\n\n\
+                 Usage: run [--entry FUNCTION] [--dir GUEST=HOST] [--memdir GUEST] [--] [ARGS...] [< FILE]\n\n\
+                 ARGS are passed to the program and FILE is used as its stdin. Both are kept for later runs \
+                 until new ones are given (see also \"set args\").\n\n\
+                 The start function of the binary is always run first. Afterwards the entry function is run, \
+                 which defaults to the exported \"_start\" or \"main\" function.\n\n\
+                 Options:\n  \
+                 --entry FUNCTION  Use the function with this index or name as the entry function for this run\n  \
+                 --dir GUEST=HOST  Make the host directory HOST available to WASI as GUEST\n  \
+                 --memdir GUEST    Make an empty in-memory directory available to WASI as GUEST\n\n\
+                 Directory mappings are also kept for later runs until new ones are given.",
+            )
             .requires_file(),
     );
     commands.add(
         Command::new("start", cmd_start)
+            .takes_args("[ARGS:line]")
             .description("Start the currently loaded binary and pause on the first instruction")
+            .help(
+                "Start the currently loaded binary and pause on the first instruction of the entry function. \
+                 Takes the same arguments as \"run\".\n\n\
+                 If the binary has a start function, it is run to completion first (stopping at breakpoints). \
+                 Without an entry function, execution pauses at the beginning of the start function instead.",
+            )
             .requires_file(),
     );
     commands.add(
         Command::new("call", cmd_call)
-            .takes_args("FUNC_INDEX:u32 [ARGS:str...]")
+            .takes_args("FUNCTION:func [ARGS:str...]")
             .description("Call a specific function in the current runtime context")
             .requires_file(),
     );
     commands.add(Command::new("reset", cmd_reset).description("Reset the current wasm instance"));
+    commands.add(
+        Command::new("checkpoint", cmd_checkpoint)
+            .description("Save the current program state")
+            .help(
+                "Save a copy of the memories, tables, globals, stacks and instruction pointer of the current wasm \
+                 instance that can be returned to with \"restart\". The state of WASI, e.g. open files, is not \
+                 included.",
+            )
+            .requires_running(),
+    );
+    commands.add(
+        Command::new("restart", cmd_restart)
+            .takes_args("CHECKPOINT_INDEX:u32")
+            .description("Return to a checkpoint")
+            .help("Restore the program state saved by checkpoint CHECKPOINT_INDEX. Checkpoints are kept until another binary is loaded, so they can be restored any number of times and also after \"reset\" or \"run\".")
+            .requires_file(),
+    );
     commands.add(
             Command::new("break", cmd_break)
                 .alias("b")
-                .takes_args("FUNC_INDEX:u32 [INSTRUCTION_INDEX:u32]")
+                .takes_args("LOCATION:func [INSTRUCTION_INDEX:u32] [if CONDITION:line]")
                 .description("Set a breakpoint")
-                .help("Set a breakpoint at the specified function and instruction. If no instruction is specified the breakpoint is set to the function start. When execution reaches a breakpoint it will pause.")
+                .help("Set a breakpoint at the specified function and instruction. If no instruction is specified the breakpoint is set to the function start. When execution reaches a breakpoint it will pause.\n\nLOCATION is a function index, a function name or an export name, optionally followed by +OFFSET to specify the instruction. Names containing * or ? are glob patterns that set a breakpoint in every matching function. If the binary contains DWARF debug info, LOCATION can also be a source line like \"src/main.rs:42\". If the line has no code, the breakpoint is set at the next line that does.\n\nIf a CONDITION is given, execution only pauses if it evaluates to a non-zero value. See \"help condition\" for the expression syntax.")
             .requires_file()
         );
+    commands.add(
+        Command::new("tbreak", cmd_tbreak)
+            .takes_args("LOCATION:func [INSTRUCTION_INDEX:u32] [if CONDITION:line]")
+            .description("Set a temporary breakpoint")
+            .help("Set a breakpoint like \"break\" that is deleted once execution pauses at it.")
+            .requires_file(),
+    );
+    commands.add(
+        Command::new("rbreak", cmd_rbreak)
+            .takes_args("REGEX:str [if CONDITION:line]")
+            .description("Set breakpoints at all functions matching a regex")
+            .help("Set a breakpoint at the start of every function whose name or export name matches the regular expression REGEX, e.g. \"rbreak ^alloc::\".")
+            .requires_file(),
+    );
     commands.add(
         Command::new_subcommand("watch")
             .description("Set a watchpoint")
             .requires_file()
             .add_subcommand(
                 Command::new("memory", cmd_watch_memory)
-                    .takes_args("ADDR:addr [read|write]")
+                    .takes_args("ADDR:addr [read|write] [if CONDITION:line]")
                     .description("Watch a memory location")
                     .help("Watch the memory at address ADDR and pause execution when it's value is read/written."),
             )
             .add_subcommand(
                 Command::new("global", cmd_watch_global)
-                    .takes_args("INDEX:u32 [read|write]")
+                    .takes_args("INDEX:u32 [read|write] [if CONDITION:line]")
                     .description("Watch a global")
                     .help("Watch the global with index INDEX and pause execution when it's value is read/written."),
             ),
     );
+    commands.add(
+        Command::new("condition", cmd_condition)
+            .takes_args("BREAKPOINT_INDEX:u32 [CONDITION:line]")
+            .description("Set the condition of a breakpoint")
+            .help(
+                "Only pause at breakpoint or watchpoint BREAKPOINT_INDEX if CONDITION evaluates to a non-zero value. \
+                 Without a CONDITION the breakpoint becomes unconditional again.\n\n\
+                 Expressions can use:\n  \
+                 local N, global N       The value of a local or global\n  \
+                 stack N                 The N-th value from the top of the value stack\n  \
+                 load8/16/32/64(ADDR)    An integer loaded from memory, 8 and 16 bit values are zero-extended\n  \
+                 loadf32/loadf64(ADDR)   A float loaded from memory\n  \
+                 i32/i64/f32/f64(EXPR)   A conversion to another type\n  \
+                 Integer and float literals, e.g. 42, 0x1000 and 1.5\n  \
+                 The operators + - * / % & | ^ << >> == != < <= > >= && || ! ~ and parentheses\n\n\
+                 Integers are signed i32 or i64 values like in WebAssembly, and literals up to 0xffffffff are i32 \
+                 values with the same bits, so 0xffffffff == -1. The unsigned operators /u %u >>u <u <=u >u >=u \
+                 treat both operands as unsigned.\n\n\
+                 Example: condition 1 local 2 == 0x1000 && load32(global 0 + 8) >u 5",
+            )
+            .requires_file(),
+    );
+    commands.add(
+        Command::new("enable", cmd_enable)
+            .takes_args("[BREAKPOINT_INDEX:u32...]")
+            .description("Enable breakpoints")
+            .help("Enable the breakpoints with the specified indices or all breakpoints if no index is given.")
+            .requires_file(),
+    );
+    commands.add(
+        Command::new("disable", cmd_disable)
+            .takes_args("[BREAKPOINT_INDEX:u32...]")
+            .description("Disable breakpoints")
+            .help(
+                "Disable the breakpoints with the specified indices or all breakpoints if no index is given. \
+                 Disabled breakpoints keep their index but don't pause execution until they are enabled again.",
+            )
+            .requires_file(),
+    );
+    commands.add(
+        Command::new("ignore", cmd_ignore)
+            .takes_args("BREAKPOINT_INDEX:u32 COUNT:u32")
+            .description("Ignore the next hits of a breakpoint")
+            .help("Don't pause execution the next COUNT times breakpoint BREAKPOINT_INDEX is reached.")
+            .requires_file(),
+    );
     commands.add(
         Command::new("delete", cmd_delete)
             .description("Delete a breakpoint")
@@ -64,22 +168,34 @@ pub fn add_cmds(commands: &mut Commands) {
     );
     commands.add(
         Command::new("step", cmd_step)
-            .alias("stepi")
             .alias("s")
-            .alias("si")
             .takes_args("[N:u32]")
-            .description("Step one instruction")
-            .help("Step exactly one or if an argument is given exactly N instructions.\nUnlike \"next\" this will enter subroutine calls.")
+            .description("Step one source line")
+            .help("Execute until the source line changes or if an argument is given, do so N times.\nUnlike \"next\" this will enter subroutine calls that have line information.\n\nThis requires DWARF debug info. Without line information for the current position, this steps exactly one instruction like \"stepi\".")
             .requires_running()
     );
     commands.add(
         Command::new("next", cmd_next)
-            .alias("nexti")
             .alias("n")
+            .takes_args("[N:u32]")
+            .description("Step one source line, but skip over subroutine calls")
+            .help("Execute until the source line changes or if an argument is given, do so N times.\nUnlike \"step\" this will skip over subroutine calls.\n\nThis requires DWARF debug info. Without line information for the current position, this steps one instruction like \"nexti\".")
+            .requires_running()
+    );
+    commands.add(
+        Command::new("stepi", cmd_stepi)
+            .alias("si")
+            .takes_args("[N:u32]")
+            .description("Step one instruction")
+            .help("Step exactly one or if an argument is given exactly N instructions.\nUnlike \"nexti\" this will enter subroutine calls.")
+            .requires_running()
+    );
+    commands.add(
+        Command::new("nexti", cmd_nexti)
             .alias("ni")
             .takes_args("[N:u32]")
             .description("Step one instruction, but skip over subroutine calls")
-            .help("Step one or if an argument is given N instructions.\nUnlike \"step\" this will skip over subroutine calls.")
+            .help("Step one or if an argument is given N instructions.\nUnlike \"stepi\" this will skip over subroutine calls.")
             .requires_running()
     );
     commands.add(
@@ -87,23 +203,108 @@ pub fn add_cmds(commands: &mut Commands) {
             .description("Execute until the current function returns")
             .requires_running(),
     );
+    commands.add(
+        Command::new("record", cmd_record)
+            .takes_args("[start|stop|trace] [FILE:path]")
+            .description("Record execution to allow reverse execution or replay")
+            .help(
+                "Start or stop recording the execution. While recording, the effect of every executed \
+                 instruction on the stacks, locals, globals and memory is logged so that it can be undone with \
+                 \"reverse-step\", \"reverse-next\", \"reverse-finish\" and \"reverse-continue\".\n\n\
+                 Recording stays enabled for later runs. Side effects outside of the wasm instance, e.g. \
+                 output or WASI file changes, are not undone. Modifying the program state with \"set\" \
+                 discards the recorded history.\n\n\
+                 \"record trace FILE\" instead writes a trace of every following run to FILE. The trace contains \
+                 the executed instructions as well as the results and effects of all host function calls and can \
+                 be replayed deterministically with \"load --replay FILE\". \"record stop\" stops both kinds of \
+                 recording.",
+            )
+            .requires_file(),
+    );
+    commands.add(
+        Command::new("reverse-step", cmd_reverse_step)
+            .alias("reverse-stepi")
+            .alias("rs")
+            .takes_args("[N:u32]")
+            .description("Step one instruction backwards")
+            .help("Undo exactly one or if an argument is given exactly N instructions.\nUnlike \"reverse-next\" this will enter subroutine calls.")
+            .requires_running(),
+    );
+    commands.add(
+        Command::new("reverse-next", cmd_reverse_next)
+            .alias("reverse-nexti")
+            .alias("rn")
+            .takes_args("[N:u32]")
+            .description("Step one instruction backwards, but skip over subroutine calls")
+            .help("Undo one or if an argument is given N instructions.\nUnlike \"reverse-step\" this will skip over subroutine calls.")
+            .requires_running(),
+    );
+    commands.add(
+        Command::new("reverse-finish", cmd_reverse_finish)
+            .description("Execute backwards until the current function was called")
+            .requires_running(),
+    );
+    commands.add(
+        Command::new("reverse-continue", cmd_reverse_continue)
+            .alias("rc")
+            .description("Execute backwards until a breakpoint or watchpoint is reached")
+            .help(
+                "Execute backwards until a breakpoint is reached, a watched value was written or the start of \
+                 the recorded history is reached.",
+            )
+            .requires_running(),
+    );
 }
 
-fn cmd_run(dbg: &mut Debugger, _args: &[CmdArg]) -> CmdResult {
-    print_run_result(dbg.run()?, dbg)
+fn cmd_run(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
+    let entry = apply_launch_args(dbg, args)?;
+    print_run_result(dbg.run(entry)?, dbg)
 }
 
-fn cmd_start(dbg: &mut Debugger, _args: &[CmdArg]) -> CmdResult {
-    if let Some(trap) = dbg.start()? {
+fn cmd_start(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
+    let entry = apply_launch_args(dbg, args)?;
+    if let Some(trap) = dbg.start(entry)? {
         print_run_result(trap, dbg)
     } else {
         context::print_context(dbg)
     }
 }
 
+// Returns the entry function selected with --entry
+fn apply_launch_args(dbg: &mut Debugger, args: &[CmdArg]) -> anyhow::Result<Option<u32>> {
+    let line = match args.first() {
+        Some(line) => line.as_string(),
+        None => return Ok(None),
+    };
+    let launch_args = parse_launch_args(&line)?;
+
+    // --entry may be mixed with the preopen options in front of the program arguments
+    let mut entry = None;
+    let mut options = Vec::new();
+    let mut rest = &launch_args.args[..];
+    loop {
+        match rest {
+            [option, value, ..] if option == "--entry" => entry = Some(resolve_function(dbg, value)?),
+            [option] if option == "--entry" => bail!("Missing value for \"{}\"", option),
+            [option, _, ..] if option == "--dir" || option == "--memdir" => options.extend_from_slice(&rest[..2]),
+            _ => break,
+        }
+        rest = &rest[2..];
+    }
+    options.extend_from_slice(rest);
+
+    let program_args = utils::apply_preopen_options(dbg, &options)?;
+    if !program_args.is_empty() || launch_args.stdin.is_some() {
+        let config = dbg.launch_config_mut();
+        config.set_args(program_args.to_vec());
+        config.set_stdin(launch_args.stdin);
+    }
+    Ok(entry)
+}
+
 fn cmd_call(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
+    let func_index = resolve_function(dbg, &args[0].as_string())?;
     let module = dbg.get_file()?.module();
-    let func_index = args[0].as_u32();
     let args = &args[1..];
 
     let func_type = module
@@ -137,45 +338,221 @@ fn cmd_reset(dbg: &mut Debugger, _args: &[CmdArg]) -> CmdResult {
     Ok(())
 }
 
+fn cmd_checkpoint(dbg: &mut Debugger, _args: &[CmdArg]) -> CmdResult {
+    let index = dbg.add_checkpoint()?;
+    let ip = dbg.get_vm()?.ip();
+    let name = dbg.function_name(ip.func_index)?;
+    println!("Checkpoint {} at {} <{}>", index, ip, name);
+    Ok(())
+}
+
+fn cmd_restart(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
+    dbg.restore_checkpoint(args[0].as_u32())?;
+    match dbg.get_vm()?.trap() {
+        Some(trap) => print_run_result(trap.clone(), dbg),
+        None => context::print_context(dbg),
+    }
+}
+
 fn cmd_break(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
-    let func_index = args[0].as_u32();
-    let instr_index = args.get(1).as_u32_or(0);
-    let pos = CodePosition {
-        func_index,
-        instr_index,
+    add_code_breakpoint(dbg, args, false)
+}
+
+fn cmd_tbreak(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
+    add_code_breakpoint(dbg, args, true)
+}
+
+fn add_code_breakpoint(dbg: &mut Debugger, args: &[CmdArg], temporary: bool) -> CmdResult {
+    let location = args[0].as_string();
+    let condition = parse_condition(args)?;
+    let instr_index = match args.get(1) {
+        Some(CmdArg::U32(instr_index)) => Some(*instr_index),
+        _ => None,
     };
-    let index = dbg.add_breakpoint(Breakpoint::Code(pos))?;
-    println!("Set breakpoint {} at {}", index, pos);
+
+    if let Some((file, line)) = parse_source_location(&location) {
+        ensure!(
+            instr_index.is_none(),
+            "Cannot specify an instruction index for a source line"
+        );
+        let (line, positions) = resolve_source_location(dbg, file, line)?;
+        ensure!(!positions.is_empty(), "No code at line {} of \"{}\"", line, file);
+        for pos in positions {
+            add_breakpoint_at(dbg, pos, condition.clone(), temporary)?;
+        }
+        return Ok(());
+    }
+
+    if location.contains(['*', '?']) {
+        let (pattern, offset) = split_offset(&location);
+        ensure!(
+            offset.is_none() || instr_index.is_none(),
+            "Specify either an offset or an instruction index"
+        );
+        let offset = offset.or(instr_index).unwrap_or(0);
+        let regex = Regex::new(&glob_to_regex(pattern))?;
+        return add_matching_breakpoints(dbg, &regex, offset, condition, temporary);
+    }
+
+    let mut pos = resolve_code_location(dbg, &location)?;
+    if let Some(instr_index) = instr_index {
+        ensure!(pos.instr_index == 0, "Specify either an offset or an instruction index");
+        pos.instr_index = instr_index;
+    }
+    add_breakpoint_at(dbg, pos, condition, temporary)
+}
+
+fn cmd_rbreak(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
+    let regex = Regex::new(&args[0].as_string())?;
+    let condition = parse_condition(args)?;
+    add_matching_breakpoints(dbg, &regex, 0, condition, false)
+}
+
+fn add_matching_breakpoints(
+    dbg: &mut Debugger,
+    regex: &Regex,
+    offset: u32,
+    condition: Option<Expr>,
+    temporary: bool,
+) -> CmdResult {
+    let file = dbg.get_file()?;
+    let mut func_indices: Vec<u32> = file
+        .function_names()
+        .iter()
+        .filter(|(name, index)| regex.is_match(name) && !file.module().func(*index).is_imported())
+        .map(|(_, index)| *index)
+        .collect();
+    func_indices.sort_unstable();
+    func_indices.dedup();
+    ensure!(!func_indices.is_empty(), "No function matches \"{}\"", regex);
+
+    for func_index in func_indices {
+        add_breakpoint_at(dbg, CodePosition::new(func_index, offset), condition.clone(), temporary)?;
+    }
+    Ok(())
+}
+
+fn add_breakpoint_at(dbg: &mut Debugger, pos: CodePosition, condition: Option<Expr>, temporary: bool) -> CmdResult {
+    let index = if temporary {
+        dbg.add_temporary_breakpoint(Breakpoint::Code(pos))?
+    } else {
+        dbg.add_breakpoint(Breakpoint::Code(pos))?
+    };
+    dbg.set_breakpoint_condition(index, condition)?;
+    let name = dbg.function_name(pos.func_index)?;
+    let kind = if temporary {
+        "temporary breakpoint"
+    } else {
+        "breakpoint"
+    };
+    match dbg
+        .get_file()?
+        .debug_info()
+        .and_then(|debug_info| debug_info.location(pos))
+    {
+        Some(location) => println!("Set {} {} at {} <{}> ({})", kind, index, pos, name, location),
+        None => println!("Set {} {} at {} <{}>", kind, index, pos, name),
+    }
     Ok(())
 }
 
 fn cmd_watch_memory(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
     let addr = args[0].as_u32();
     let trigger = match args.get(1) {
-        Some(trigger) => match trigger.as_const() {
-            "read" => BreakpointTrigger::Read,
-            "write" => BreakpointTrigger::Write,
-            trigger => bail!("Invalid watchpoint trigger: {}", trigger),
-        },
-        None => BreakpointTrigger::ReadWrite,
+        Some(CmdArg::Const("read")) => BreakpointTrigger::Read,
+        Some(CmdArg::Const("write")) => BreakpointTrigger::Write,
+        _ => BreakpointTrigger::ReadWrite,
     };
+    let condition = parse_condition(args)?;
     let index = dbg.add_breakpoint(Breakpoint::Memory(trigger, addr))?;
+    dbg.set_breakpoint_condition(index, condition)?;
     println!("Set watchpoint {} at address 0x{:>08x}", index, addr);
     Ok(())
 }
 
 fn cmd_watch_global(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
-    let index = args[0].as_u32();
+    let global_index = args[0].as_u32();
     let trigger = match args.get(1) {
-        Some(trigger) => match trigger.as_const() {
-            "read" => BreakpointTrigger::Read,
-            "write" => BreakpointTrigger::Write,
-            trigger => bail!("Invalid watchpoint trigger: {}", trigger),
-        },
-        None => BreakpointTrigger::ReadWrite,
+        Some(CmdArg::Const("read")) => BreakpointTrigger::Read,
+        Some(CmdArg::Const("write")) => BreakpointTrigger::Write,
+        _ => BreakpointTrigger::ReadWrite,
+    };
+    let condition = parse_condition(args)?;
+    let index = dbg.add_breakpoint(Breakpoint::Global(trigger, global_index))?;
+    dbg.set_breakpoint_condition(index, condition)?;
+    println!("Set watchpoint {} at global {}", index, global_index);
+    Ok(())
+}
+
+fn cmd_condition(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
+    let index = args[0].as_u32();
+    let condition = match args.get(1) {
+        Some(condition) if !condition.as_string().trim().is_empty() => Some(Expr::parse(&condition.as_string())?),
+        _ => None,
+    };
+    let is_conditional = condition.is_some();
+    ensure!(
+        dbg.set_breakpoint_condition(index, condition)?,
+        "No breakpoint with index {}",
+        index
+    );
+    if is_conditional {
+        println!("Condition of breakpoint {} set", index);
+    } else {
+        println!("Breakpoint {} is now unconditional", index);
+    }
+    Ok(())
+}
+
+// Parses the condition following an "if" in the arguments of "break" and "watch"
+fn parse_condition(args: &[CmdArg]) -> anyhow::Result<Option<Expr>> {
+    match args.iter().position(|arg| matches!(arg, CmdArg::Const("if"))) {
+        Some(pos) => {
+            let condition = args.get(pos + 1).map(CmdArg::as_string).unwrap_or_default();
+            ensure!(!condition.trim().is_empty(), "Missing condition after \"if\"");
+            Ok(Some(Expr::parse(&condition)?))
+        }
+        None => Ok(None),
+    }
+}
+
+fn cmd_enable(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
+    set_breakpoints_enabled(dbg, args, true)
+}
+
+fn cmd_disable(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
+    set_breakpoints_enabled(dbg, args, false)
+}
+
+fn set_breakpoints_enabled(dbg: &mut Debugger, args: &[CmdArg], enabled: bool) -> CmdResult {
+    let indices: Vec<u32> = if args.is_empty() {
+        dbg.breakpoints()?.iter().map(|(index, _)| *index).collect()
+    } else {
+        args.iter().map(CmdArg::as_u32).collect()
     };
-    let index = dbg.add_breakpoint(Breakpoint::Global(trigger, index))?;
-    println!("Set watchpoint {} at global {}", index, index);
+    for index in indices {
+        ensure!(
+            dbg.set_breakpoint_enabled(index, enabled)?,
+            "No breakpoint with index {}",
+            index
+        );
+    }
+    Ok(())
+}
+
+fn cmd_ignore(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
+    let index = args[0].as_u32();
+    let count = args[1].as_u32();
+    ensure!(
+        dbg.set_breakpoint_ignore_count(index, count)?,
+        "No breakpoint with index {}",
+        index
+    );
+    match count {
+        0 => println!("Will stop next time breakpoint {} is reached", index),
+        1 => println!("Will ignore next crossing of breakpoint {}", index),
+        _ => println!("Will ignore next {} crossings of breakpoint {}", count, index),
+    }
     Ok(())
 }
 
@@ -198,51 +575,228 @@ fn cmd_continue(dbg: &mut Debugger, _args: &[CmdArg]) -> CmdResult {
 }
 
 fn cmd_step(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
-    let steps = args.get(0).as_u32_or(1);
+    repeat_step(dbg, args, Debugger::execute_line_step)
+}
+
+fn cmd_next(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
+    repeat_step(dbg, args, Debugger::execute_line_step_over)
+}
+
+fn cmd_stepi(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
+    repeat_step(dbg, args, Debugger::execute_step)
+}
+
+fn cmd_nexti(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
+    repeat_step(dbg, args, Debugger::execute_step_over)
+}
+
+fn repeat_step(
+    dbg: &mut Debugger,
+    args: &[CmdArg],
+    step: fn(&mut Debugger) -> DebuggerResult<Option<Trap>>,
+) -> CmdResult {
+    let steps = args.first().as_u32_or(1);
     for _ in 0..steps {
-        if let Some(trap) = dbg.execute_step()? {
+        if let Some(trap) = step(dbg)? {
             return print_run_result(trap, dbg);
         }
     }
     context::print_context(dbg)
 }
 
-fn cmd_next(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
-    let steps = args.get(0).as_u32_or(1);
+fn cmd_finish(dbg: &mut Debugger, _args: &[CmdArg]) -> CmdResult {
+    if let Some(trap) = dbg.execute_step_out()? {
+        print_run_result(trap, dbg)
+    } else {
+        context::print_context(dbg)
+    }
+}
+
+fn cmd_record(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
+    match args.first() {
+        Some(CmdArg::Const("trace")) => {
+            let path = match args.get(1) {
+                Some(path) => path.as_string(),
+                None => bail!("Missing trace file"),
+            };
+            dbg.set_trace_output(Some(shellexpand::tilde(&path).into_owned().into()));
+            println!("Recording traces of new runs to \"{}\"", path);
+            return Ok(());
+        }
+        Some(CmdArg::Const("stop")) if dbg.trace_output().is_some() => {
+            dbg.set_trace_output(None);
+            println!("Stopped recording traces");
+            if !dbg.is_recording() {
+                return Ok(());
+            }
+        }
+        _ => ensure!(
+            !matches!(args.last(), Some(CmdArg::Str(_))),
+            "Only \"record trace\" takes a file"
+        ),
+    }
+    let recording = !matches!(args.first(), Some(CmdArg::Const("stop")));
+    ensure!(
+        recording != dbg.is_recording(),
+        "Execution is {} being recorded",
+        if recording { "already" } else { "not" }
+    );
+    dbg.set_recording(recording);
+    if recording {
+        println!("Recording execution");
+    } else {
+        println!("Stopped recording execution");
+    }
+    Ok(())
+}
+
+fn cmd_reverse_step(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
+    let steps = args.first().as_u32_or(1);
     for _ in 0..steps {
-        if let Some(trap) = dbg.execute_step_over()? {
+        if let Some(trap) = dbg.reverse_step()? {
             return print_run_result(trap, dbg);
         }
     }
     context::print_context(dbg)
 }
 
-fn cmd_finish(dbg: &mut Debugger, _args: &[CmdArg]) -> CmdResult {
-    if let Some(trap) = dbg.execute_step_out()? {
+fn cmd_reverse_next(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
+    let steps = args.first().as_u32_or(1);
+    for _ in 0..steps {
+        if let Some(trap) = dbg.reverse_step_over()? {
+            return print_run_result(trap, dbg);
+        }
+    }
+    context::print_context(dbg)
+}
+
+fn cmd_reverse_finish(dbg: &mut Debugger, _args: &[CmdArg]) -> CmdResult {
+    if let Some(trap) = dbg.reverse_step_out()? {
         print_run_result(trap, dbg)
     } else {
         context::print_context(dbg)
     }
 }
 
+fn cmd_reverse_continue(dbg: &mut Debugger, _args: &[CmdArg]) -> CmdResult {
+    print_run_result(dbg.reverse_continue()?, dbg)
+}
+
 fn print_run_result(trap: Trap, dbg: &mut Debugger) -> CmdResult {
     match trap {
-        Trap::ExecutionFinished => {
-            if let Some(result) = dbg.get_vm()?.value_stack().first() {
-                println!("Finished execution => {}", result);
-            } else {
-                println!("Finished execution")
+        Trap::ExecutionFinished => match dbg.get_vm()?.value_stack() {
+            [] => println!("Finished execution"),
+            [result] => println!("Finished execution => {}", result),
+            results => {
+                println!("Finished execution =>");
+                for (i, result) in results.iter().enumerate() {
+                    println!(" {}: {}", i, result);
+                }
             }
-        }
+        },
         Trap::BreakpointReached(index) => {
-            context::print_context(dbg)?;
-            println!("Reached breakpoint {}", index);
+            if !is_silent(dbg, index)? {
+                context::print_context(dbg)?;
+                println!("Reached breakpoint {}", index);
+            }
         }
         Trap::WatchpointReached(index) => {
+            if !is_silent(dbg, index)? {
+                context::print_context(dbg)?;
+                println!("Reached watchpoint {}", index);
+            }
+        }
+        Trap::EntryReached(index) => {
             context::print_context(dbg)?;
-            println!("Reached watchpoint {}", index);
+            println!("Reached entry function {}", index);
+        }
+        Trap::BreakpointConditionFailed { .. }
+        | Trap::NoMoreHistory
+        | Trap::RewindWhileTracing
+        | Trap::ReplayDiverged(_)
+        | Trap::ReplayEnded => {
+            context::print_context(dbg)?;
+            println!("{}", trap);
         }
         _ => println!("Trap: {}", trap),
     }
     Ok(())
 }
+
+fn is_silent(dbg: &Debugger, breakpoint: u32) -> anyhow::Result<bool> {
+    let breakpoints = dbg.breakpoints()?;
+    Ok(breakpoints
+        .commands(breakpoint)
+        .first()
+        .is_some_and(|line| line == "silent"))
+}
+
+#[cfg(test)]
+mod tests {
+    use wasmdbg::vm::CodePosition;
+    use wasmdbg::{Breakpoint, Debugger};
+
+    use super::Commands;
+    use crate::test_utils::load_wat;
+
+    fn code_breakpoints(dbg: &mut Debugger, command: &str, args: &str) -> Vec<(u32, u32)> {
+        dbg.clear_breakpoints().unwrap();
+        Commands::all().find_by_name(command).unwrap().handle(dbg, args);
+        let mut positions: Vec<(u32, u32)> = dbg
+            .breakpoints()
+            .unwrap()
+            .iter()
+            .map(|(_, breakpoint)| match breakpoint {
+                Breakpoint::Code(CodePosition {
+                    func_index,
+                    instr_index,
+                }) => (*func_index, *instr_index),
+                _ => panic!("unexpected breakpoint"),
+            })
+            .collect();
+        positions.sort_unstable();
+        positions
+    }
+
+    #[test]
+    fn break_by_name_pattern_and_export() {
+        let mut dbg = load_wat(
+            r#"(module
+                (import "env" "helper_import" (func))
+                (func $main (export "run") nop nop)
+                (func $helper_one nop nop)
+                (func $helper_two nop nop)
+                (func $operator+ nop nop)
+                (func $operator+= nop nop))"#,
+        );
+        let mut check = |command, args, expected: &[(u32, u32)]| {
+            assert_eq!(
+                code_breakpoints(&mut dbg, command, args),
+                expected,
+                "{} {}",
+                command,
+                args
+            );
+        };
+        check("break", "main", &[(1, 0)]);
+        check("break", "run", &[(1, 0)]);
+        check("break", "run+1", &[(1, 1)]);
+        check("break", "main 1", &[(1, 1)]);
+        check("break", "3", &[(3, 0)]);
+        check("break", "operator+", &[(4, 0)]);
+        check("break", "operator++1", &[(4, 1)]);
+        check("tbreak", "operator+=", &[(5, 0)]);
+        check("break", "missing", &[]);
+        // Imported functions have no code, so patterns skip them
+        check("break", "helper_*", &[(2, 0), (3, 0)]);
+        check("break", "helper_*+1", &[(2, 1), (3, 1)]);
+        check("break", "helper_* 1", &[(2, 1), (3, 1)]);
+        check("break", "helper_*+1 1", &[]);
+        check("break", "operator+*", &[(4, 0), (5, 0)]);
+        check("break", "operator+?+1", &[(5, 1)]);
+        check("rbreak", "^operator", &[(4, 0), (5, 0)]);
+        check("rbreak", "^(main|helper_two)$", &[(1, 0), (3, 0)]);
+        check("rbreak", "^ru", &[(1, 0)]);
+        check("rbreak", "nothing", &[]);
+    }
+}
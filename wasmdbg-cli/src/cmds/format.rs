@@ -77,7 +77,7 @@ const fn hex_char(b: u8) -> char {
     HEX_CHARS[b as usize]
 }
 
-pub fn fmt_char(b: &u8) -> CharIter {
+pub fn fmt_char(b: &u8) -> CharIter<'_> {
     match *b {
         0 => CharIter::Chars(r"\0".chars()),
         0x7 => CharIter::Chars(r"\a".chars()),
@@ -88,7 +88,7 @@ pub fn fmt_char(b: &u8) -> CharIter {
         0xc => CharIter::Chars(r"\f".chars()),
         0xd => CharIter::Chars(r"\r".chars()),
         0x5c => CharIter::Chars(r"\\".chars()),
-        c if 0x20 <= c && c < 0x7e => CharIter::Char(Some(c as char)),
+        c if (0x20..0x7e).contains(&c) => CharIter::Char(Some(c as char)),
         _ => {
             let v = vec!['\\', 'x', hex_char(b / 16), hex_char(b % 16)];
             CharIter::Vec(v.into_iter())
@@ -1,7 +1,8 @@
-use bwasm::{External, InitExpr, Internal, PAGE_SIZE};
-use wasmdbg::vm::Trap;
-use wasmdbg::{Breakpoint, Debugger};
+use wasmdbg::vm::{Trap, HISTORY_LIMIT};
+use wasmdbg::wasi::Handle;
+use wasmdbg::{Breakpoint, Debugger, External, InitExpr, Internal, ResizableLimits, VariableKind, PAGE_SIZE};
 
+use super::utils::get_debug_info;
 use super::{CmdArg, CmdResult, Command, Commands};
 
 pub fn add_cmds(commands: &mut Commands) {
@@ -34,6 +35,31 @@ pub fn add_cmds(commands: &mut Commands) {
             .add_subcommand(Command::new("memory", cmd_info_memory).description("Print memory section"))
             .add_subcommand(Command::new("globals", cmd_info_globals).description("Print globals"))
             .add_subcommand(Command::new("exports", cmd_info_exports).description("Print exports"))
+            .add_subcommand(
+                Command::new("fds", cmd_info_fds)
+                    .description("Print open WASI file descriptors")
+                    .requires_running(),
+            )
+            .add_subcommand(
+                Command::new("checkpoints", cmd_info_checkpoints)
+                    .description("Print checkpoints")
+                    .requires_file(),
+            )
+            .add_subcommand(
+                Command::new("record", cmd_info_record)
+                    .description("Print the status of execution recording")
+                    .requires_file(),
+            )
+            .add_subcommand(
+                Command::new("locals", cmd_info_locals)
+                    .description("Print the source variables in scope in the selected frame")
+                    .requires_running(),
+            )
+            .add_subcommand(
+                Command::new("args", cmd_info_args)
+                    .description("Print the arguments of the function of the selected frame")
+                    .requires_running(),
+            )
             .add_subcommand(Command::new("start", cmd_info_start).description("Print start section"))
             .add_subcommand(Command::new("elements", cmd_info_elements).description("Print element section"))
             .add_subcommand(Command::new("data", cmd_info_data).description("Print data section"))
@@ -78,13 +104,13 @@ fn cmd_info_file(dbg: &mut Debugger, _args: &[CmdArg]) -> CmdResult {
             println!(" -> {} function imports", func_count);
         }
         if table_count > 0 {
-            println!(" -> {} table imports (currently not supported)", func_count);
+            println!(" -> {} table imports", table_count);
         }
         if memory_count > 0 {
-            println!(" -> {} memory imports (currently not supported)", memory_count);
+            println!(" -> {} memory imports", memory_count);
         }
         if global_count > 0 {
-            println!(" -> {} global imports (currently not supported)", global_count);
+            println!(" -> {} global imports", global_count);
         }
     }
 
@@ -133,9 +159,25 @@ fn cmd_info_file(dbg: &mut Debugger, _args: &[CmdArg]) -> CmdResult {
         }
     }
 
+    if let Some(debug_info) = file.debug_info() {
+        print_count(debug_info.files().len(), "source file");
+        for path in debug_info.files() {
+            println!(" -> {}", path);
+        }
+    } else if let Some(error) = file.debug_info_error() {
+        println!("Failed to read debug info: {}", error);
+    }
+
     Ok(())
 }
 
+fn format_limits(limits: &ResizableLimits) -> String {
+    match limits.maximum() {
+        Some(maximum) => format!("{}..{}", limits.initial(), maximum),
+        None => format!("{}..", limits.initial()),
+    }
+}
+
 fn print_count(count: usize, name: &str) {
     match count {
         0 => println!("no {}s", name),
@@ -145,18 +187,49 @@ fn print_count(count: usize, name: &str) {
 }
 
 fn cmd_info_break(dbg: &mut Debugger, _args: &[CmdArg]) -> CmdResult {
-    let breakpoints = dbg.breakpoints()?;
-    ensure!(breakpoints.len() > 0, "No breakpoints");
+    let breakpoints_ref = dbg.breakpoints()?;
+    ensure!(!breakpoints_ref.is_empty(), "No breakpoints");
 
-    let mut breakpoints: Vec<(&u32, &Breakpoint)> = breakpoints.iter().collect();
-    breakpoints.sort_unstable_by(|(index1, _), (index2, _)| index1.cmp(index2));
+    let mut breakpoints: Vec<(&u32, &Breakpoint)> = breakpoints_ref.iter().collect();
+    breakpoints.sort_unstable_by_key(|(index1, _)| *index1);
 
-    println!("Num\tType\t\tWhere");
+    println!("Num\tType\t\tDisp\tEnb\tWhere");
     for (i, breakpoint) in breakpoints {
+        let disposition = if breakpoints_ref.is_temporary(*i) {
+            "del"
+        } else {
+            "keep"
+        };
+        let enabled = if breakpoints_ref.is_enabled(*i) { "y" } else { "n" };
         match breakpoint {
-            Breakpoint::Code(pos) => println!("{}\tbreakpoint\t{}\t{}", i, pos.func_index, pos.instr_index),
-            Breakpoint::Memory(trigger, addr) => println!("{}\twatchpoint\tMemory\t0x{:>08x}\t{}", i, addr, trigger),
-            Breakpoint::Global(trigger, index) => println!("{}\twatchpoint\tGlobal\t{}\t{}", i, index, trigger),
+            Breakpoint::Code(pos) => println!(
+                "{}\tbreakpoint\t{}\t{}\t{}\t{}",
+                i, disposition, enabled, pos.func_index, pos.instr_index
+            ),
+            Breakpoint::Memory(trigger, addr) => println!(
+                "{}\twatchpoint\t{}\t{}\tMemory\t0x{:>08x}\t{}",
+                i, disposition, enabled, addr, trigger
+            ),
+            Breakpoint::Global(trigger, index) => println!(
+                "{}\twatchpoint\t{}\t{}\tGlobal\t{}\t{}",
+                i, disposition, enabled, index, trigger
+            ),
+        }
+        if let Some(condition) = breakpoints_ref.condition(*i) {
+            println!("\tstop only if {}", condition);
+        }
+        match breakpoints_ref.hit_count(*i) {
+            0 => (),
+            1 => println!("\talready hit 1 time"),
+            count => println!("\talready hit {} times", count),
+        }
+        match breakpoints_ref.ignore_count(*i) {
+            0 => (),
+            1 => println!("\twill ignore next crossing"),
+            count => println!("\twill ignore next {} crossings", count),
+        }
+        for line in breakpoints_ref.commands(*i) {
+            println!("\t    {}", line);
         }
     }
 
@@ -186,30 +259,116 @@ fn cmd_info_imports(dbg: &mut Debugger, _args: &[CmdArg]) -> CmdResult {
         match entry.external() {
             External::Function(type_index) => {
                 let func_type = &module.types()[*type_index as usize];
+                let defined_str = if dbg.imports().has_function(entry.module(), entry.field()) {
+                    ""
+                } else {
+                    " (undefined)"
+                };
                 // TODO: group functions from the same module
-                println!("fn {}.{}{}", entry.module(), entry.field(), &func_type.to_string()[3..]);
+                println!(
+                    "fn {}.{}{}{}",
+                    entry.module(),
+                    entry.field(),
+                    &func_type.to_string()[3..],
+                    defined_str
+                );
+            }
+            External::Table(table_type) => {
+                // Undefined tables and memories are created by the debugger when the binary is started
+                let defined_str = if dbg.imports().has_table(entry.module(), entry.field()) {
+                    ""
+                } else {
+                    " (default)"
+                };
+                println!(
+                    "table {}.{}: {}{}",
+                    entry.module(),
+                    entry.field(),
+                    format_limits(table_type.limits()),
+                    defined_str
+                );
+            }
+            External::Memory(memory_type) => {
+                let defined_str = if dbg.imports().has_memory(entry.module(), entry.field()) {
+                    ""
+                } else {
+                    " (default)"
+                };
+                println!(
+                    "memory {}.{}: {}{}",
+                    entry.module(),
+                    entry.field(),
+                    format_limits(memory_type.limits()),
+                    defined_str
+                );
+            }
+            External::Global(global_type) => {
+                let defined_str = match dbg.imports().get_global(entry.module(), entry.field()) {
+                    Some(value) => format!(" = {}", value),
+                    None => String::from(" (undefined)"),
+                };
+                println!(
+                    "global {}.{}: {}{}{}",
+                    entry.module(),
+                    entry.field(),
+                    if global_type.is_mutable() { "mut " } else { "" },
+                    global_type.content_type(),
+                    defined_str
+                );
             }
-            External::Table(table_type) => println!("Table: {:?}", table_type),
-            External::Memory(memory_type) => println!("Memory: {:?}", memory_type),
-            External::Global(global_type) => println!("Global: {:?}", global_type),
         }
     }
     Ok(())
 }
 
+fn cmd_info_fds(dbg: &mut Debugger, _args: &[CmdArg]) -> CmdResult {
+    let wasi = dbg.wasi();
+    println!(
+        "{:<4} {:<17} {:>8}  {:<18} {:<18} Name",
+        "Fd", "Type", "Offset", "Rights", "Inheriting"
+    );
+    for (fd, entry) in wasi.fds().iter() {
+        let offset = entry
+            .offset()
+            .map_or_else(|| String::from("-"), |offset| offset.to_string());
+        let location = match entry.handle {
+            Handle::Dir(ref dir) => format!(" ({})", dir.fs.describe()),
+            Handle::File(_) => String::new(),
+        };
+        println!(
+            "{:<4} {:<17} {:>8}  {:#018x} {:#018x} {}{}",
+            fd,
+            entry.filetype().to_string(),
+            offset,
+            entry.rights_base,
+            entry.rights_inheriting,
+            entry.name,
+            location
+        );
+    }
+    Ok(())
+}
+
 fn cmd_info_functions(dbg: &mut Debugger, _args: &[CmdArg]) -> CmdResult {
     let functions = dbg.get_file()?.module().functions();
     let highest_index_len = functions.len().to_string().len();
     for (i, func) in functions.iter().enumerate() {
+        let signature = format_function(dbg, i as u32)?;
         if func.is_imported() {
-            println!(" {:>2$}: imported {}", i, func, highest_index_len);
+            println!(" {:>2$}: imported {}", i, signature, highest_index_len);
         } else {
-            println!(" {:>2$}: {}", i, func, highest_index_len);
+            println!(" {:>2$}: {}", i, signature, highest_index_len);
         }
     }
     Ok(())
 }
 
+// Formats the function like "fn NAME(PARAMS) -> RESULT" with its name demangled
+fn format_function(dbg: &Debugger, func_index: u32) -> anyhow::Result<String> {
+    let func_type = dbg.get_file()?.module().func(func_index).func_type().to_string();
+    Ok(format!("fn {}{}", dbg.function_name(func_index)?, &func_type[3..]))
+}
+
 fn cmd_info_tables(dbg: &mut Debugger, _args: &[CmdArg]) -> CmdResult {
     for (i, table) in dbg.get_file()?.module().tables().iter().enumerate() {
         println!(
@@ -266,7 +425,7 @@ fn cmd_info_exports(dbg: &mut Debugger, _args: &[CmdArg]) -> CmdResult {
     print_count(module.exports().len(), "export");
     for entry in module.exports() {
         match entry.internal() {
-            Internal::Function(index) => println!("Function {}: {}", index, &module.functions()[*index as usize]),
+            Internal::Function(index) => println!("Function {}: {}", index, format_function(dbg, *index)?),
             Internal::Table(index) => println!("Table {}", index),
             Internal::Memory(index) => println!("Memory {}", index),
             Internal::Global(index) => {
@@ -285,6 +444,88 @@ fn cmd_info_exports(dbg: &mut Debugger, _args: &[CmdArg]) -> CmdResult {
     Ok(())
 }
 
+fn cmd_info_checkpoints(dbg: &mut Debugger, _args: &[CmdArg]) -> CmdResult {
+    let checkpoints = dbg.checkpoints();
+    if checkpoints.is_empty() {
+        println!("No checkpoints");
+        return Ok(());
+    }
+    println!("Num\tDepth\tWhere");
+    for (index, checkpoint) in checkpoints.iter().enumerate() {
+        let ip = checkpoint.ip();
+        print!(
+            "{}\t{}\t{} <{}>",
+            index,
+            checkpoint.function_stack().len(),
+            ip,
+            dbg.function_name(ip.func_index)?
+        );
+        match checkpoint.trap() {
+            Some(trap) => println!(" ({})", trap),
+            None => println!(),
+        }
+    }
+    Ok(())
+}
+
+fn cmd_info_record(dbg: &mut Debugger, _args: &[CmdArg]) -> CmdResult {
+    if dbg.is_recording() {
+        println!("Execution is being recorded");
+        if let Ok(vm) = dbg.get_vm() {
+            println!("Recorded instructions: {} (limit {})", vm.history_len(), HISTORY_LIMIT);
+        }
+    } else {
+        println!("Execution is not being recorded");
+    }
+    if let Some(path) = dbg.trace_output() {
+        println!("Traces of new runs are recorded to \"{}\"", path.display());
+    }
+    if let Some(trace) = dbg.replay() {
+        println!(
+            "Replaying a trace of \"{}\" ({} instructions, {} host calls)",
+            trace.module_path(),
+            trace.step_count(),
+            trace.host_calls().len()
+        );
+        if let Ok(vm) = dbg.get_vm() {
+            println!("Replayed instructions: {}", vm.step_count());
+        }
+    }
+    Ok(())
+}
+
+fn cmd_info_locals(dbg: &mut Debugger, _args: &[CmdArg]) -> CmdResult {
+    print_variables(dbg, VariableKind::Local, "<no locals>")
+}
+
+fn cmd_info_args(dbg: &mut Debugger, _args: &[CmdArg]) -> CmdResult {
+    print_variables(dbg, VariableKind::Parameter, "<no arguments>")
+}
+
+// Prints the variables of the given kind, locals from the innermost scope outwards and arguments in order
+fn print_variables(dbg: &Debugger, kind: VariableKind, empty_message: &str) -> CmdResult {
+    let debug_info = get_debug_info(dbg)?;
+    let frame = dbg.selected_frame_state()?;
+    let mut variables: Vec<_> = debug_info
+        .variables(&frame)?
+        .into_iter()
+        .filter(|variable| variable.kind() == kind)
+        .collect();
+    if kind == VariableKind::Local {
+        variables.reverse();
+    }
+    if variables.is_empty() {
+        println!("{}", empty_message);
+    }
+    for variable in variables {
+        match debug_info.format_variable(&frame, &variable) {
+            Ok(value) => println!("{} = {}", variable.name(), value),
+            Err(error) => println!("{} = <{}>", variable.name(), error),
+        }
+    }
+    Ok(())
+}
+
 fn cmd_info_start(dbg: &mut Debugger, _args: &[CmdArg]) -> CmdResult {
     let module = dbg.get_file()?.module();
     if let Some(start_func_index) = module.start_func() {
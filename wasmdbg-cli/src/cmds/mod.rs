@@ -11,11 +11,17 @@ mod info;
 mod modify;
 mod parse;
 mod printing;
+mod profiling;
+mod source;
+mod tracing;
 mod utils;
 
 use format::Format;
 use parse::{parse_types, ParseCmdArg};
 
+pub use tracing::trace_to_file;
+pub use utils::{add_dir_mapping, function_completions, load_file, load_trace, terminal_output_sink};
+
 type CmdResult = anyhow::Result<()>;
 
 pub enum CmdArg {
@@ -45,13 +51,6 @@ impl CmdArg {
         }
     }
 
-    fn as_const(&self) -> &'static str {
-        match self {
-            CmdArg::Const(val) => val,
-            _ => panic!("Parsed arg has wrong type. Expected const, found {}", self.type_str()),
-        }
-    }
-
     fn as_u32(&self) -> u32 {
         match self {
             CmdArg::U32(val) => *val,
@@ -69,29 +68,22 @@ impl CmdArg {
 
 trait CmdArgOptionExt {
     fn as_u32_or(&self, default: u32) -> u32;
-    fn as_usize_or(&self, default: usize) -> usize;
 }
 
-impl<'a> CmdArgOptionExt for Option<&'a CmdArg> {
+impl CmdArgOptionExt for Option<&CmdArg> {
     fn as_u32_or(&self, default: u32) -> u32 {
         match self {
             Some(arg) => arg.as_u32(),
             None => default,
         }
     }
-
-    fn as_usize_or(&self, default: usize) -> usize {
-        match self {
-            Some(arg) => arg.as_usize(),
-            None => default,
-        }
-    }
 }
 
 pub enum CmdArgType {
     Str(&'static str),
     Fmt(&'static str),
     Path(&'static str),
+    Func(&'static str),
     Line(&'static str),
     Usize(&'static str),
     U32(&'static str),
@@ -214,7 +206,7 @@ impl Command {
     }
 
     pub fn has_name(&self, name: &str) -> bool {
-        self.name == name || self.aliases.iter().any(|&x| x == name)
+        self.name == name || self.aliases.contains(&name)
     }
 
     pub fn alias(mut self, alias: &'static str) -> Self {
@@ -271,13 +263,29 @@ impl Commands {
                 .alias("quit")
                 .description("Exit wasmdbg"),
         );
+        cmds.add(
+            Command::new("commands", cmd_unreachable)
+                .takes_args("[BREAKPOINT_INDEX:u32]")
+                .description("Set commands to run when a breakpoint is reached")
+                .help(
+                    "Read commands, one per line and terminated by a line containing just \"end\", that are run \
+                     each time breakpoint BREAKPOINT_INDEX (or the last breakpoint that was set) pauses execution.\n\n\
+                     If the first command is \"silent\" the usual message about reaching the breakpoint is not \
+                     printed. A command that continues execution ends the list if another breakpoint is reached.\n\
+                     An empty list removes all commands from the breakpoint.\n\n\
+                     Example:\n  commands 1\n  silent\n  locals\n  continue\n  end",
+                ),
+        );
 
         info::add_cmds(&mut cmds);
         utils::add_cmds(&mut cmds);
         modify::add_cmds(&mut cmds);
         context::add_cmds(&mut cmds);
+        source::add_cmds(&mut cmds);
         printing::add_cmds(&mut cmds);
         execution::add_cmds(&mut cmds);
+        tracing::add_cmds(&mut cmds);
+        profiling::add_cmds(&mut cmds);
 
         cmds
     }
@@ -287,12 +295,7 @@ impl Commands {
     }
 
     pub fn find_by_name(&self, name: &str) -> Option<&Command> {
-        for cmd in &self.commands {
-            if cmd.has_name(name) {
-                return Some(cmd);
-            }
-        }
-        None
+        self.commands.iter().find(|cmd| cmd.has_name(name))
     }
 }
 
@@ -304,9 +307,15 @@ impl std::ops::Deref for Commands {
     }
 }
 
+struct CommandBlock {
+    breakpoint: u32,
+    lines: Vec<String>,
+}
+
 pub struct CommandHandler {
     commands: Arc<Commands>,
     last_line: Option<String>,
+    block: Option<CommandBlock>,
 }
 
 impl CommandHandler {
@@ -314,10 +323,97 @@ impl CommandHandler {
         CommandHandler {
             commands,
             last_line: None,
+            block: None,
         }
     }
 
+    pub fn is_reading_block(&self) -> bool {
+        self.block.is_some()
+    }
+
     pub fn handle_line(&mut self, dbg: &mut Debugger, line: &str) -> bool {
+        if let Some(block) = &mut self.block {
+            match line.trim() {
+                "end" => {
+                    let block = self.block.take().unwrap();
+                    if let Err(error) = dbg.set_breakpoint_commands(block.breakpoint, block.lines) {
+                        println!("{}", error);
+                    }
+                }
+                "" => (),
+                line => block.lines.push(line.to_string()),
+            }
+            return false;
+        }
+
+        if self.execute_line(dbg, line) {
+            return true;
+        }
+        self.run_breakpoint_commands(dbg)
+    }
+
+    // Runs the commands of the breakpoints that paused execution, including those reached by the commands themselves
+    fn run_breakpoint_commands(&mut self, dbg: &mut Debugger) -> bool {
+        let last_line = self.last_line.clone();
+        let mut hit = dbg.take_breakpoint_hit();
+        while let Some(index) = hit.take() {
+            let commands = match dbg.breakpoints() {
+                Ok(breakpoints) => breakpoints.commands(index).to_vec(),
+                Err(_) => break,
+            };
+            let skip = usize::from(commands.first().is_some_and(|line| line == "silent"));
+            for line in &commands[skip..] {
+                if self.execute_line(dbg, line) {
+                    return true;
+                }
+                hit = dbg.take_breakpoint_hit();
+                if hit.is_some() {
+                    break;
+                }
+            }
+        }
+        self.last_line = last_line;
+        false
+    }
+
+    fn start_block(&mut self, dbg: &mut Debugger, args: &str) {
+        let breakpoint = match dbg.breakpoints() {
+            Ok(breakpoints) => match args.trim() {
+                "" => match breakpoints.iter().map(|(index, _)| *index).max() {
+                    Some(index) => index,
+                    None => {
+                        println!("No breakpoints");
+                        return;
+                    }
+                },
+                arg => match arg.parse() {
+                    Ok(index) if breakpoints.iter().any(|(i, _)| *i == index) => index,
+                    Ok(index) => {
+                        println!("No breakpoint with index {}", index);
+                        return;
+                    }
+                    Err(error) => {
+                        println!("Invalid argument: \"{}\". {}", arg, error);
+                        return;
+                    }
+                },
+            },
+            Err(error) => {
+                println!("{}", error);
+                return;
+            }
+        };
+        println!(
+            "Type commands for breakpoint {}, one per line.\nEnd with a line saying just \"end\".",
+            breakpoint
+        );
+        self.block = Some(CommandBlock {
+            breakpoint,
+            lines: Vec::new(),
+        });
+    }
+
+    fn execute_line(&mut self, dbg: &mut Debugger, line: &str) -> bool {
         let (cmd_name, args) = split_cmd_name_and_args(line);
 
         match cmd_name {
@@ -325,9 +421,10 @@ impl CommandHandler {
             "quit" | "exit" => {
                 return true;
             }
+            "commands" => self.start_block(dbg, args),
             "" => {
                 if let Some(last_line) = self.last_line.clone() {
-                    self.handle_line(dbg, &last_line);
+                    return self.execute_line(dbg, &last_line);
                 }
                 return false;
             }
@@ -1,24 +1,27 @@
 use std::convert::TryFrom;
+use std::fs::File;
 
-use bwasm::ValueType;
 use wasmdbg::value::Integer;
-use wasmdbg::Debugger;
+use wasmdbg::wasi::OutputSink;
+use wasmdbg::{Debugger, ValueType};
 
 use super::context;
+use super::parse::parse_launch_args;
+use super::utils::terminal_output_sink;
 use super::{CmdArg, CmdResult, Command, Commands};
 
 pub fn add_cmds(commands: &mut Commands) {
     commands.add(
         Command::new_subcommand("set")
-            .description("Modify various values of the currently running program")
-            .requires_running()
+            .description("Modify the currently running program or the settings")
             .add_subcommand(
                 Command::new("memory", cmd_set_memory)
                     .takes_args("ADDR:addr = VAL:str i8|i16|i32|i64|f32|f64")
                     .description("Modify the linear memory")
                     .help(
                         "Write the the value VAL to the address ADDR in the default linear memory.",
-                    ),
+                    )
+                    .requires_running(),
             )
             .add_subcommand(
                 Command::new("stack", cmd_set_stack)
@@ -26,23 +29,89 @@ pub fn add_cmds(commands: &mut Commands) {
                     .description("Modify a value on the stack")
                     .help(
                         "Replace the value at index INDEX on the stack. The type of the value will be unchanged to preserve wasm validation guarantees.",
-                    ),
+                    )
+                    .requires_running(),
             )
             .add_subcommand(
                 Command::new("local", cmd_set_local)
                     .takes_args("INDEX:usize = VAL:str")
                     .description("Modify the value of a local")
                     .help(
-                        "Replace the value of the local with index INDEX.",
-                    ),
+                        "Replace the value of the local with index INDEX in the function of the selected frame.",
+                    )
+                    .requires_running(),
             )
             .add_subcommand(
-                Command::new("stack", cmd_set_global)
+                Command::new("global", cmd_set_global)
                     .takes_args("INDEX:usize = VAL:str")
                     .description("Modify the value of a global")
                     .help(
                         "Replace the value of the global with index INDEX.",
+                    )
+                    .requires_running(),
+            )
+            .add_subcommand(
+                Command::new("args", cmd_set_args)
+                    .takes_args("[ARGS:line]")
+                    .description("Set the arguments passed to the program")
+                    .help(
+                        "Set the arguments passed to the program on the next \"run\" or \"start\". Arguments can be quoted and \"< FILE\" reads stdin from FILE. Without arguments, the argument list and stdin redirection are cleared.",
                     ),
+            )
+            .add_subcommand(
+                Command::new("output", cmd_set_output)
+                    .takes_args("terminal|buffer|FILE:path")
+                    .description("Set where the output of the program goes")
+                    .help(
+                        "Set where the program's stdout and stderr go: \"terminal\" prints it colored to the terminal, \"buffer\" captures it to be viewed with \"show output\" and otherwise it is written to the file FILE.",
+                    ),
+            )
+            .add_subcommand(
+                Command::new("env", cmd_set_env)
+                    .alias("environment")
+                    .takes_args("NAME=VALUE:line")
+                    .description("Set an environment variable for the program")
+                    .help(
+                        "Set the environment variable NAME to VALUE for the next \"run\" or \"start\".",
+                    ),
+            )
+            .add_subcommand(
+                Command::new("demangle", cmd_set_demangle)
+                    .takes_args("on|off|short")
+                    .description("Set how function names are demangled")
+                    .help(
+                        "Set whether Rust and C++ function names are shown demangled. \"short\" leaves out the hashes of Rust names and the parameter lists of C++ names. Functions can be referred to by their mangled and demangled names regardless of this setting.",
+                    ),
+            ),
+    );
+    commands.add(
+        Command::new_subcommand("unset")
+            .description("Remove launch settings")
+            .add_subcommand(
+                Command::new("env", cmd_unset_env)
+                    .alias("environment")
+                    .takes_args("[NAME:str]")
+                    .description("Remove an environment variable")
+                    .help("Remove the environment variable NAME or all environment variables if no NAME is given."),
+            ),
+    );
+    commands.add(
+        Command::new_subcommand("show")
+            .description("Show settings")
+            .add_subcommand(
+                Command::new("args", cmd_show_args).description("Show the arguments and stdin passed to the program"),
+            )
+            .add_subcommand(
+                Command::new("output", cmd_show_output)
+                    .description("Show the output captured with \"set output buffer\""),
+            )
+            .add_subcommand(
+                Command::new("env", cmd_show_env)
+                    .alias("environment")
+                    .description("Show the environment passed to the program"),
+            )
+            .add_subcommand(
+                Command::new("demangle", cmd_show_demangle).description("Show how function names are demangled"),
             ),
     );
 }
@@ -112,7 +181,8 @@ fn cmd_set_stack(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
 fn cmd_set_local(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
     let index = args[0].as_usize();
     let val = args[2].as_string();
-    let locals = dbg.get_vm_mut()?.locals_mut()?;
+    let depth = dbg.selected_frame()?.depth;
+    let locals = dbg.get_vm_mut()?.frame_locals_mut(depth)?;
 
     ensure!(index < locals.len(), "Index out of range");
 
@@ -142,3 +212,116 @@ fn cmd_set_global(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
 
     Ok(())
 }
+
+fn cmd_set_args(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
+    let line = args.first().map_or_else(String::new, CmdArg::as_string);
+    let launch_args = parse_launch_args(&line)?;
+    let config = dbg.launch_config_mut();
+    config.set_args(launch_args.args);
+    config.set_stdin(launch_args.stdin);
+    Ok(())
+}
+
+fn cmd_set_env(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
+    let line = args[0].as_string();
+    let (name, value) = match line.find('=') {
+        Some(index) => (line[..index].trim(), &line[index + 1..]),
+        None => match line.trim().find(char::is_whitespace) {
+            Some(index) => (&line.trim()[..index], line.trim()[index..].trim_start()),
+            None => (line.trim(), ""),
+        },
+    };
+    ensure!(!name.is_empty(), "Missing variable name");
+    dbg.launch_config_mut().set_env(name, value);
+    Ok(())
+}
+
+fn cmd_unset_env(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
+    match args.first() {
+        Some(name) => {
+            let name = name.as_string();
+            ensure!(
+                dbg.launch_config_mut().unset_env(&name),
+                "Environment variable \"{}\" is not set",
+                name
+            );
+        }
+        None => dbg.launch_config_mut().clear_env(),
+    }
+    Ok(())
+}
+
+fn cmd_show_args(dbg: &mut Debugger, _args: &[CmdArg]) -> CmdResult {
+    let config = dbg.launch_config();
+    if config.args().is_empty() {
+        println!("No arguments");
+    } else {
+        let args: Vec<String> = config
+            .args()
+            .iter()
+            .map(|arg| {
+                if arg.is_empty() || arg.contains(char::is_whitespace) {
+                    format!("{:?}", arg)
+                } else {
+                    arg.clone()
+                }
+            })
+            .collect();
+        println!("Arguments: {}", args.join(" "));
+    }
+    if let Some(stdin) = config.stdin() {
+        println!("Stdin: {}", stdin.display());
+    }
+    Ok(())
+}
+
+fn cmd_show_env(dbg: &mut Debugger, _args: &[CmdArg]) -> CmdResult {
+    let env = dbg.launch_config().env_strings();
+    if env.is_empty() {
+        println!("No environment variables");
+    }
+    for var in env {
+        println!("{}", var);
+    }
+    Ok(())
+}
+
+fn cmd_set_output(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
+    let sink = match args[0] {
+        CmdArg::Const("terminal") => terminal_output_sink(),
+        CmdArg::Const("buffer") => OutputSink::Buffer,
+        ref path => {
+            let path = shellexpand::tilde(&path.as_string()).into_owned();
+            let file = File::create(&path).map_err(|error| format_err!("Failed to open \"{}\": {}", path, error))?;
+            OutputSink::File(file)
+        }
+    };
+    dbg.set_output_sink(sink);
+    Ok(())
+}
+
+fn cmd_set_demangle(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
+    let style = args[0]
+        .as_string()
+        .parse()
+        .map_err(|_| format_err!("Invalid demangle style: {}", args[0].as_string()))?;
+    dbg.set_demangle_style(style);
+    Ok(())
+}
+
+fn cmd_show_demangle(dbg: &mut Debugger, _args: &[CmdArg]) -> CmdResult {
+    println!("Demangling is {}", dbg.demangle_style());
+    Ok(())
+}
+
+fn cmd_show_output(dbg: &mut Debugger, _args: &[CmdArg]) -> CmdResult {
+    let output = dbg.captured_output();
+    for (name, data) in &[("stdout", &output.stdout), ("stderr", &output.stderr)] {
+        println!("{} ({} bytes):", name, data.len());
+        if !data.is_empty() {
+            let text = String::from_utf8_lossy(data);
+            println!("{}", text.trim_end_matches('\n'));
+        }
+    }
+    Ok(())
+}
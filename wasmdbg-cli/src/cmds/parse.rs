@@ -42,6 +42,7 @@ impl fmt::Display for CmdArgType {
             CmdArgType::Str(name) => write!(f, "{}", name),
             CmdArgType::Fmt(name) => write!(f, "/{}", name),
             CmdArgType::Path(name) => write!(f, "{}", name),
+            CmdArgType::Func(name) => write!(f, "{}", name),
             CmdArgType::Line(name) => write!(f, "{}", name),
             CmdArgType::Usize(name) => write!(f, "{}", name),
             CmdArgType::U32(name) => write!(f, "{}", name),
@@ -74,8 +75,8 @@ impl From<&'static str> for CmdArgType {
     fn from(s: &'static str) -> Self {
         if s.starts_with('[') {
             CmdArgType::Opt(parse_types(&s[1..s.len() - 1]))
-        } else if s.ends_with("...") {
-            CmdArgType::List(Box::new(s[..s.len() - 3].into()))
+        } else if let Some(s) = s.strip_suffix("...") {
+            CmdArgType::List(Box::new(s.into()))
         } else if s.contains('|') {
             CmdArgType::Union(s.split('|').map(|a| a.into()).collect())
         } else if s.contains(':') {
@@ -84,14 +85,15 @@ impl From<&'static str> for CmdArgType {
             match split.next().unwrap() {
                 "str" => CmdArgType::Str(name),
                 "path" => CmdArgType::Path(name),
+                "func" => CmdArgType::Func(name),
                 "line" => CmdArgType::Line(name),
                 "usize" => CmdArgType::Usize(name),
                 "u32" => CmdArgType::U32(name),
                 "addr" => CmdArgType::Addr(name),
                 other => panic!("Invalid type in cmd arguments: {}", other),
             }
-        } else if s.starts_with('/') {
-            CmdArgType::Fmt(&s[1..])
+        } else if let Some(name) = s.strip_prefix('/') {
+            CmdArgType::Fmt(name)
         } else {
             CmdArgType::Const(s)
         }
@@ -112,7 +114,9 @@ pub trait ParseCmdArg {
 impl ParseCmdArg for CmdArgType {
     fn parse<'a>(&self, line: &'a str) -> anyhow::Result<(&'a str, Vec<CmdArg>)> {
         match self {
-            CmdArgType::Str(_) | CmdArgType::Path(_) => wrap(next_arg(line), |a| Ok(CmdArg::Str(a.to_string()))),
+            CmdArgType::Str(_) | CmdArgType::Path(_) | CmdArgType::Func(_) => {
+                wrap(next_arg(line), |a| Ok(CmdArg::Str(a.to_string())))
+            }
             CmdArgType::Line(_) => Ok(("", vec![CmdArg::Str(line.to_string())])),
             CmdArgType::Fmt(_) => {
                 if let Some('/') = line.trim_start().chars().next() {
@@ -128,7 +132,8 @@ impl ParseCmdArg for CmdArgType {
             CmdArgType::U32(_) => wrap(next_arg(line), |a| Ok(CmdArg::U32(a.parse()?))),
             CmdArgType::Addr(_) => wrap(next_arg(line), |a| Ok(CmdArg::U32(u32::from_str_with_radix(a)?))),
             CmdArgType::Const(val) => {
-                if line.trim_start().starts_with(*val) {
+                let line = line.trim_start();
+                if line.starts_with(*val) {
                     Ok((&line[val.len()..], vec![CmdArg::Const(val)]))
                 } else {
                     bail!("Expected \"{}\"", val);
@@ -166,16 +171,25 @@ impl ParseCmdArg for CmdArgType {
     }
 }
 
-impl ParseCmdArg for Vec<CmdArgType> {
+impl ParseCmdArg for [CmdArgType] {
     fn parse<'a>(&self, mut line: &'a str) -> anyhow::Result<(&'a str, Vec<CmdArg>)> {
         let mut result = Vec::new();
-        for arg_type in self {
+        for (i, arg_type) in self.iter().enumerate() {
             match arg_type.parse(line) {
                 Ok((rest, args)) => {
                     result.extend(args);
                     line = rest;
                 }
                 Err(error) => {
+                    // An optional argument that doesn't match may be followed by one that does
+                    if let CmdArgType::Opt(_) = arg_type {
+                        if let Ok((rest, args)) = self[i + 1..].parse(line) {
+                            if rest.trim().is_empty() {
+                                result.extend(args);
+                                return Ok((rest, result));
+                            }
+                        }
+                    }
                     if line.is_empty() {
                         bail!("Missing argument(s)");
                     }
@@ -187,6 +201,62 @@ impl ParseCmdArg for Vec<CmdArgType> {
     }
 }
 
+pub struct LaunchArgs {
+    pub args: Vec<String>,
+    pub stdin: Option<String>,
+}
+
+pub fn parse_launch_args(line: &str) -> anyhow::Result<LaunchArgs> {
+    let mut result = LaunchArgs {
+        args: Vec::new(),
+        stdin: None,
+    };
+    let mut tokens = tokenize_launch_args(line)?.into_iter();
+    while let Some(token) = tokens.next() {
+        match token {
+            LaunchToken::Arg(arg) => result.args.push(arg),
+            LaunchToken::Redirect => match tokens.next() {
+                Some(LaunchToken::Arg(path)) => result.stdin = Some(shellexpand::tilde(&path).into_owned()),
+                _ => bail!("Expected file name after '<'"),
+            },
+        }
+    }
+    Ok(result)
+}
+
+enum LaunchToken {
+    Arg(String),
+    Redirect,
+}
+
+fn tokenize_launch_args(line: &str) -> anyhow::Result<Vec<LaunchToken>> {
+    let mut tokens = Vec::new();
+    let mut current: Option<String> = None;
+    let mut quote = None;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"'), '\\') | (None, '\\') => current
+                .get_or_insert_with(String::new)
+                .push(chars.next().unwrap_or('\\')),
+            (Some(_), c) => current.get_or_insert_with(String::new).push(c),
+            (None, '"') | (None, '\'') => {
+                quote = Some(c);
+                current.get_or_insert_with(String::new);
+            }
+            (None, '<') if current.is_none() => tokens.push(LaunchToken::Redirect),
+            (None, c) if c.is_whitespace() => tokens.extend(current.take().map(LaunchToken::Arg)),
+            (None, c) => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    ensure!(quote.is_none(), "Unmatched quote in arguments");
+    tokens.extend(current.take().map(LaunchToken::Arg));
+    Ok(tokens)
+}
+
 fn wrap<'a, F>(arg: anyhow::Result<(&'a str, &str)>, f: F) -> anyhow::Result<(&'a str, Vec<CmdArg>)>
 where
     F: Fn(&str) -> anyhow::Result<CmdArg>,
@@ -295,3 +365,52 @@ impl TryFrom<char> for Format {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::parse_launch_args;
+
+    fn args(line: &str) -> Vec<String> {
+        parse_launch_args(line).unwrap().args
+    }
+
+    #[test]
+    fn launch_args_quoting() {
+        assert_eq!(args("a  b\tc"), ["a", "b", "c"]);
+        assert_eq!(
+            args(r#""hello world" 'single quoted'"#),
+            ["hello world", "single quoted"]
+        );
+        assert_eq!(args(r#"pre"fix"'ed' "" ''"#), ["prefixed", "", ""]);
+        assert_eq!(args(r#""it's" 'say "hi"'"#), ["it's", r#"say "hi""#]);
+        assert!(parse_launch_args(r#"a "b"#).is_err());
+        assert!(parse_launch_args("'a").is_err());
+    }
+
+    #[test]
+    fn launch_args_escapes() {
+        assert_eq!(args(r"a\ b c\\d"), ["a b", r"c\d"]);
+        assert_eq!(args(r#""x\"y" "a\\b""#), [r#"x"y"#, r"a\b"]);
+        // Backslashes are literal inside single quotes
+        assert_eq!(args(r"'a\b' \'"), [r"a\b", "'"]);
+        assert_eq!(args(r"end\"), [r"end\"]);
+    }
+
+    #[test]
+    fn launch_args_redirection() {
+        let launch_args = parse_launch_args("x < in.txt y").unwrap();
+        assert_eq!(launch_args.args, ["x", "y"]);
+        assert_eq!(launch_args.stdin.as_deref(), Some("in.txt"));
+
+        let launch_args = parse_launch_args(r#"<"my input" x"#).unwrap();
+        assert_eq!(launch_args.args, ["x"]);
+        assert_eq!(launch_args.stdin.as_deref(), Some("my input"));
+
+        let launch_args = parse_launch_args(r#"a<b "<" \<"#).unwrap();
+        assert_eq!(launch_args.args, ["a<b", "<", "<"]);
+        assert_eq!(launch_args.stdin, None);
+
+        assert!(parse_launch_args("x <").is_err());
+        assert!(parse_launch_args("< < in.txt").is_err());
+    }
+}
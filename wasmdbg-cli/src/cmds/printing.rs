@@ -1,6 +1,7 @@
-use wasmdbg::Debugger;
+use wasmdbg::{Debugger, DwarfError, Expr};
 
 use super::format::{fmt_char, Format};
+use super::utils::get_debug_info;
 use super::{CmdArg, CmdResult, Command, Commands};
 
 pub fn add_cmds(commands: &mut Commands) {
@@ -10,6 +11,65 @@ pub fn add_cmds(commands: &mut Commands) {
             .description("Examine memory")
             .requires_running(),
     );
+    commands.add(
+        Command::new("print", cmd_print)
+            .alias("p")
+            .takes_args("EXPR:line")
+            .description("Print the value of a variable or expression")
+            .help(
+                "Print the value of EXPR. EXPR can be a source variable with member accesses, indexing and \
+                 dereferences like \"data.values[2]\", \"*ptr\" or \"node->next\" if the binary contains DWARF \
+                 debug info, or an expression over the VM state like \"local 2 + load32(global 0)\".",
+            )
+            .requires_running(),
+    );
+    commands.add(
+        Command::new("ptype", cmd_ptype)
+            .takes_args("EXPR:line")
+            .description("Print the type of a variable or a type definition")
+            .help(
+                "Print the type of the source variable expression EXPR or the definition of the type named EXPR. \
+                 This requires the binary to contain DWARF debug info.",
+            )
+            .requires_file(),
+    );
+}
+
+fn cmd_print(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
+    let source = args[0].as_string();
+    let vm = dbg.get_vm()?;
+    let dwarf_error = match (
+        dbg.file().and_then(|file| file.debug_info()),
+        dbg.selected_frame_state(),
+    ) {
+        (Some(debug_info), Ok(frame)) => match debug_info.format_expression(&frame, &source) {
+            Ok(value) => {
+                println!("{}", value);
+                return Ok(());
+            }
+            Err(error @ DwarfError::UnknownVariable(_)) | Err(error @ DwarfError::InvalidExpression(_)) => Some(error),
+            Err(error) => return Err(error.into()),
+        },
+        _ => None,
+    };
+    match Expr::parse(&source) {
+        Ok(expr) => println!("{}", expr.eval(vm)?),
+        Err(error) => match dwarf_error {
+            Some(dwarf_error) => bail!("{}", dwarf_error),
+            None => bail!("{}", error),
+        },
+    }
+    Ok(())
+}
+
+fn cmd_ptype(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
+    let debug_info = get_debug_info(dbg)?;
+    let frame = dbg.selected_frame_state().ok();
+    println!(
+        "type = {}",
+        debug_info.describe_type(frame.as_ref(), &args[0].as_string())?
+    );
+    Ok(())
 }
 
 fn cmd_x(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
@@ -22,7 +82,10 @@ fn cmd_x(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
     let mut addr = address;
     for _ in 0..count {
         if let Format::Str = format {
-            let bytes: Vec<u8> = memory.data()[addr as usize..]
+            let bytes: Vec<u8> = memory
+                .data()
+                .get(addr as usize..)
+                .ok_or_else(|| format_err!("Out of range memory access at address {:#08x}", addr))?
                 .iter()
                 .cloned()
                 .take_while(|b| *b != 0)
@@ -0,0 +1,224 @@
+use std::fs;
+use std::io::{BufWriter, Write};
+
+use wasmdbg::Debugger;
+
+use super::{CmdArg, CmdResult, Command, Commands};
+
+pub fn add_cmds(commands: &mut Commands) {
+    commands.add(
+        Command::new_subcommand("profile")
+            .description("Profile the execution")
+            .add_subcommand(
+                Command::new("start", cmd_profile_start)
+                    .description("Start profiling")
+                    .help(
+                        "Start counting the executed instructions per function and call stack. Any previous \
+                         profile is discarded. Profiling stays enabled for later runs.",
+                    ),
+            )
+            .add_subcommand(Command::new("stop", cmd_profile_stop).description("Stop profiling"))
+            .add_subcommand(
+                Command::new("report", cmd_profile_report)
+                    .takes_args("[COUNT:usize]")
+                    .description("Print the profile")
+                    .help(
+                        "Print the executed instructions of the COUNT (default: all) functions that executed \
+                         the most instructions themselves. \"Self\" counts the instructions of the function \
+                         itself, \"Inclusive\" also counts the instructions of the functions it called.",
+                    ),
+            )
+            .add_subcommand(
+                Command::new("export", cmd_profile_export)
+                    .takes_args("collapsed|chrome|speedscope FILE:path")
+                    .description("Export the profile")
+                    .help(
+                        "Write the profile to FILE in one of the following formats:\n  \
+                         collapsed   Collapsed stacks as used by flamegraph tools\n  \
+                         chrome      Chrome trace event JSON, e.g. for chrome://tracing or Perfetto\n  \
+                         speedscope  Speedscope JSON\n\n\
+                         Time is measured in executed instructions.",
+                    ),
+            ),
+    );
+    commands.add(
+        Command::new_subcommand("coverage")
+            .description("Collect code coverage")
+            .add_subcommand(
+                Command::new("on", cmd_coverage_on)
+                    .alias("start")
+                    .description("Start collecting coverage")
+                    .help(
+                        "Start recording which instructions are executed. Coverage accumulates over all runs \
+                         and calls until it is reset. Instructions that were never executed are shown dimmed \
+                         in the disassembly.",
+                    ),
+            )
+            .add_subcommand(
+                Command::new("off", cmd_coverage_off)
+                    .alias("stop")
+                    .description("Stop collecting coverage"),
+            )
+            .add_subcommand(Command::new("reset", cmd_coverage_reset).description("Discard the collected coverage"))
+            .add_subcommand(
+                Command::new("report", cmd_coverage_report).description("Print the coverage of every function"),
+            )
+            .add_subcommand(
+                Command::new("export", cmd_coverage_export)
+                    .takes_args("lcov|json FILE:path")
+                    .description("Export the coverage")
+                    .help(
+                        "Write the coverage to FILE in one of the following formats:\n  \
+                         lcov  An lcov tracefile of the source files if the binary contains DWARF debug \
+                         info. Otherwise every instruction is one line of a listing of all function bodies in \
+                         order.\n  \
+                         json  The indices of the executed instructions of every function",
+                    ),
+            ),
+    );
+}
+
+fn cmd_profile_start(dbg: &mut Debugger, _args: &[CmdArg]) -> CmdResult {
+    dbg.start_profiling();
+    println!("Started profiling");
+    Ok(())
+}
+
+fn cmd_profile_stop(dbg: &mut Debugger, _args: &[CmdArg]) -> CmdResult {
+    dbg.stop_profiling()?;
+    println!("Stopped profiling");
+    Ok(())
+}
+
+fn cmd_profile_report(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
+    let profiler = dbg.profiler().ok_or_else(|| format_err!("No profile recorded"))?;
+    let total = profiler.instruction_count();
+    let percent = |count: u64| {
+        if total == 0 {
+            0.0
+        } else {
+            count as f64 * 100.0 / total as f64
+        }
+    };
+    let profiles = profiler.function_profiles();
+    let count = match args.first() {
+        Some(count) => count.as_usize(),
+        None => profiles.len(),
+    };
+
+    println!("Executed instructions: {}", total);
+    println!(
+        "{:>12} {:>7} {:>12} {:>7} {:>8}  Function",
+        "Self", "", "Inclusive", "", "Calls"
+    );
+    for profile in profiles.iter().take(count) {
+        println!(
+            "{:>12} {:>6.2}% {:>12} {:>6.2}% {:>8}  {} <{}>",
+            profile.self_count,
+            percent(profile.self_count),
+            profile.inclusive_count,
+            percent(profile.inclusive_count),
+            profile.calls,
+            profile.func_index,
+            dbg.function_name(profile.func_index)?
+        );
+    }
+    Ok(())
+}
+
+fn cmd_coverage_on(dbg: &mut Debugger, _args: &[CmdArg]) -> CmdResult {
+    dbg.start_coverage();
+    println!("Collecting coverage");
+    Ok(())
+}
+
+fn cmd_coverage_off(dbg: &mut Debugger, _args: &[CmdArg]) -> CmdResult {
+    dbg.stop_coverage()?;
+    println!("Stopped collecting coverage");
+    Ok(())
+}
+
+fn cmd_coverage_reset(dbg: &mut Debugger, _args: &[CmdArg]) -> CmdResult {
+    dbg.reset_coverage();
+    println!("Discarded coverage");
+    Ok(())
+}
+
+fn cmd_coverage_report(dbg: &mut Debugger, _args: &[CmdArg]) -> CmdResult {
+    let coverage = dbg.coverage().ok_or_else(|| format_err!("No coverage collected"))?;
+    let module = dbg.get_file()?.module();
+    let mut total = 0;
+    let mut total_covered = 0;
+    println!("{:>9} {:>13}  Function", "Covered", "Instructions");
+    for (func_index, func) in module.functions().iter().enumerate() {
+        if func.is_imported() {
+            continue;
+        }
+        let len = func.instructions().len();
+        let covered = coverage.covered_count(func_index as u32);
+        total += len;
+        total_covered += covered;
+        println!(
+            "{:>8.2}% {:>6}/{:<6}  {} <{}>",
+            covered as f64 * 100.0 / len as f64,
+            covered,
+            len,
+            func_index,
+            dbg.function_name(func_index as u32)?
+        );
+    }
+    if total > 0 {
+        println!(
+            "Total: {:.2}% ({}/{} instructions)",
+            total_covered as f64 * 100.0 / total as f64,
+            total_covered,
+            total
+        );
+    }
+    Ok(())
+}
+
+fn cmd_coverage_export(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
+    let coverage = dbg.coverage().ok_or_else(|| format_err!("No coverage collected"))?;
+    let file = dbg.get_file()?;
+    let path = args[1].as_string();
+    let mut output = create_output(&path)?;
+    match args[0] {
+        CmdArg::Const("lcov") => match file.debug_info() {
+            Some(debug_info) => coverage.write_lcov(file.module(), debug_info, &mut output)?,
+            None => coverage.write_lcov_listing(file.module(), file.file_path(), &mut output)?,
+        },
+        _ => coverage.write_json(file.module(), &mut output)?,
+    }
+    output.flush()?;
+    println!("Exported coverage to \"{}\"", path);
+    Ok(())
+}
+
+fn cmd_profile_export(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
+    let profiler = dbg.profiler().ok_or_else(|| format_err!("No profile recorded"))?;
+    let module = dbg.get_file()?.module();
+    let path = args[1].as_string();
+    let mut output = create_output(&path)?;
+    match args[0] {
+        CmdArg::Const("collapsed") => profiler.write_collapsed(module, &mut output)?,
+        CmdArg::Const("chrome") => profiler.write_chrome_trace(module, &mut output)?,
+        _ => profiler.write_speedscope(module, &mut output)?,
+    }
+    output.flush()?;
+    println!("Exported profile to \"{}\"", path);
+    if !matches!(args[0], CmdArg::Const("collapsed")) && profiler.timeline_end() < profiler.instruction_count() {
+        println!(
+            "The timeline ends after {} of {} instructions",
+            profiler.timeline_end(),
+            profiler.instruction_count()
+        );
+    }
+    Ok(())
+}
+
+fn create_output(path: &str) -> anyhow::Result<BufWriter<fs::File>> {
+    let file = fs::File::create(&*shellexpand::tilde(path))
+        .map_err(|error| format_err!("Failed to create \"{}\": {}", path, error))?;
+    Ok(BufWriter::new(file))
+}
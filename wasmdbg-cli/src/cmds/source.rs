@@ -0,0 +1,136 @@
+use std::collections::HashSet;
+use std::fs;
+
+use colored::*;
+use wasmdbg::{Breakpoint, DebugInfo, Debugger, SourceLocation};
+
+use super::utils::{get_debug_info, parse_source_location, resolve_function};
+use super::{CmdArg, CmdResult, Command, Commands};
+
+const LIST_DEFAULT_LINES: u32 = 10;
+
+pub fn add_cmds(commands: &mut Commands) {
+    commands.add(
+        Command::new("list", cmd_list)
+            .alias("l")
+            .takes_args("[LOCATION:str]")
+            .description("Print source code")
+            .help(
+                "Print the source code around LOCATION or the position of the selected frame if no LOCATION is given. \
+                 LOCATION can be FILE:LINE, a LINE of the current file or a function index, function name or \
+                 export name.\n\n\
+                 This requires the binary to contain DWARF debug info and the source files to be readable at \
+                 the paths recorded in it.",
+            )
+            .requires_file(),
+    );
+}
+
+fn cmd_list(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
+    let debug_info = get_debug_info(dbg)?;
+    let current = selected_location(dbg, debug_info);
+    let (file, line) = match args.first().map(CmdArg::as_string) {
+        None => match current {
+            Some(location) => (location.file, location.line),
+            None => bail!("No source information for the current position"),
+        },
+        Some(location) => {
+            if let Some((file, line)) = parse_source_location(&location) {
+                let file = debug_info
+                    .find_file(file)
+                    .ok_or_else(|| format_err!("No source file \"{}\"", file))?;
+                (file, line)
+            } else if let Ok(line) = location.parse() {
+                match current {
+                    Some(current) => (current.file, line),
+                    None => bail!("No current source file"),
+                }
+            } else {
+                let func_index = resolve_function(dbg, &location)?;
+                match debug_info.function_location(func_index) {
+                    Some(location) => (location.file, location.line),
+                    None => bail!("No source information for function {}", func_index),
+                }
+            }
+        }
+    };
+    let current_line = current
+        .filter(|current| current.file == file)
+        .map(|current| current.line);
+    let start = line.saturating_sub(LIST_DEFAULT_LINES / 2).max(1);
+    print_source(dbg, file, start, start + LIST_DEFAULT_LINES - 1, current_line)
+}
+
+fn selected_location<'a>(dbg: &Debugger, debug_info: &'a DebugInfo) -> Option<SourceLocation<'a>> {
+    let frame = dbg.selected_frame().ok()?;
+    debug_info.frame_location(frame.pos, frame.inline_depth).ok()?
+}
+
+// Prints the source of the selected frame's line with a few lines around it if source information is available
+pub fn print_source_context(dbg: &Debugger, lines_around: u32) -> CmdResult {
+    let location = dbg
+        .file()
+        .and_then(|file| file.debug_info())
+        .and_then(|debug_info| selected_location(dbg, debug_info));
+    match location {
+        Some(location) => {
+            let start = location.line.saturating_sub(lines_around).max(1);
+            let end = location.line + lines_around;
+            print_source(dbg, location.file, start, end, Some(location.line))
+        }
+        None => {
+            println!("<no source information>");
+            Ok(())
+        }
+    }
+}
+
+fn print_source(dbg: &Debugger, path: &str, start: u32, end: u32, current_line: Option<u32>) -> CmdResult {
+    let source = fs::read_to_string(path).map_err(|error| format_err!("Failed to read \"{}\": {}", path, error))?;
+    let lines: Vec<&str> = source.lines().collect();
+    ensure!(
+        start as usize <= lines.len(),
+        "Line {} is out of range for \"{}\" which has {} lines",
+        start,
+        path,
+        lines.len()
+    );
+    let end = end.min(lines.len() as u32);
+    let breakpoint_lines = breakpoint_lines(dbg, path);
+    let max_line_len = end.to_string().len();
+    for line in start..=end {
+        let text = lines[line as usize - 1];
+        let breakpoint_str = if breakpoint_lines.contains(&line) {
+            "*".red().to_string()
+        } else {
+            " ".to_string()
+        };
+        let line_str = format!("{:>1$}", line, max_line_len);
+        if current_line == Some(line) {
+            println!("=> {}{}  {}", breakpoint_str, line_str.green(), text);
+        } else {
+            println!("   {}{}  {}", breakpoint_str, line_str, text);
+        }
+    }
+    Ok(())
+}
+
+fn breakpoint_lines(dbg: &Debugger, path: &str) -> HashSet<u32> {
+    let debug_info = match dbg.file().and_then(|file| file.debug_info()) {
+        Some(debug_info) => debug_info,
+        None => return HashSet::new(),
+    };
+    let breakpoints = match dbg.breakpoints() {
+        Ok(breakpoints) => breakpoints,
+        Err(_) => return HashSet::new(),
+    };
+    breakpoints
+        .iter()
+        .filter_map(|(_, breakpoint)| match breakpoint {
+            Breakpoint::Code(pos) => debug_info.location(*pos),
+            _ => None,
+        })
+        .filter(|location| location.file == path)
+        .map(|location| location.line)
+        .collect()
+}
@@ -0,0 +1,189 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+
+use wasmdbg::{CallTracer, Debugger, InstructionTracer};
+
+use super::utils::{resolve_function, resolve_function_pattern};
+use super::{CmdArg, CmdResult, Command, Commands};
+
+pub fn add_cmds(commands: &mut Commands) {
+    commands.add(
+        Command::new_subcommand("trace")
+            .description("Trace the execution")
+            .add_subcommand(
+                Command::new("on", cmd_trace_on)
+                    .takes_args("[FILE:path]")
+                    .description("Start tracing executed instructions")
+                    .help(
+                        "Log every executed instruction to FILE or to the terminal if no FILE is given. Each line \
+                         contains the position of the instruction, the name of its function, the instruction and \
+                         the top of the value stack after executing it, separated by tabs.\n\n\
+                         Tracing stays enabled for later runs. Use \"trace functions\" to only trace some \
+                         functions.",
+                    ),
+            )
+            .add_subcommand(Command::new("off", cmd_trace_off).description("Stop tracing executed instructions"))
+            .add_subcommand(
+                Command::new("functions", cmd_trace_functions)
+                    .takes_args("[FUNCTIONS:func...]")
+                    .description("Only trace instructions of some functions")
+                    .help(
+                        "Only trace the instructions of FUNCTIONS or all functions if none are given. FUNCTIONS \
+                         can be function indices, function names or export names.",
+                    ),
+            )
+            .add_subcommand(
+                Command::new("calls", cmd_trace_calls)
+                    .takes_args("[OPTIONS:str...]")
+                    .description("Trace function calls")
+                    .help(
+                        "Log every function call with its arguments and result, indented by the call depth. \
+                         \"trace calls off\" stops tracing calls.\n\n\
+                         Options:\n  \
+                         --include PATTERN  Only trace calls to functions matching PATTERN (can be repeated)\n  \
+                         --exclude PATTERN  Don't trace calls to functions matching PATTERN (can be repeated)\n  \
+                         --depth N          Don't trace calls nested deeper than N\n  \
+                         FILE               Write the trace to FILE instead of the terminal\n\n\
+                         PATTERN can be a function index, name, export name or a glob pattern like \"alloc*\".",
+                    ),
+            )
+            .add_subcommand(
+                Command::new("imports", cmd_trace_imports)
+                    .takes_args("[OPTIONS:str...]")
+                    .description("Trace calls to imported functions")
+                    .help(
+                        "Like \"trace calls\" but only log calls to imported functions, e.g. WASI or other host \
+                         functions. Takes the same options. \"trace imports off\" stops tracing calls.",
+                    ),
+            ),
+    );
+}
+
+fn cmd_trace_on(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
+    let functions = dbg.instruction_tracer().and_then(|tracer| tracer.functions().cloned());
+    let mut tracer = match args.first() {
+        Some(path) => {
+            let path = path.as_string();
+            let tracer = InstructionTracer::new(create_output(&path)?);
+            println!("Tracing instructions to \"{}\"", path);
+            tracer
+        }
+        None => {
+            println!("Tracing instructions");
+            InstructionTracer::new(Box::new(io::stdout()))
+        }
+    };
+    tracer.set_functions(functions);
+    dbg.set_instruction_tracer(Some(tracer));
+    Ok(())
+}
+
+fn cmd_trace_off(dbg: &mut Debugger, _args: &[CmdArg]) -> CmdResult {
+    ensure!(dbg.instruction_tracer().is_some(), "Instructions are not being traced");
+    dbg.set_instruction_tracer(None);
+    println!("Stopped tracing instructions");
+    Ok(())
+}
+
+fn cmd_trace_functions(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
+    if args.is_empty() {
+        dbg.set_traced_functions(None)?;
+        println!("Tracing all functions");
+        return Ok(());
+    }
+    let functions = args
+        .iter()
+        .map(|arg| resolve_function(dbg, &arg.as_string()))
+        .collect::<anyhow::Result<HashSet<u32>>>()?;
+    let count = functions.len();
+    dbg.set_traced_functions(Some(functions))?;
+    println!("Tracing {} function(s)", count);
+    Ok(())
+}
+
+fn cmd_trace_calls(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
+    start_call_tracing(dbg, args, false)
+}
+
+fn cmd_trace_imports(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
+    start_call_tracing(dbg, args, true)
+}
+
+fn start_call_tracing(dbg: &mut Debugger, args: &[CmdArg], imports_only: bool) -> CmdResult {
+    let args: Vec<String> = args.iter().map(CmdArg::as_string).collect();
+    if let [arg] = args.as_slice() {
+        if arg == "off" {
+            ensure!(dbg.call_tracer().is_some(), "Calls are not being traced");
+            dbg.set_call_tracer(None);
+            println!("Stopped tracing calls");
+            return Ok(());
+        }
+    }
+
+    let mut include = None;
+    let mut exclude = HashSet::new();
+    let mut max_depth = None;
+    let mut path = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--include" => include
+                .get_or_insert_with(HashSet::new)
+                .extend(resolve_function_pattern(dbg, option_value(&mut args, arg)?)?),
+            "--exclude" => exclude.extend(resolve_function_pattern(dbg, option_value(&mut args, arg)?)?),
+            "--depth" => {
+                let value = option_value(&mut args, arg)?;
+                max_depth = Some(value.parse().map_err(|_| format_err!("Invalid depth: \"{}\"", value))?);
+            }
+            option if option.starts_with("--") => bail!("Unknown option: \"{}\"", option),
+            _ if path.is_some() => bail!("Too many arguments"),
+            _ => path = Some(arg),
+        }
+    }
+
+    let output = match path {
+        Some(path) => create_output(path)?,
+        None => Box::new(io::stdout()),
+    };
+    let mut tracer = CallTracer::new(output, imports_only);
+    tracer.set_include(include);
+    tracer.set_exclude(exclude);
+    tracer.set_max_depth(max_depth);
+    dbg.set_call_tracer(Some(tracer));
+    let kind = if imports_only {
+        "calls to imported functions"
+    } else {
+        "calls"
+    };
+    match path {
+        Some(path) => println!("Tracing {} to \"{}\"", kind, path),
+        None => println!("Tracing {}", kind),
+    }
+    Ok(())
+}
+
+fn option_value<'a>(args: &mut impl Iterator<Item = &'a String>, option: &str) -> anyhow::Result<&'a String> {
+    args.next()
+        .ok_or_else(|| format_err!("Missing value for \"{}\"", option))
+}
+
+// Used for the non-interactive --trace mode
+pub fn trace_to_file(dbg: &mut Debugger, path: &str, functions: &[&str]) -> CmdResult {
+    let mut tracer = InstructionTracer::new(create_output(path)?);
+    if !functions.is_empty() {
+        let functions = functions
+            .iter()
+            .map(|name| resolve_function(dbg, name))
+            .collect::<anyhow::Result<HashSet<u32>>>()?;
+        tracer.set_functions(Some(functions));
+    }
+    dbg.set_instruction_tracer(Some(tracer));
+    Ok(())
+}
+
+fn create_output(path: &str) -> anyhow::Result<Box<dyn Write>> {
+    let file = fs::File::create(&*shellexpand::tilde(path))
+        .map_err(|error| format_err!("Failed to create \"{}\": {}", path, error))?;
+    Ok(Box::new(BufWriter::new(file)))
+}
@@ -1,6 +1,11 @@
+use std::io::{self, Write};
 use std::process;
 
-use wasmdbg::Debugger;
+use colored::*;
+use regex::Regex;
+use wasmdbg::vm::CodePosition;
+use wasmdbg::wasi::{OutputSink, OutputStream};
+use wasmdbg::{DebugInfo, Debugger, Trace};
 
 use super::{CmdArg, CmdResult, Command, Commands};
 
@@ -8,9 +13,16 @@ pub fn add_cmds(commands: &mut Commands) {
     commands.add(
         Command::new("load", cmd_load)
             .alias("file")
-            .takes_args("FILE:path")
+            .takes_args("FILE:path [OPTIONS:str...]")
             .description("Load a wasm binary")
-            .help("Load the wasm binary FILE."),
+            .help(
+                "Load the wasm binary FILE.\n\n\
+                 Options:\n  \
+                 --replay TRACE    Replay the trace TRACE recorded with \"record trace\" in all runs. FILE \
+                 defaults to the binary the trace was recorded with\n  \
+                 --dir GUEST=HOST  Make the host directory HOST available to WASI as GUEST\n  \
+                 --memdir GUEST    Make an empty in-memory directory available to WASI as GUEST",
+            ),
     );
     commands.add(
         Command::new("python", cmd_python)
@@ -32,11 +44,224 @@ fn cmd_python(_dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
 }
 
 fn cmd_load(dbg: &mut Debugger, args: &[CmdArg]) -> CmdResult {
-    let file_path = &args[0].as_string();
+    let mut args: Vec<String> = args.iter().map(CmdArg::as_string).collect();
+    let trace = match args.iter().position(|arg| arg == "--replay") {
+        Some(index) => {
+            ensure!(index + 1 < args.len(), "Missing value for \"--replay\"");
+            let trace_path = args.remove(index + 1);
+            args.remove(index);
+            Some(load_trace(&trace_path)?)
+        }
+        None => None,
+    };
+    let file_path = match (args.first(), &trace) {
+        (Some(file_path), _) if !file_path.starts_with("--") => args.remove(0),
+        (_, Some(trace)) => trace.module_path().to_owned(),
+        _ => bail!("Missing file"),
+    };
+    if let Some(option) = apply_preopen_options(dbg, &args)?.first() {
+        bail!("Unknown option: \"{}\"", option);
+    }
+    load_file(dbg, &file_path, trace);
+    Ok(())
+}
+
+pub fn load_trace(path: &str) -> anyhow::Result<Trace> {
+    Trace::load(&*shellexpand::tilde(path)).map_err(|error| format_err!("Failed to load trace \"{}\": {}", path, error))
+}
+
+// Loads the binary and starts replaying the trace if one is given
+pub fn load_file(dbg: &mut Debugger, file_path: &str, trace: Option<Trace>) {
     if let Err(error) = dbg.load_file(file_path) {
         println!("{}", error);
-    } else {
-        println!("Loaded \"{}\"", file_path);
+        return;
     }
+    println!("Loaded \"{}\"", file_path);
+    if let Some(trace) = trace {
+        match dbg.set_replay(Some(trace)) {
+            Ok(()) => println!("Replaying trace"),
+            Err(error) => println!("{}", error),
+        }
+    }
+}
+
+// Applies the leading --dir and --memdir options and returns the remaining arguments
+pub fn apply_preopen_options<'a>(dbg: &mut Debugger, options: &'a [String]) -> anyhow::Result<&'a [String]> {
+    let mut cleared = false;
+    let mut rest = options;
+    loop {
+        let (option, value) = match rest {
+            [option, value, ..] if option == "--dir" || option == "--memdir" => (option, value),
+            [option] if option == "--dir" || option == "--memdir" => bail!("Missing value for \"{}\"", option),
+            [option, ..] if option == "--" => return Ok(&rest[1..]),
+            _ => return Ok(rest),
+        };
+        if !cleared {
+            dbg.clear_preopens();
+            cleared = true;
+        }
+        if option == "--dir" {
+            add_dir_mapping(dbg, value)?;
+        } else {
+            dbg.preopen_memory_dir(value);
+        }
+        rest = &rest[2..];
+    }
+}
+
+pub fn add_dir_mapping(dbg: &mut Debugger, mapping: &str) -> CmdResult {
+    let (guest_path, host_path) = match mapping.find('=') {
+        Some(index) => (&mapping[..index], &mapping[index + 1..]),
+        None => (mapping, mapping),
+    };
+    dbg.preopen_dir(guest_path, &shellexpand::tilde(host_path))?;
     Ok(())
 }
+
+// Guest output is colored to keep it apart from the debugger's own output
+pub fn terminal_output_sink() -> OutputSink {
+    OutputSink::Custom(Box::new(|stream, data| {
+        let text = String::from_utf8_lossy(data);
+        match stream {
+            OutputStream::Stdout => {
+                let mut stdout = io::stdout();
+                write!(stdout, "{}", text.green())?;
+                stdout.flush()
+            }
+            OutputStream::Stderr => write!(io::stderr(), "{}", text.red()),
+        }
+    }))
+}
+
+// Resolves a function index, function name or export name
+pub fn resolve_function(dbg: &Debugger, name: &str) -> anyhow::Result<u32> {
+    if let Ok(index) = name.parse() {
+        return Ok(index);
+    }
+    dbg.get_file()?
+        .find_function(name)
+        .ok_or_else(|| format_err!("No function named \"{}\"", name))
+}
+
+// Resolves a function like resolve_function or all functions whose names match a glob pattern
+pub fn resolve_function_pattern(dbg: &Debugger, pattern: &str) -> anyhow::Result<Vec<u32>> {
+    if !pattern.contains(['*', '?']) {
+        return Ok(vec![resolve_function(dbg, pattern)?]);
+    }
+    let regex = Regex::new(&glob_to_regex(pattern))?;
+    let mut func_indices: Vec<u32> = dbg
+        .get_file()?
+        .function_names()
+        .iter()
+        .filter(|(name, _)| regex.is_match(name))
+        .map(|(_, index)| *index)
+        .collect();
+    func_indices.sort_unstable();
+    func_indices.dedup();
+    ensure!(!func_indices.is_empty(), "No function matches \"{}\"", pattern);
+    Ok(func_indices)
+}
+
+pub fn glob_to_regex(pattern: &str) -> String {
+    let mut regex = String::from("^");
+    for c in pattern.chars() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    regex
+}
+
+// Resolves FUNCTION or FUNCTION+OFFSET where FUNCTION is anything accepted by resolve_function
+pub fn resolve_code_location(dbg: &Debugger, location: &str) -> anyhow::Result<CodePosition> {
+    let (name, offset) = split_offset(location);
+    Ok(CodePosition::new(resolve_function(dbg, name)?, offset.unwrap_or(0)))
+}
+
+// Splits off a +OFFSET suffix. Other '+' characters are part of the name, e.g. in "operator+".
+pub fn split_offset(location: &str) -> (&str, Option<u32>) {
+    if let Some(plus_index) = location.rfind('+') {
+        if let Ok(offset) = location[plus_index + 1..].parse() {
+            return (&location[..plus_index], Some(offset));
+        }
+    }
+    (location, None)
+}
+
+// Parses FILE:LINE. Function names and glob patterns can contain colons but don't end with :NUMBER.
+pub fn parse_source_location(location: &str) -> Option<(&str, u32)> {
+    let colon_index = location.rfind(':')?;
+    let line = location[colon_index + 1..].parse().ok()?;
+    match &location[..colon_index] {
+        "" => None,
+        file => Some((file, line)),
+    }
+}
+
+// Resolves FILE:LINE to the first line at or after LINE that has code and the start of that line in every
+// function containing it
+pub fn resolve_source_location(dbg: &Debugger, file: &str, line: u32) -> anyhow::Result<(u32, Vec<CodePosition>)> {
+    let debug_info = get_debug_info(dbg)?;
+    ensure!(debug_info.find_file(file).is_some(), "No source file \"{}\"", file);
+    debug_info
+        .find_line(file, line)
+        .ok_or_else(|| format_err!("No code at or after line {} of \"{}\"", line, file))
+}
+
+pub fn get_debug_info(dbg: &Debugger) -> anyhow::Result<&DebugInfo> {
+    let file = dbg.get_file()?;
+    if let Some(error) = file.debug_info_error() {
+        bail!("Failed to read debug info: {}", error);
+    }
+    file.debug_info()
+        .ok_or_else(|| format_err!("The binary contains no debug info"))
+}
+
+pub fn function_completions(dbg: &Debugger) -> Vec<String> {
+    let mut names: Vec<String> = match dbg.file() {
+        Some(file) => file.function_names().iter().map(|(name, _)| name.clone()).collect(),
+        None => Vec::new(),
+    };
+    names.sort_unstable();
+    names.dedup();
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use wasmdbg::vm::CodePosition;
+
+    use super::{resolve_code_location, resolve_function_pattern};
+    use crate::test_utils::load_wat;
+
+    #[test]
+    fn resolve_locations() {
+        let dbg = load_wat(
+            r#"(module
+                (func $main (export "run") nop)
+                (func $helper_one nop)
+                (func $helper_two nop nop)
+                (func $operator+ nop)
+                (func $operator+= nop))"#,
+        );
+        let resolve = |location| resolve_code_location(&dbg, location).ok();
+        assert_eq!(resolve("main"), Some(CodePosition::new(0, 0)));
+        assert_eq!(resolve("run"), Some(CodePosition::new(0, 0)));
+        assert_eq!(resolve("2"), Some(CodePosition::new(2, 0)));
+        assert_eq!(resolve("helper_two+1"), Some(CodePosition::new(2, 1)));
+        assert_eq!(resolve("run+1"), Some(CodePosition::new(0, 1)));
+        assert_eq!(resolve("operator+"), Some(CodePosition::new(3, 0)));
+        assert_eq!(resolve("operator++1"), Some(CodePosition::new(3, 1)));
+        assert_eq!(resolve("operator+="), Some(CodePosition::new(4, 0)));
+        assert_eq!(resolve("missing"), None);
+        assert_eq!(resolve("main+x"), None);
+
+        assert_eq!(resolve_function_pattern(&dbg, "helper_*").unwrap(), [1, 2]);
+        assert_eq!(resolve_function_pattern(&dbg, "operator+?").unwrap(), [4]);
+        assert_eq!(resolve_function_pattern(&dbg, "ru?").unwrap(), [0]);
+        assert!(resolve_function_pattern(&dbg, "none*").is_err());
+    }
+}
@@ -1,6 +1,7 @@
 #[macro_use]
 extern crate anyhow;
 
+use std::rc::Rc;
 use std::sync::Arc;
 
 use clap::{App, Arg};
@@ -8,6 +9,8 @@ use wasmdbg::Debugger;
 
 mod cmds;
 mod readline;
+#[cfg(test)]
+mod test_utils;
 mod utils;
 
 use cmds::{CommandHandler, Commands};
@@ -19,24 +22,114 @@ fn main() {
     let matches = App::new("wasmdbg")
         .version(VERSION)
         .arg(Arg::with_name("file").help("The wasm binary to debug"))
+        .arg(
+            Arg::with_name("dir")
+                .long("dir")
+                .value_name("GUEST=HOST")
+                .multiple(true)
+                .number_of_values(1)
+                .help("Make the host directory HOST available to WASI as GUEST"),
+        )
+        .arg(
+            Arg::with_name("trace")
+                .long("trace")
+                .value_name("FILE")
+                .requires("file")
+                .help("Run the binary without prompting and log every executed instruction to FILE"),
+        )
+        .arg(
+            Arg::with_name("trace-function")
+                .long("trace-function")
+                .value_name("FUNCTION")
+                .multiple(true)
+                .number_of_values(1)
+                .requires("trace")
+                .help("Only trace the instructions of FUNCTION"),
+        )
+        .arg(
+            Arg::with_name("record-trace")
+                .long("record-trace")
+                .value_name("TRACE")
+                .help("Record a trace of every run to TRACE"),
+        )
+        .arg(
+            Arg::with_name("replay")
+                .long("replay")
+                .value_name("TRACE")
+                .help("Replay TRACE. FILE defaults to the binary the trace was recorded with"),
+        )
+        .arg(
+            Arg::with_name("memdir")
+                .long("memdir")
+                .value_name("GUEST")
+                .multiple(true)
+                .number_of_values(1)
+                .help("Make an empty in-memory directory available to WASI as GUEST"),
+        )
         .get_matches();
 
     let mut dbg = Debugger::new();
+    dbg.set_output_sink(cmds::terminal_output_sink());
     let cmds = Arc::new(Commands::all());
-    let mut rl = Readline::new(Arc::clone(&cmds));
-    let mut cmd_handler = CommandHandler::new(cmds);
+    let mut cmd_handler = CommandHandler::new(Arc::clone(&cmds));
 
-    if let Some(file_path) = matches.value_of("file") {
-        if let Err(error) = dbg.load_file(file_path) {
+    for mapping in matches.values_of("dir").into_iter().flatten() {
+        if let Err(error) = cmds::add_dir_mapping(&mut dbg, mapping) {
             println!("{}", error);
-        } else {
-            println!("Loaded \"{}\"", file_path);
         }
     }
+    for guest_path in matches.values_of("memdir").into_iter().flatten() {
+        dbg.preopen_memory_dir(guest_path);
+    }
+
+    if let Some(path) = matches.value_of("record-trace") {
+        dbg.set_trace_output(Some(path.into()));
+    }
+
+    let trace = match matches.value_of("replay").map(cmds::load_trace) {
+        Some(Ok(trace)) => Some(trace),
+        Some(Err(error)) => {
+            println!("{}", error);
+            None
+        }
+        None => None,
+    };
+    match (matches.value_of("file"), trace) {
+        (Some(file_path), trace) => cmds::load_file(&mut dbg, file_path, trace),
+        (None, Some(trace)) => {
+            let file_path = trace.module_path().to_owned();
+            cmds::load_file(&mut dbg, &file_path, Some(trace))
+        }
+        (None, None) => (),
+    }
 
     cmd_handler.load_init_file(&mut dbg, ".wasmdbg_init");
 
-    while let Some(line) = rl.readline() {
+    if let Some(path) = matches.value_of("trace") {
+        let functions: Vec<&str> = matches.values_of("trace-function").into_iter().flatten().collect();
+        match cmds::trace_to_file(&mut dbg, path, &functions) {
+            Ok(()) => {
+                cmd_handler.handle_line(&mut dbg, "run");
+            }
+            Err(error) => println!("{}", error),
+        }
+        return;
+    }
+
+    let mut rl = Readline::new(cmds);
+    let mut completed_module = None;
+    loop {
+        // Only recompute the function names for completion when a different binary was loaded
+        let module = dbg.file().map(|file| Rc::as_ptr(file.module()));
+        if module != completed_module {
+            rl.set_function_names(cmds::function_completions(&dbg));
+            completed_module = module;
+        }
+        rl.set_block_prompt(cmd_handler.is_reading_block());
+        let line = match rl.readline() {
+            Some(line) => line,
+            None => break,
+        };
         if cmd_handler.handle_line(&mut dbg, &line) {
             break;
         }
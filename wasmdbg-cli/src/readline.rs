@@ -1,5 +1,5 @@
 use std::io;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
 use colored::*;
 
@@ -21,6 +21,7 @@ fn find_cmds<'a>(cmds: &'a Commands, prefix: &str) -> Vec<&'a Command> {
 
 struct MyCompleter {
     cmds: Arc<Commands>,
+    functions: Arc<Mutex<Vec<String>>>,
 }
 
 impl<Term: Terminal> Completer<Term> for MyCompleter {
@@ -33,17 +34,23 @@ impl<Term: Terminal> Completer<Term> for MyCompleter {
     ) -> Option<Vec<Completion>> {
         let line = prompter.buffer();
         let mut words = line[..start].split_whitespace();
-        complete(&self.cmds, curr_word, &mut words)
+        let functions = self.functions.lock().unwrap();
+        complete(&self.cmds, &functions, curr_word, &mut words)
     }
 }
 
-fn complete<'a, I>(cmds: &Commands, curr_word: &'a str, other_words: &mut I) -> Option<Vec<Completion>>
+fn complete<'a, I>(
+    cmds: &Commands,
+    functions: &[String],
+    curr_word: &'a str,
+    other_words: &mut I,
+) -> Option<Vec<Completion>>
 where
     I: Iterator<Item = &'a str> + Clone,
 {
     match other_words.next() {
         Some(word) => match cmds.find_by_name(word) {
-            Some(cmd) if cmd.is_subcommand() => complete(&cmd.subcommands, curr_word, other_words),
+            Some(cmd) if cmd.is_subcommand() => complete(&cmd.subcommands, functions, curr_word, other_words),
             Some(cmd) if cmd.name == "help" => {
                 if other_words.next().is_some() {
                     None
@@ -56,7 +63,7 @@ where
                     )
                 }
             }
-            Some(cmd) => complete_cmd_args(&cmd.args, curr_word, other_words),
+            Some(cmd) => complete_cmd_args(&cmd.args, functions, curr_word, other_words),
             _ => None,
         },
         None => Some(
@@ -70,6 +77,7 @@ where
 
 fn complete_cmd_arg<'a, I>(
     arg_type: &CmdArgType,
+    functions: &[String],
     curr_word: &'a str,
     other_words: &mut I,
 ) -> (bool, Option<Vec<Completion>>)
@@ -96,6 +104,18 @@ where
                 (true, Some(complete_path(curr_word)))
             }
         }
+        CmdArgType::Func(_) => {
+            if other_words.next().is_some() {
+                (true, None)
+            } else {
+                let completions = functions
+                    .iter()
+                    .filter(|name| name.starts_with(curr_word))
+                    .map(|name| Completion::simple(name.clone()))
+                    .collect();
+                (true, Some(completions))
+            }
+        }
 
         CmdArgType::Const(val) => {
             if let Some(word) = other_words.next() {
@@ -110,11 +130,11 @@ where
             let mut matches = Vec::new();
             for e in elements {
                 let mut clone = other_words.clone();
-                match complete_cmd_arg(e, curr_word, &mut clone) {
+                match complete_cmd_arg(e, functions, curr_word, &mut clone) {
                     (true, Some(result)) => matches.extend(result),
                     (true, None) => {
                         if matches.is_empty() {
-                            return complete_cmd_arg(e, curr_word, other_words);
+                            return complete_cmd_arg(e, functions, curr_word, other_words);
                         } else {
                             return (true, Some(matches));
                         }
@@ -125,14 +145,14 @@ where
             (true, Some(matches))
         }
         CmdArgType::List(_) => loop {
-            match complete_cmd_arg(arg_type, curr_word, other_words) {
+            match complete_cmd_arg(arg_type, functions, curr_word, other_words) {
                 (true, None) => (),
                 other => return other,
             }
         },
         CmdArgType::Opt(arg_types) => {
             for arg_type in arg_types {
-                match complete_cmd_arg(arg_type, curr_word, other_words) {
+                match complete_cmd_arg(arg_type, functions, curr_word, other_words) {
                     (true, None) => (),
                     other => return other,
                 }
@@ -144,6 +164,7 @@ where
 
 fn complete_cmd_args<'a, I>(
     arg_types: &[CmdArgType],
+    functions: &[String],
     curr_word: &'a str,
     other_words: &mut I,
 ) -> Option<Vec<Completion>>
@@ -151,7 +172,7 @@ where
     I: Iterator<Item = &'a str> + Clone,
 {
     for arg_type in arg_types {
-        match complete_cmd_arg(arg_type, curr_word, other_words) {
+        match complete_cmd_arg(arg_type, functions, curr_word, other_words) {
             (true, Some(result)) => return Some(result),
             (true, None) => (),
             (false, _) => return None,
@@ -162,13 +183,17 @@ where
 
 pub struct Readline {
     interface: Interface<DefaultTerminal>,
+    functions: Arc<Mutex<Vec<String>>>,
 }
 
 impl Readline {
     pub fn new(cmds: Arc<Commands>) -> Self {
         let interface = Interface::new("wasmdbg").unwrap();
-        interface.set_completer(Arc::new(MyCompleter { cmds }));
-        interface.set_prompt(&"wasmdbg> ".red().to_string()).unwrap();
+        let functions = Arc::new(Mutex::new(Vec::new()));
+        interface.set_completer(Arc::new(MyCompleter {
+            cmds,
+            functions: Arc::clone(&functions),
+        }));
 
         if let Err(error) = interface.load_history(get_history_file()) {
             if error.kind() != io::ErrorKind::NotFound {
@@ -176,7 +201,22 @@ impl Readline {
             }
         }
 
-        Readline { interface }
+        let readline = Readline { interface, functions };
+        readline.set_block_prompt(false);
+        readline
+    }
+
+    pub fn set_function_names(&self, names: Vec<String>) {
+        *self.functions.lock().unwrap() = names;
+    }
+
+    pub fn set_block_prompt(&self, in_block: bool) {
+        let prompt = if in_block {
+            String::from(">")
+        } else {
+            "wasmdbg> ".red().to_string()
+        };
+        self.interface.set_prompt(&prompt).unwrap();
     }
 
     pub fn readline(&mut self) -> Option<String> {
@@ -184,7 +224,7 @@ impl Readline {
             match self.interface.read_line() {
                 Ok(result) => match result {
                     ReadResult::Input(line) => {
-                        if line != "" {
+                        if !line.is_empty() {
                             self.interface.add_history_unique(line.clone());
                         }
                         return Some(line);
@@ -0,0 +1,19 @@
+use std::env;
+use std::fs;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use wasmdbg::Debugger;
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+pub fn load_wat(wat: &str) -> Debugger {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let path = env::temp_dir().join(format!("wasmdbg-cli-test-{}-{}.wasm", process::id(), id));
+    fs::write(&path, wat::parse_str(wat).unwrap()).unwrap();
+    let mut dbg = Debugger::new();
+    let result = dbg.load_file(path.to_str().unwrap());
+    fs::remove_file(&path).unwrap();
+    result.unwrap();
+    dbg
+}
@@ -3,6 +3,7 @@ use std::fmt;
 use std::iter;
 
 use crate::vm::CodePosition;
+use crate::Expr;
 
 #[derive(Clone, Copy)]
 pub enum BreakpointTrigger {
@@ -24,6 +25,13 @@ impl BreakpointTrigger {
             BreakpointTrigger::Read => false,
         }
     }
+    fn matches(self, write: bool) -> bool {
+        if write {
+            self.is_write()
+        } else {
+            self.is_read()
+        }
+    }
 }
 
 impl fmt::Display for BreakpointTrigger {
@@ -36,12 +44,33 @@ impl fmt::Display for BreakpointTrigger {
     }
 }
 
+#[derive(Clone, Copy)]
 pub enum Breakpoint {
     Code(CodePosition),
     Memory(BreakpointTrigger, u32),
     Global(BreakpointTrigger, u32),
 }
 
+struct BreakpointState {
+    condition: Option<Expr>,
+    enabled: bool,
+    temporary: bool,
+    hit_count: u32,
+    ignore_count: u32,
+}
+
+impl BreakpointState {
+    const fn new(temporary: bool) -> Self {
+        BreakpointState {
+            condition: None,
+            enabled: true,
+            temporary,
+            hit_count: 0,
+            ignore_count: 0,
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct Breakpoints {
     code: HashSet<CodePosition>,
@@ -50,6 +79,10 @@ pub struct Breakpoints {
     global_read: HashSet<u32>,
     global_write: HashSet<u32>,
     index_map: HashMap<u32, Breakpoint>,
+    states: HashMap<u32, BreakpointState>,
+    commands: HashMap<u32, Vec<String>>,
+    expired_commands: Option<(u32, Vec<String>)>,
+    last_hit: Option<u32>,
     next_index: u32,
 }
 
@@ -62,6 +95,10 @@ impl Breakpoints {
             global_read: HashSet::new(),
             global_write: HashSet::new(),
             index_map: HashMap::new(),
+            states: HashMap::new(),
+            commands: HashMap::new(),
+            expired_commands: None,
+            last_hit: None,
             next_index: 0,
         }
     }
@@ -75,53 +112,190 @@ impl Breakpoints {
     }
 
     pub fn find_code(&self, pos: CodePosition) -> Option<u32> {
-        if self.code.contains(&pos) {
-            for (index, breakpoint) in self {
-                if let Breakpoint::Code(break_pos) = breakpoint {
-                    if *break_pos == pos {
-                        return Some(*index);
-                    }
-                }
-            }
-        }
-        None
+        self.code_matches(pos).first().copied()
     }
 
     pub fn find_global(&self, global: u32, write: bool) -> Option<u32> {
+        self.global_matches(global, write).first().copied()
+    }
+
+    pub fn find_memory(&self, start: u32, len: u32, write: bool) -> Option<u32> {
+        self.memory_matches(start, len, write).first().copied()
+    }
+
+    // The indices of all breakpoints at pos, regardless of their conditions
+    pub fn code_matches(&self, pos: CodePosition) -> Vec<u32> {
+        if !self.code.contains(&pos) {
+            return Vec::new();
+        }
+        self.matches(|breakpoint| matches!(breakpoint, Breakpoint::Code(break_pos) if *break_pos == pos))
+    }
+
+    pub fn global_matches(&self, global: u32, write: bool) -> Vec<u32> {
         let found = if write {
             self.global_write.contains(&global)
         } else {
             self.global_read.contains(&global)
         };
-        if found {
-            for (index, breakpoint) in self {
-                if let Breakpoint::Global(_, break_global) = breakpoint {
-                    if *break_global == global {
-                        return Some(*index);
-                    }
-                }
-            }
+        if !found {
+            return Vec::new();
         }
-        None
+        self.matches(|breakpoint| match breakpoint {
+            Breakpoint::Global(trigger, break_global) => *break_global == global && trigger.matches(write),
+            _ => false,
+        })
     }
 
-    pub fn find_memory(&self, start: u32, len: u32, write: bool) -> Option<u32> {
+    pub fn memory_matches(&self, start: u32, len: u32, write: bool) -> Vec<u32> {
         let watchpoints = if write { &self.memory_write } else { &self.memory_read };
-        for &addr in watchpoints {
-            if start <= addr && addr < start + len {
-                for (index, breakpoint) in self {
-                    if let Breakpoint::Memory(_, break_addr) = breakpoint {
-                        if *break_addr == addr {
-                            return Some(*index);
-                        }
-                    }
-                }
+        let end = start.saturating_add(len);
+        if !watchpoints.iter().any(|&addr| start <= addr && addr < end) {
+            return Vec::new();
+        }
+        self.matches(|breakpoint| match breakpoint {
+            Breakpoint::Memory(trigger, addr) => start <= *addr && *addr < end && trigger.matches(write),
+            _ => false,
+        })
+    }
+
+    fn matches<F: Fn(&Breakpoint) -> bool>(&self, predicate: F) -> Vec<u32> {
+        let mut indices: Vec<u32> = self
+            .index_map
+            .iter()
+            .filter(|(_, breakpoint)| predicate(breakpoint))
+            .map(|(index, _)| *index)
+            .collect();
+        indices.sort_unstable();
+        indices
+    }
+
+    pub fn condition(&self, index: u32) -> Option<&Expr> {
+        self.states.get(&index)?.condition.as_ref()
+    }
+
+    pub fn set_condition(&mut self, index: u32, condition: Option<Expr>) -> bool {
+        match self.states.get_mut(&index) {
+            Some(state) => {
+                state.condition = condition;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn is_enabled(&self, index: u32) -> bool {
+        self.states.get(&index).is_some_and(|state| state.enabled)
+    }
+
+    pub fn set_enabled(&mut self, index: u32, enabled: bool) -> bool {
+        match self.states.get_mut(&index) {
+            Some(state) => {
+                state.enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn is_temporary(&self, index: u32) -> bool {
+        self.states.get(&index).is_some_and(|state| state.temporary)
+    }
+
+    pub fn hit_count(&self, index: u32) -> u32 {
+        self.states.get(&index).map_or(0, |state| state.hit_count)
+    }
+
+    pub fn ignore_count(&self, index: u32) -> u32 {
+        self.states.get(&index).map_or(0, |state| state.ignore_count)
+    }
+
+    pub fn set_ignore_count(&mut self, index: u32, count: u32) -> bool {
+        match self.states.get_mut(&index) {
+            Some(state) => {
+                state.ignore_count = count;
+                true
             }
+            None => false,
+        }
+    }
+
+    pub fn commands(&self, index: u32) -> &[String] {
+        match self.commands.get(&index) {
+            Some(commands) => commands,
+            None => match &self.expired_commands {
+                Some((expired_index, commands)) if *expired_index == index => commands,
+                _ => &[],
+            },
+        }
+    }
+
+    pub fn set_commands(&mut self, index: u32, commands: Vec<String>) -> bool {
+        if !self.index_map.contains_key(&index) {
+            return false;
+        }
+        if commands.is_empty() {
+            self.commands.remove(&index);
+        } else {
+            self.commands.insert(index, commands);
+        }
+        true
+    }
+
+    // The breakpoint that most recently paused execution, if it wasn't taken yet
+    pub fn take_last_hit(&mut self) -> Option<u32> {
+        self.last_hit.take()
+    }
+
+    // Counts a hit of a breakpoint whose condition holds and returns whether it would pause execution
+    pub(crate) fn register_hit(&mut self, index: u32) -> bool {
+        let state = match self.states.get_mut(&index) {
+            Some(state) => state,
+            None => return false,
+        };
+        state.hit_count += 1;
+        if state.ignore_count > 0 {
+            state.ignore_count -= 1;
+            return false;
+        }
+        true
+    }
+
+    // Records the breakpoint that paused execution. A temporary breakpoint is deleted but its commands are kept
+    // until the next one expires so they can still run.
+    pub(crate) fn trigger(&mut self, index: u32) {
+        if self.is_temporary(index) {
+            self.remove_breakpoint(index);
+            self.expired_commands = self.commands.remove(&index).map(|commands| (index, commands));
         }
-        None
+        self.last_hit = Some(index);
+    }
+
+    // Whether a hit would pause execution, without counting it
+    pub(crate) fn would_trigger(&self, index: u32) -> bool {
+        self.states.get(&index).is_some_and(|state| state.ignore_count == 0)
+    }
+
+    pub(crate) fn set_last_hit(&mut self, index: u32) {
+        self.last_hit = Some(index);
     }
 
     pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> u32 {
+        self.insert_breakpoint(breakpoint, false)
+    }
+
+    pub fn add_temporary_breakpoint(&mut self, breakpoint: Breakpoint) -> u32 {
+        self.insert_breakpoint(breakpoint, true)
+    }
+
+    fn insert_breakpoint(&mut self, breakpoint: Breakpoint, temporary: bool) -> u32 {
+        self.insert_location(breakpoint);
+        self.index_map.insert(self.next_index, breakpoint);
+        self.states.insert(self.next_index, BreakpointState::new(temporary));
+        self.next_index += 1;
+        self.next_index - 1
+    }
+
+    fn insert_location(&mut self, breakpoint: Breakpoint) {
         match breakpoint {
             Breakpoint::Code(position) => {
                 self.code.insert(position);
@@ -143,39 +317,30 @@ impl Breakpoints {
                 }
             }
         };
-
-        self.index_map.insert(self.next_index, breakpoint);
-        self.next_index += 1;
-        self.next_index - 1
     }
 
     pub fn delete_breakpoint(&mut self, index: u32) -> bool {
-        if let Some(breakpoint) = self.index_map.get(&index) {
-            match breakpoint {
-                Breakpoint::Code(position) => {
-                    self.code.remove(position);
-                }
-                Breakpoint::Memory(trigger, addr) => {
-                    if trigger.is_read() {
-                        self.memory_read.remove(addr);
-                    }
-                    if trigger.is_write() {
-                        self.memory_write.remove(addr);
-                    }
-                }
-                Breakpoint::Global(trigger, index) => {
-                    if trigger.is_read() {
-                        self.global_read.remove(index);
-                    }
-                    if trigger.is_write() {
-                        self.global_write.remove(index);
-                    }
-                }
-            };
-            self.index_map.remove(&index);
-            return true;
+        self.commands.remove(&index);
+        self.remove_breakpoint(index)
+    }
+
+    fn remove_breakpoint(&mut self, index: u32) -> bool {
+        if self.index_map.remove(&index).is_none() {
+            return false;
+        }
+        self.states.remove(&index);
+
+        // Other breakpoints may share the location of the deleted one
+        let breakpoints: Vec<Breakpoint> = self.index_map.values().cloned().collect();
+        self.code.clear();
+        self.memory_read.clear();
+        self.memory_write.clear();
+        self.global_read.clear();
+        self.global_write.clear();
+        for breakpoint in breakpoints {
+            self.insert_location(breakpoint);
         }
-        false
+        true
     }
 
     pub fn clear(&mut self) {
@@ -185,6 +350,10 @@ impl Breakpoints {
         self.global_read.clear();
         self.global_write.clear();
         self.index_map.clear();
+        self.states.clear();
+        self.commands.clear();
+        self.expired_commands = None;
+        self.last_hit = None;
     }
 
     pub fn iter(&self) -> <&Self as iter::IntoIterator>::IntoIter {
@@ -0,0 +1,273 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Write};
+
+use crate::vm::CodePosition;
+use crate::{DebugInfo, Module};
+
+// Remembers which instructions were executed as one bitmap per function indexed by the instruction index
+#[derive(Default)]
+pub struct Coverage {
+    functions: HashMap<u32, Vec<u64>>,
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Coverage::default()
+    }
+
+    pub(crate) fn hit(&mut self, module: &Module, pos: CodePosition) {
+        let func = module.func(pos.func_index);
+        if func.is_imported() {
+            return;
+        }
+        let bitmap = self
+            .functions
+            .entry(pos.func_index)
+            .or_insert_with(|| vec![0; func.instructions().len().div_ceil(64)]);
+        bitmap[pos.instr_index as usize / 64] |= 1 << (pos.instr_index % 64);
+    }
+
+    pub fn is_covered(&self, pos: CodePosition) -> bool {
+        match self.functions.get(&pos.func_index) {
+            Some(bitmap) => bitmap[pos.instr_index as usize / 64] & (1 << (pos.instr_index % 64)) != 0,
+            None => false,
+        }
+    }
+
+    pub fn covered_count(&self, func_index: u32) -> usize {
+        match self.functions.get(&func_index) {
+            Some(bitmap) => bitmap.iter().map(|word| word.count_ones() as usize).sum(),
+            None => 0,
+        }
+    }
+
+    fn covered_instructions(&self, func_index: u32, len: usize) -> Vec<u32> {
+        (0..len as u32)
+            .filter(|instr_index| self.is_covered(CodePosition::new(func_index, *instr_index)))
+            .collect()
+    }
+
+    // A source line is covered if any of its instructions was executed. Functions without line information
+    // are left out.
+    pub fn write_lcov(&self, module: &Module, debug_info: &DebugInfo, output: &mut dyn Write) -> io::Result<()> {
+        let mut files: BTreeMap<&str, LcovFile> = BTreeMap::new();
+        for (func_index, func) in module.functions().iter().enumerate() {
+            let func_index = func_index as u32;
+            let location = match debug_info.function_location(func_index) {
+                Some(location) if !func.is_imported() => location,
+                _ => continue,
+            };
+            let hit = self.is_covered(CodePosition::new(func_index, 0));
+            let functions = &mut files.entry(location.file).or_default().functions;
+            functions.push((location.line, func.name(), hit));
+            // Instructions of inlined functions can belong to other files
+            for instr_index in 0..func.instructions().len() as u32 {
+                let pos = CodePosition::new(func_index, instr_index);
+                if let Some(location) = debug_info.location(pos) {
+                    let lines = &mut files.entry(location.file).or_default().lines;
+                    *lines.entry(location.line).or_insert(false) |= self.is_covered(pos);
+                }
+            }
+        }
+        writeln!(output, "TN:")?;
+        for (path, file) in &files {
+            write_lcov_record(output, path, file)?;
+        }
+        Ok(())
+    }
+
+    // Without source information, every instruction is one line of a listing of all function bodies in order
+    pub fn write_lcov_listing(&self, module: &Module, module_path: &str, output: &mut dyn Write) -> io::Result<()> {
+        let mut line = 1;
+        let mut file = LcovFile::default();
+        for (func_index, func) in module.functions().iter().enumerate() {
+            if func.is_imported() {
+                continue;
+            }
+            let func_index = func_index as u32;
+            let hit = self.is_covered(CodePosition::new(func_index, 0));
+            file.functions.push((line, func.name(), hit));
+            for instr_index in 0..func.instructions().len() as u32 {
+                file.lines
+                    .insert(line, self.is_covered(CodePosition::new(func_index, instr_index)));
+                line += 1;
+            }
+        }
+        writeln!(output, "TN:")?;
+        write_lcov_record(output, module_path, &file)
+    }
+
+    pub fn write_json(&self, module: &Module, output: &mut dyn Write) -> io::Result<()> {
+        write!(output, "{{\"functions\":[")?;
+        let mut first = true;
+        for (func_index, func) in module.functions().iter().enumerate() {
+            if func.is_imported() {
+                continue;
+            }
+            if !first {
+                write!(output, ",")?;
+            }
+            first = false;
+            let func_index = func_index as u32;
+            let covered: Vec<String> = self
+                .covered_instructions(func_index, func.instructions().len())
+                .iter()
+                .map(u32::to_string)
+                .collect();
+            write!(
+                output,
+                "{{\"index\":{},\"name\":\"{}\",\"instructions\":{},\"covered\":[{}]}}",
+                func_index,
+                json_escape(func.name()),
+                func.instructions().len(),
+                covered.join(",")
+            )?;
+        }
+        writeln!(output, "]}}")
+    }
+}
+
+// The functions of a source file with their first line and whether they were called, and whether each line
+// with code was executed
+#[derive(Default)]
+struct LcovFile<'a> {
+    functions: Vec<(u32, &'a str, bool)>,
+    lines: BTreeMap<u32, bool>,
+}
+
+fn write_lcov_record(output: &mut dyn Write, path: &str, file: &LcovFile) -> io::Result<()> {
+    let LcovFile { functions, lines } = file;
+    writeln!(output, "SF:{}", path)?;
+    for (line, name, _) in functions {
+        writeln!(output, "FN:{},{}", line, name)?;
+    }
+    for (_, name, hit) in functions {
+        writeln!(output, "FNDA:{},{}", *hit as u32, name)?;
+    }
+    writeln!(output, "FNF:{}", functions.len())?;
+    writeln!(output, "FNH:{}", functions.iter().filter(|(_, _, hit)| *hit).count())?;
+    for (line, covered) in lines {
+        writeln!(output, "DA:{},{}", line, *covered as u32)?;
+    }
+    writeln!(output, "LF:{}", lines.len())?;
+    writeln!(output, "LH:{}", lines.values().filter(|covered| **covered).count())?;
+    writeln!(output, "end_of_record")
+}
+
+pub(crate) fn json_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::{load_wat, load_wat_with_lines};
+    use crate::vm::CodePosition;
+
+    #[test]
+    fn bitmap() {
+        // The instructions span two words of the bitmap
+        let mut dbg = load_wat(&format!("(module (func {}) (func))", "nop ".repeat(70)));
+        dbg.start_coverage();
+        dbg.call(0, &[]).unwrap();
+        let coverage = dbg.coverage().unwrap();
+        assert_eq!(coverage.covered_count(0), 71);
+        assert_eq!(coverage.covered_count(1), 0);
+        assert!(coverage.is_covered(CodePosition::new(0, 63)));
+        assert!(coverage.is_covered(CodePosition::new(0, 70)));
+        assert!(!coverage.is_covered(CodePosition::new(1, 0)));
+    }
+
+    #[test]
+    fn exports() {
+        let mut dbg = load_wat_with_lines(
+            r#"(module
+                (func $main
+                    i32.const 0
+                    if
+                        nop
+                    end)
+                (func $unused
+                    nop))"#,
+            &[
+                (CodePosition::new(0, 0), 1, true),
+                (CodePosition::new(0, 2), 2, true),
+                (CodePosition::new(0, 3), 3, true),
+                (CodePosition::new(1, 0), 5, true),
+            ],
+        );
+        dbg.start_coverage();
+        dbg.call(0, &[]).unwrap();
+        let coverage = dbg.coverage().unwrap();
+        let file = dbg.get_file().unwrap();
+
+        let mut output = Vec::new();
+        coverage
+            .write_lcov(file.module(), file.debug_info().unwrap(), &mut output)
+            .unwrap();
+        let lcov = [
+            "TN:",
+            "SF:main.c",
+            "FN:1,main",
+            "FN:5,unused",
+            "FNDA:1,main",
+            "FNDA:0,unused",
+            "FNF:2",
+            "FNH:1",
+            "DA:1,1",
+            "DA:2,0",
+            "DA:3,1",
+            "DA:5,0",
+            "LF:4",
+            "LH:2",
+            "end_of_record",
+        ];
+        assert_eq!(String::from_utf8(output).unwrap().lines().collect::<Vec<_>>(), lcov);
+
+        // Every instruction is a line in the listing
+        let mut output = Vec::new();
+        coverage
+            .write_lcov_listing(file.module(), "module.wasm", &mut output)
+            .unwrap();
+        let listing = [
+            "TN:",
+            "SF:module.wasm",
+            "FN:1,main",
+            "FN:6,unused",
+            "FNDA:1,main",
+            "FNDA:0,unused",
+            "FNF:2",
+            "FNH:1",
+            "DA:1,1",
+            "DA:2,1",
+            "DA:3,0",
+            "DA:4,1",
+            "DA:5,1",
+            "DA:6,0",
+            "DA:7,0",
+            "LF:7",
+            "LH:4",
+            "end_of_record",
+        ];
+        assert_eq!(String::from_utf8(output).unwrap().lines().collect::<Vec<_>>(), listing);
+
+        let mut output = Vec::new();
+        coverage.write_json(file.module(), &mut output).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            concat!(
+                r#"{"functions":[{"index":0,"name":"main","instructions":5,"covered":[0,1,3,4]},"#,
+                r#"{"index":1,"name":"unused","instructions":2,"covered":[]}]}"#,
+                "\n"
+            )
+        );
+    }
+}
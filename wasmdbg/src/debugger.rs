@@ -1,11 +1,18 @@
-use std::cell::Ref;
+use std::borrow::Cow;
+use std::cell::{Ref, RefCell};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
 
-use bwasm::{LoadError, Module};
 use thiserror::Error;
 
-use crate::vm::{CodePosition, InitError, Memory, Trap, VM};
-use crate::{Breakpoint, Breakpoints, File, Value};
+use crate::vm::{CodePosition, HostFunction, Imports, InitError, Memory, Snapshot, Trap, VM};
+use crate::wasi::{self, CapturedOutput, HostFile, HostFs, MemoryFs, OutputSink, Wasi};
+use crate::{
+    module_hash, Breakpoint, Breakpoints, CallTracer, Coverage, DebugInfo, DemangleStyle, Expr, File, FrameState,
+    InstructionTracer, LaunchConfig, LoadError, Module, Profiler, Trace, TraceError, TraceWriter, Value,
+};
 
 #[derive(Error, Clone, Debug)]
 pub enum DebuggerError {
@@ -21,21 +28,100 @@ pub enum DebuggerError {
     InvalidBreakpointPosition,
     #[error("Invalid global for watchpoint")]
     InvalidWatchpointGlobal,
+    #[error("No function with index {0}")]
+    InvalidEntry(u32),
+    #[error("Entry function {0} takes parameters. Use \"call\" to pass arguments to it")]
+    EntryTakesParameters(u32),
+    #[error("Failed to preopen \"{path}\": {reason}")]
+    InvalidPreopen { path: String, reason: String },
+    #[error("Failed to open \"{path}\" as stdin: {reason}")]
+    InvalidStdin { path: String, reason: String },
+    #[error("Failed to write trace \"{path}\": {reason}")]
+    InvalidTraceOutput { path: String, reason: String },
+    #[error("Failed to replay \"{path}\": {reason}")]
+    InvalidReplay { path: String, reason: String },
+    #[error("The trace was recorded with a different binary (\"{0}\")")]
+    TraceMismatch(String),
+    #[error("No checkpoint with index {0}")]
+    InvalidCheckpoint(u32),
+    #[error("No frame at level {0}")]
+    InvalidFrame(usize),
+    #[error("Execution is not being recorded")]
+    NotRecording,
+    #[error("{0}")]
+    Trap(Trap),
+    #[error("Instructions are not being traced")]
+    NotTracing,
+    #[error("The execution is not being profiled")]
+    NotProfiling,
+    #[error("Coverage is not being collected")]
+    NotCollectingCoverage,
     #[error("This feature is still unimplemented")]
     Unimplemented,
 }
 
 pub type DebuggerResult<T> = Result<T, DebuggerError>;
 
-#[derive(Default)]
+// A frame of the backtrace. Functions inlined into a function frame get frames of their own.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Frame {
+    // The current instruction in the innermost function frame and the call instruction in its callers
+    pub pos: CodePosition,
+    // The index of the function frame counted from the innermost one
+    pub depth: usize,
+    pub inline_depth: usize,
+}
+
 pub struct Debugger {
     file: Option<File>,
     vm: Option<VM>,
+    selected_frame: usize,
+    imports: Imports,
+    wasi: Rc<RefCell<Wasi>>,
+    launch_config: LaunchConfig,
+    recording: bool,
+    checkpoints: Vec<Snapshot>,
+    trace_output: Option<PathBuf>,
+    replay: Option<Rc<Trace>>,
+    instruction_tracer: Option<Rc<RefCell<InstructionTracer>>>,
+    call_tracer: Option<Rc<RefCell<CallTracer>>>,
+    profiler: Option<Rc<RefCell<Profiler>>>,
+    profiling: bool,
+    coverage: Option<Rc<RefCell<Coverage>>>,
+    collecting_coverage: bool,
+    demangle_style: DemangleStyle,
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Debugger::new()
+    }
 }
 
 impl Debugger {
-    pub const fn new() -> Self {
-        Debugger { file: None, vm: None }
+    pub fn new() -> Self {
+        let wasi = Rc::new(RefCell::new(Wasi::new()));
+        let mut imports = Imports::new();
+        wasi::add_to_imports(&mut imports, &wasi);
+        Debugger {
+            file: None,
+            vm: None,
+            selected_frame: 0,
+            imports,
+            wasi,
+            launch_config: LaunchConfig::new(),
+            recording: false,
+            checkpoints: Vec::new(),
+            trace_output: None,
+            replay: None,
+            instruction_tracer: None,
+            call_tracer: None,
+            profiler: None,
+            profiling: false,
+            coverage: None,
+            collecting_coverage: false,
+            demangle_style: DemangleStyle::default(),
+        }
     }
 
     pub fn file(&self) -> Option<&File> {
@@ -46,24 +132,139 @@ impl Debugger {
         self.vm.as_ref()
     }
 
+    pub const fn imports(&self) -> &Imports {
+        &self.imports
+    }
+
+    pub fn imports_mut(&mut self) -> &mut Imports {
+        &mut self.imports
+    }
+
+    pub fn add_host_function<F: HostFunction + 'static>(&mut self, module: &str, field: &str, function: F) {
+        self.imports.add_function(module, field, function);
+    }
+
+    pub fn wasi(&self) -> Ref<'_, Wasi> {
+        self.wasi.borrow()
+    }
+
+    pub const fn launch_config(&self) -> &LaunchConfig {
+        &self.launch_config
+    }
+
+    pub fn launch_config_mut(&mut self) -> &mut LaunchConfig {
+        &mut self.launch_config
+    }
+
+    pub fn set_output_sink(&mut self, sink: OutputSink) {
+        self.wasi.borrow().output().borrow_mut().set_sink(sink);
+    }
+
+    pub fn captured_output(&self) -> CapturedOutput {
+        self.wasi.borrow().output().borrow().captured().clone()
+    }
+
+    pub fn take_captured_output(&mut self) -> CapturedOutput {
+        self.wasi.borrow().output().borrow_mut().take_captured()
+    }
+
+    pub fn preopen_dir(&mut self, guest_path: &str, host_path: &str) -> DebuggerResult<()> {
+        let fs = HostFs::new(host_path).map_err(|error| DebuggerError::InvalidPreopen {
+            path: host_path.to_owned(),
+            reason: error.to_string(),
+        })?;
+        self.wasi.borrow_mut().add_preopen(guest_path, Rc::new(fs));
+        Ok(())
+    }
+
+    pub fn preopen_memory_dir(&mut self, guest_path: &str) -> Rc<MemoryFs> {
+        let fs = Rc::new(MemoryFs::new());
+        self.wasi.borrow_mut().add_preopen(guest_path, Rc::clone(&fs) as _);
+        fs
+    }
+
+    pub fn clear_preopens(&mut self) {
+        self.wasi.borrow_mut().clear_preopens();
+    }
+
     pub fn load_file(&mut self, file_path: &str) -> Result<(), LoadError> {
-        let module = Module::from_file(file_path)?;
+        let bytes = fs::read(file_path)?;
+        let module = Module::from_bytes(&bytes)?;
+        let debug_info = DebugInfo::load(&module, &bytes);
 
-        self.file = Some(File::new(file_path.to_owned(), module));
+        self.file = Some(File::with_debug_info(file_path.to_owned(), module, debug_info));
         self.vm = None;
+        self.checkpoints.clear();
+        self.replay = None;
+        // Profiles refer to the functions of the previous binary
+        self.profiler = None;
+        if self.profiling {
+            self.start_profiling();
+        }
+        self.reset_coverage();
 
         Ok(())
     }
 
-    pub fn backtrace(&self) -> DebuggerResult<Vec<CodePosition>> {
+    pub fn backtrace(&self) -> DebuggerResult<Vec<Frame>> {
         let vm = self.get_vm()?;
-        let mut backtrace = vec![vm.ip()];
+        let mut positions = vec![vm.ip()];
         for frame in vm.function_stack().iter().skip(1).rev() {
-            backtrace.push(frame.ret_addr);
+            // The return address is the instruction after the call
+            let ret_addr = frame.ret_addr;
+            positions.push(CodePosition::new(
+                ret_addr.func_index,
+                ret_addr.instr_index.saturating_sub(1),
+            ));
+        }
+        let debug_info = self.file.as_ref().and_then(File::debug_info);
+        let mut backtrace = Vec::with_capacity(positions.len());
+        for (depth, pos) in positions.into_iter().enumerate() {
+            let inline_depth = debug_info.map_or(0, |debug_info| debug_info.inline_depth(pos).unwrap_or(0));
+            for inline_depth in 0..=inline_depth {
+                backtrace.push(Frame {
+                    pos,
+                    depth,
+                    inline_depth,
+                });
+            }
         }
         Ok(backtrace)
     }
 
+    // The selection is reset to the innermost frame whenever execution continues
+    pub fn selected_frame_index(&self) -> usize {
+        self.selected_frame
+    }
+
+    pub fn selected_frame(&self) -> DebuggerResult<Frame> {
+        let backtrace = self.backtrace()?;
+        Ok(backtrace[self.selected_frame.min(backtrace.len() - 1)])
+    }
+
+    pub fn select_frame(&mut self, index: usize) -> DebuggerResult<Frame> {
+        let frame = *self.backtrace()?.get(index).ok_or(DebuggerError::InvalidFrame(index))?;
+        self.selected_frame = index;
+        Ok(frame)
+    }
+
+    pub fn frame_state(&self, frame: &Frame) -> DebuggerResult<FrameState<'_>> {
+        let vm = self.get_vm()?;
+        let locals = vm
+            .frame_locals(frame.depth)
+            .map_err(|_| DebuggerError::InvalidFrame(frame.depth))?;
+        Ok(FrameState {
+            vm,
+            pos: frame.pos,
+            locals,
+            inline_depth: frame.inline_depth,
+        })
+    }
+
+    pub fn selected_frame_state(&self) -> DebuggerResult<FrameState<'_>> {
+        self.frame_state(&self.selected_frame()?)
+    }
+
     pub fn globals(&self) -> DebuggerResult<&[Value]> {
         Ok(self.get_vm()?.globals())
     }
@@ -81,8 +282,22 @@ impl Debugger {
     }
 
     pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) -> DebuggerResult<u32> {
-        let file = self.get_file_mut()?;
-        match breakpoint {
+        self.check_breakpoint(&breakpoint)?;
+        Ok(self.get_file()?.breakpoints().borrow_mut().add_breakpoint(breakpoint))
+    }
+
+    pub fn add_temporary_breakpoint(&mut self, breakpoint: Breakpoint) -> DebuggerResult<u32> {
+        self.check_breakpoint(&breakpoint)?;
+        Ok(self
+            .get_file()?
+            .breakpoints()
+            .borrow_mut()
+            .add_temporary_breakpoint(breakpoint))
+    }
+
+    fn check_breakpoint(&self, breakpoint: &Breakpoint) -> DebuggerResult<()> {
+        let file = self.get_file()?;
+        match *breakpoint {
             Breakpoint::Code(pos) => {
                 if file
                     .module()
@@ -100,7 +315,39 @@ impl Debugger {
                 }
             }
         }
-        Ok(file.breakpoints().borrow_mut().add_breakpoint(breakpoint))
+        Ok(())
+    }
+
+    pub fn set_breakpoint_condition(&mut self, index: u32, condition: Option<Expr>) -> DebuggerResult<bool> {
+        Ok(self
+            .get_file()?
+            .breakpoints()
+            .borrow_mut()
+            .set_condition(index, condition))
+    }
+
+    pub fn set_breakpoint_enabled(&mut self, index: u32, enabled: bool) -> DebuggerResult<bool> {
+        Ok(self.get_file()?.breakpoints().borrow_mut().set_enabled(index, enabled))
+    }
+
+    pub fn set_breakpoint_ignore_count(&mut self, index: u32, count: u32) -> DebuggerResult<bool> {
+        Ok(self
+            .get_file()?
+            .breakpoints()
+            .borrow_mut()
+            .set_ignore_count(index, count))
+    }
+
+    pub fn set_breakpoint_commands(&mut self, index: u32, commands: Vec<String>) -> DebuggerResult<bool> {
+        Ok(self
+            .get_file()?
+            .breakpoints()
+            .borrow_mut()
+            .set_commands(index, commands))
+    }
+
+    pub fn take_breakpoint_hit(&mut self) -> Option<u32> {
+        self.file.as_ref()?.breakpoints().borrow_mut().take_last_hit()
     }
 
     pub fn delete_breakpoint(&mut self, index: u32) -> DebuggerResult<bool> {
@@ -112,12 +359,30 @@ impl Debugger {
         Ok(())
     }
 
-    pub fn run(&mut self) -> DebuggerResult<Trap> {
-        Ok(self.create_vm()?.run())
+    pub fn run(&mut self, entry: Option<u32>) -> DebuggerResult<Trap> {
+        let entry = self.resolve_entry(entry)?;
+        Ok(self.create_vm()?.run(entry))
     }
 
-    pub fn start(&mut self) -> DebuggerResult<Option<Trap>> {
-        Ok(self.create_vm()?.start().err())
+    pub fn start(&mut self, entry: Option<u32>) -> DebuggerResult<Option<Trap>> {
+        let entry = self.resolve_entry(entry)?;
+        Ok(self.create_vm()?.start(entry).err())
+    }
+
+    fn resolve_entry(&self, entry: Option<u32>) -> DebuggerResult<Option<u32>> {
+        let file = self.get_file()?;
+        let entry = match entry {
+            Some(index) => index,
+            None => return Ok(file.default_entry()),
+        };
+        let func = file
+            .module()
+            .get_func(entry)
+            .ok_or(DebuggerError::InvalidEntry(entry))?;
+        if !func.func_type().params().is_empty() {
+            return Err(DebuggerError::EntryTakesParameters(entry));
+        }
+        Ok(Some(entry))
     }
 
     pub fn call(&mut self, index: u32, args: &[Value]) -> DebuggerResult<Trap> {
@@ -129,31 +394,350 @@ impl Debugger {
         Ok(())
     }
 
+    pub fn checkpoints(&self) -> &[Snapshot] {
+        &self.checkpoints
+    }
+
+    pub fn add_checkpoint(&mut self) -> DebuggerResult<u32> {
+        let snapshot = self.get_vm()?.snapshot();
+        self.checkpoints.push(snapshot);
+        Ok(self.checkpoints.len() as u32 - 1)
+    }
+
+    // Checkpoints stay valid after the instance is reset, so this creates a new instance if necessary
+    pub fn restore_checkpoint(&mut self, index: u32) -> DebuggerResult<()> {
+        let snapshot = self
+            .checkpoints
+            .get(index as usize)
+            .ok_or(DebuggerError::InvalidCheckpoint(index))?
+            .clone();
+        self.ensure_vm()?.restore(snapshot).map_err(DebuggerError::Trap)
+    }
+
     pub fn continue_execution(&mut self) -> DebuggerResult<Trap> {
-        Ok(self.get_vm_mut()?.continue_execution())
+        Ok(self.get_executing_vm()?.continue_execution())
     }
 
     pub fn execute_step(&mut self) -> DebuggerResult<Option<Trap>> {
-        Ok(self.get_vm_mut()?.execute_step().err())
+        Ok(self.get_executing_vm()?.execute_step().err())
     }
 
     pub fn execute_step_over(&mut self) -> DebuggerResult<Option<Trap>> {
-        Ok(self.get_vm_mut()?.execute_step_over().err())
+        Ok(self.get_executing_vm()?.execute_step_over().err())
     }
 
     pub fn execute_step_out(&mut self) -> DebuggerResult<Option<Trap>> {
-        Ok(self.get_vm_mut()?.execute_step_out().err())
+        Ok(self.get_executing_vm()?.execute_step_out().err())
+    }
+
+    pub fn execute_line_step(&mut self) -> DebuggerResult<Option<Trap>> {
+        self.execute_line_step_internal(false)
+    }
+
+    pub fn execute_line_step_over(&mut self) -> DebuggerResult<Option<Trap>> {
+        self.execute_line_step_internal(true)
+    }
+
+    // Executes until the source line changes. Without line information for the current position, this executes a
+    // single instruction instead. Functions without line information are stepped through. Only the start of a
+    // statement counts as a new line, so rows without a line (line 0) or inside a statement don't stop stepping.
+    fn execute_line_step_internal(&mut self, over_calls: bool) -> DebuggerResult<Option<Trap>> {
+        self.selected_frame = 0;
+        let file = self.file.as_ref().ok_or(DebuggerError::NoFileLoaded)?;
+        let vm = self.vm.as_mut().ok_or(DebuggerError::NoRunningInstance)?;
+        let debug_info = file.debug_info();
+        let line_of = |vm: &VM, statements_only: bool| {
+            debug_info
+                .and_then(|debug_info| {
+                    if statements_only {
+                        debug_info.statement_location(vm.ip())
+                    } else {
+                        debug_info.location(vm.ip())
+                    }
+                })
+                .filter(|location| location.line != 0)
+                .map(|location| (location.file, location.line))
+        };
+        let mut line = match line_of(vm, false) {
+            Some(line) => line,
+            None if over_calls => return Ok(vm.execute_step_over().err()),
+            None => return Ok(vm.execute_step().err()),
+        };
+        let mut depth = vm.function_stack().len();
+        loop {
+            let result = if over_calls {
+                vm.execute_step_over()
+            } else {
+                vm.execute_step()
+            };
+            if let Err(trap) = result {
+                return Ok(Some(trap));
+            }
+            let new_depth = vm.function_stack().len();
+            // Returning lands in the middle of the line of the call
+            if new_depth < depth {
+                depth = new_depth;
+                if let Some(new_line) = line_of(vm, false) {
+                    line = new_line;
+                }
+                continue;
+            }
+            match line_of(vm, true) {
+                Some(new_line) if new_depth > depth || new_line != line => return Ok(None),
+                _ => (),
+            }
+        }
+    }
+
+    pub const fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    // Also applies to the current instance, whose history starts at the current state
+    pub fn set_recording(&mut self, recording: bool) {
+        self.recording = recording;
+        if let Some(vm) = &mut self.vm {
+            vm.set_recording(recording);
+        }
+    }
+
+    pub fn trace_output(&self) -> Option<&Path> {
+        self.trace_output.as_deref()
+    }
+
+    // Every new instance records its execution to the trace output. Setting it to None also stops the recording
+    // of the current instance.
+    pub fn set_trace_output(&mut self, path: Option<PathBuf>) {
+        if path.is_none() {
+            if let Some(vm) = &mut self.vm {
+                vm.set_trace_writer(None);
+            }
+        }
+        self.trace_output = path;
+    }
+
+    pub fn replay(&self) -> Option<&Rc<Trace>> {
+        self.replay.as_ref()
+    }
+
+    // Replays the trace in all new instances of the loaded binary, which has to be the one the trace was recorded with
+    pub fn set_replay(&mut self, trace: Option<Trace>) -> DebuggerResult<()> {
+        if let Some(trace) = &trace {
+            let file_path = self.get_file()?.file_path();
+            let bytes = fs::read(file_path).map_err(|error| DebuggerError::InvalidReplay {
+                path: file_path.to_owned(),
+                reason: error.to_string(),
+            })?;
+            if module_hash(&bytes) != trace.module_hash() {
+                return Err(DebuggerError::TraceMismatch(trace.module_path().to_owned()));
+            }
+        }
+        self.replay = trace.map(Rc::new);
+        self.vm = None;
+        Ok(())
+    }
+
+    pub const fn demangle_style(&self) -> DemangleStyle {
+        self.demangle_style
+    }
+
+    pub fn set_demangle_style(&mut self, style: DemangleStyle) {
+        self.demangle_style = style;
+        if let Some(tracer) = &self.instruction_tracer {
+            tracer.borrow_mut().set_demangle_style(style);
+        }
+        if let Some(tracer) = &self.call_tracer {
+            tracer.borrow_mut().set_demangle_style(style);
+        }
+    }
+
+    // The name of the function demangled according to the demangle style
+    pub fn function_name(&self, func_index: u32) -> DebuggerResult<Cow<'_, str>> {
+        Ok(self.get_file()?.function_name(func_index, self.demangle_style))
+    }
+
+    pub fn instruction_tracer(&self) -> Option<Ref<'_, InstructionTracer>> {
+        self.instruction_tracer.as_ref().map(|tracer| tracer.borrow())
+    }
+
+    pub fn set_instruction_tracer(&mut self, tracer: Option<InstructionTracer>) {
+        self.instruction_tracer = tracer.map(|mut tracer| {
+            tracer.set_demangle_style(self.demangle_style);
+            Rc::new(RefCell::new(tracer))
+        });
+        if let Some(vm) = &mut self.vm {
+            vm.set_instruction_tracer(self.instruction_tracer.clone());
+        }
+    }
+
+    pub fn set_traced_functions(&mut self, functions: Option<HashSet<u32>>) -> DebuggerResult<()> {
+        match &self.instruction_tracer {
+            Some(tracer) => {
+                tracer.borrow_mut().set_functions(functions);
+                Ok(())
+            }
+            None => Err(DebuggerError::NotTracing),
+        }
+    }
+
+    pub fn call_tracer(&self) -> Option<Ref<'_, CallTracer>> {
+        self.call_tracer.as_ref().map(|tracer| tracer.borrow())
+    }
+
+    pub fn set_call_tracer(&mut self, tracer: Option<CallTracer>) {
+        self.call_tracer = tracer.map(|mut tracer| {
+            tracer.set_demangle_style(self.demangle_style);
+            Rc::new(RefCell::new(tracer))
+        });
+        if let Some(vm) = &mut self.vm {
+            vm.set_call_tracer(self.call_tracer.clone());
+        }
+    }
+
+    pub const fn is_profiling(&self) -> bool {
+        self.profiling
+    }
+
+    // The profile stays available after profiling was stopped
+    pub fn profiler(&self) -> Option<Ref<'_, Profiler>> {
+        self.profiler.as_ref().map(|profiler| profiler.borrow())
+    }
+
+    pub fn start_profiling(&mut self) {
+        self.profiler = Some(Rc::new(RefCell::new(Profiler::new())));
+        self.profiling = true;
+        if let Some(vm) = &mut self.vm {
+            vm.set_profiler(self.profiler.clone());
+        }
+    }
+
+    pub fn stop_profiling(&mut self) -> DebuggerResult<()> {
+        if !self.profiling {
+            return Err(DebuggerError::NotProfiling);
+        }
+        self.profiling = false;
+        if let Some(vm) = &mut self.vm {
+            vm.set_profiler(None);
+        }
+        Ok(())
+    }
+
+    pub const fn is_collecting_coverage(&self) -> bool {
+        self.collecting_coverage
+    }
+
+    pub fn coverage(&self) -> Option<Ref<'_, Coverage>> {
+        self.coverage.as_ref().map(|coverage| coverage.borrow())
+    }
+
+    // Coverage accumulates over all runs and calls until it is reset
+    pub fn start_coverage(&mut self) {
+        let coverage = self
+            .coverage
+            .get_or_insert_with(|| Rc::new(RefCell::new(Coverage::new())));
+        if let Some(vm) = &mut self.vm {
+            vm.set_coverage(Some(Rc::clone(coverage)));
+        }
+        self.collecting_coverage = true;
+    }
+
+    pub fn stop_coverage(&mut self) -> DebuggerResult<()> {
+        if !self.collecting_coverage {
+            return Err(DebuggerError::NotCollectingCoverage);
+        }
+        self.collecting_coverage = false;
+        if let Some(vm) = &mut self.vm {
+            vm.set_coverage(None);
+        }
+        Ok(())
+    }
+
+    pub fn reset_coverage(&mut self) {
+        self.coverage = None;
+        if self.collecting_coverage {
+            self.start_coverage();
+        }
+    }
+
+    pub fn reverse_step(&mut self) -> DebuggerResult<Option<Trap>> {
+        Ok(self.get_recording_vm()?.reverse_step().err())
+    }
+
+    pub fn reverse_step_over(&mut self) -> DebuggerResult<Option<Trap>> {
+        Ok(self.get_recording_vm()?.reverse_step_over().err())
+    }
+
+    pub fn reverse_step_out(&mut self) -> DebuggerResult<Option<Trap>> {
+        Ok(self.get_recording_vm()?.reverse_step_out().err())
+    }
+
+    pub fn reverse_continue(&mut self) -> DebuggerResult<Trap> {
+        Ok(self.get_recording_vm()?.reverse_continue())
+    }
+
+    fn get_recording_vm(&mut self) -> DebuggerResult<&mut VM> {
+        let vm = self.get_executing_vm()?;
+        if vm.is_recording() {
+            Ok(vm)
+        } else {
+            Err(DebuggerError::NotRecording)
+        }
     }
 
     fn create_vm(&mut self) -> DebuggerResult<&mut VM> {
+        self.selected_frame = 0;
         let file = self.file.as_ref().ok_or(DebuggerError::NoFileLoaded)?;
         let module = Rc::clone(file.module());
         let breakpoints = Rc::clone(file.breakpoints());
-        self.vm = Some(VM::new(module, breakpoints).map_err(DebuggerError::InitError)?);
+        let program_name = Path::new(file.file_path())
+            .file_name()
+            .map_or_else(|| file.file_path().clone(), |name| name.to_string_lossy().into_owned());
+        let mut args = vec![program_name];
+        args.extend_from_slice(self.launch_config.args());
+        let stdin = match self.launch_config.stdin() {
+            Some(path) => Some(fs::File::open(path).map_err(|error| DebuggerError::InvalidStdin {
+                path: path.display().to_string(),
+                reason: error.to_string(),
+            })?),
+            None => None,
+        };
+        {
+            let mut wasi = self.wasi.borrow_mut();
+            wasi.reset(args, self.launch_config.env_strings());
+            if let Some(stdin) = stdin {
+                wasi.set_stdin(Box::new(HostFile::new(stdin)));
+            }
+        }
+        // Memory and table imports without an explicit definition get a fresh one, e.g. for --import-memory
+        let mut imports = self.imports.clone();
+        imports.add_default_definitions(&module);
+        let mut vm = VM::new(module, breakpoints, &imports).map_err(DebuggerError::InitError)?;
+        vm.set_recording(self.recording);
+        vm.set_replay(self.replay.clone());
+        vm.set_instruction_tracer(self.instruction_tracer.clone());
+        vm.set_call_tracer(self.call_tracer.clone());
+        if self.profiling {
+            vm.set_profiler(self.profiler.clone());
+        }
+        if self.collecting_coverage {
+            vm.set_coverage(self.coverage.clone());
+        }
+        if let Some(path) = &self.trace_output {
+            let writer = fs::read(file.file_path())
+                .map_err(TraceError::from)
+                .and_then(|bytes| TraceWriter::create(path, file.file_path(), module_hash(&bytes)))
+                .map_err(|error| DebuggerError::InvalidTraceOutput {
+                    path: path.display().to_string(),
+                    reason: error.to_string(),
+                })?;
+            vm.set_trace_writer(Some(writer));
+        }
+        self.vm = Some(vm);
         Ok(self.vm.as_mut().unwrap())
     }
 
     fn ensure_vm(&mut self) -> DebuggerResult<&mut VM> {
+        self.selected_frame = 0;
         if let Some(ref mut vm) = self.vm {
             Ok(vm)
         } else {
@@ -161,6 +745,11 @@ impl Debugger {
         }
     }
 
+    fn get_executing_vm(&mut self) -> DebuggerResult<&mut VM> {
+        self.selected_frame = 0;
+        self.get_vm_mut()
+    }
+
     pub fn get_vm(&self) -> DebuggerResult<&VM> {
         if let Some(ref vm) = self.vm {
             Ok(vm)
@@ -193,3 +782,56 @@ impl Debugger {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::load_wat_with_lines;
+    use crate::vm::CodePosition;
+
+    #[test]
+    fn line_steps_stop_at_statements() {
+        let mut dbg = load_wat_with_lines(
+            r#"(module
+                (func
+                    i32.const 1
+                    drop
+                    i32.const 2
+                    drop
+                    i32.const 3
+                    drop
+                    call 1
+                    nop)
+                (func
+                    nop
+                    nop))"#,
+            &[
+                (CodePosition::new(0, 0), 1, true),
+                // Compiler generated code without a line
+                (CodePosition::new(0, 1), 0, true),
+                // The middle of a statement
+                (CodePosition::new(0, 2), 2, false),
+                (CodePosition::new(0, 4), 2, true),
+                (CodePosition::new(0, 6), 3, true),
+                (CodePosition::new(0, 7), 3, false),
+                (CodePosition::new(1, 0), 10, false),
+                (CodePosition::new(1, 1), 11, true),
+            ],
+        );
+        let ip = |dbg: &crate::Debugger| dbg.get_vm().unwrap().ip();
+
+        dbg.start(Some(0)).unwrap();
+        assert!(dbg.execute_line_step().unwrap().is_none());
+        assert_eq!(ip(&dbg), CodePosition::new(0, 4));
+        assert!(dbg.execute_line_step().unwrap().is_none());
+        assert_eq!(ip(&dbg), CodePosition::new(0, 6));
+        assert!(dbg.execute_line_step().unwrap().is_none());
+        assert_eq!(ip(&dbg), CodePosition::new(1, 1));
+
+        dbg.start(Some(0)).unwrap();
+        dbg.execute_line_step_over().unwrap();
+        dbg.execute_line_step_over().unwrap();
+        assert_eq!(ip(&dbg), CodePosition::new(0, 6));
+        // The rest of line 3 after the call is not a new statement
+        assert!(dbg.execute_line_step_over().unwrap().is_some());
+    }
+}
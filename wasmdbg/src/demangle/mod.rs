@@ -0,0 +1,158 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+
+use cpp_demangle::{DemangleOptions, Symbol};
+
+// Substitutions let the demangled name grow exponentially with the length of the symbol
+const MAX_DEMANGLED_LEN: usize = 4096;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum DemangleStyle {
+    Off,
+    #[default]
+    On,
+    // Without Rust hashes and C++ parameter lists
+    Short,
+}
+
+impl FromStr for DemangleStyle {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(DemangleStyle::Off),
+            "on" => Ok(DemangleStyle::On),
+            "short" => Ok(DemangleStyle::Short),
+            _ => Err(()),
+        }
+    }
+}
+
+impl fmt::Display for DemangleStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DemangleStyle::Off => write!(f, "off"),
+            DemangleStyle::On => write!(f, "on"),
+            DemangleStyle::Short => write!(f, "short"),
+        }
+    }
+}
+
+// Demangles Rust (legacy and v0) and Itanium C++ symbols. Other names are returned unchanged.
+pub fn demangle(name: &str, style: DemangleStyle) -> Cow<'_, str> {
+    if style == DemangleStyle::Off {
+        return Cow::Borrowed(name);
+    }
+    if let Ok(demangled) = rustc_demangle::try_demangle(name) {
+        return Cow::Owned(match style {
+            DemangleStyle::Short => format!("{:#}", demangled),
+            _ => demangled.to_string(),
+        });
+    }
+    let options = match style {
+        DemangleStyle::Short => DemangleOptions::new().no_params().no_return_type(),
+        _ => DemangleOptions::new(),
+    };
+    let symbol = match Symbol::new(name) {
+        Ok(symbol) => symbol,
+        Err(_) => return Cow::Borrowed(name),
+    };
+    let mut demangled = BoundedString(String::new());
+    match symbol.structured_demangle(&mut demangled, &options) {
+        Ok(()) => Cow::Owned(demangled.0),
+        Err(_) => Cow::Borrowed(name),
+    }
+}
+
+struct BoundedString(String);
+
+impl fmt::Write for BoundedString {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.0.len() + s.len() > MAX_DEMANGLED_LEN {
+            return Err(fmt::Error);
+        }
+        self.0.push_str(s);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{demangle, DemangleStyle};
+
+    fn check(mangled: &str, full: &str, short: &str) {
+        assert_eq!(demangle(mangled, DemangleStyle::On), full);
+        assert_eq!(demangle(mangled, DemangleStyle::Short), short);
+        assert_eq!(demangle(mangled, DemangleStyle::Off), mangled);
+    }
+
+    fn unchanged(symbol: &str) {
+        check(symbol, symbol, symbol);
+    }
+
+    #[test]
+    fn rust() {
+        check(
+            "_ZN4core3fmt5write17h0123456789abcdefE",
+            "core::fmt::write::h0123456789abcdef",
+            "core::fmt::write",
+        );
+        check("_RNvCs1234_7mycrate3foo", "mycrate[3c1c0]::foo", "mycrate::foo");
+    }
+
+    #[test]
+    fn cpp() {
+        check("_Z3fooi", "foo(int)", "foo");
+        check("_ZN3foo3barEv", "foo::bar()", "foo::bar");
+        check("_ZNK3Foo3getEv", "Foo::get() const", "Foo::get");
+        check("_ZN3FooC2Ev", "Foo::Foo()", "Foo::Foo");
+        check("_ZN3FooD0Ev", "Foo::~Foo()", "Foo::~Foo");
+        check(
+            "_ZNSt6vectorIiSaIiEE9push_backERKi",
+            "std::vector<int, std::allocator<int> >::push_back(int const&)",
+            "std::vector<int, std::allocator<int> >::push_back",
+        );
+        check("_Z3maxIiET_S0_S0_", "int max<int>(int, int)", "max<int>");
+        check(
+            "_ZN12_GLOBAL__N_13fooEPFviEPA3_c",
+            "(anonymous namespace)::foo(void (*)(int), char (*) [3])",
+            "(anonymous namespace)::foo",
+        );
+        check("_ZplRK3VecS1_", "operator+(Vec const&, Vec const&)", "operator+");
+        check("_ZZ4mainE5count", "main::count", "main::count");
+        check("_ZL6helperv.cold", "helper() [clone .cold]", "helper");
+        check("_ZTV3Foo", "{vtable(Foo)}", "{vtable(Foo)}");
+        check(
+            "_ZN5Outer5InnerIcE3getIiEEPT_RKS1_",
+            "int* Outer::Inner<char>::get<int>(Outer::Inner<char> const&)",
+            "Outer::Inner<char>::get<int>",
+        );
+    }
+
+    #[test]
+    fn not_mangled() {
+        check("main", "main", "main");
+        check("_Zfoo", "_Zfoo", "_Zfoo");
+        check("func_12", "func_12", "func_12");
+    }
+
+    #[test]
+    fn hostile() {
+        unchanged("_Z18446744073709551615a");
+        unchanged("_Z1fIT18446744073709551615_E");
+        unchanged("_Z1fSZZZZZZZZZZZZZZZ_");
+        // Each of these nests far deeper than the stack allows without a limit
+        for chain in ["P", "R", "K", "M", "F", "A_", "Dp", "Z", "L", "IJ"] {
+            let symbol = format!("_Z1f{}i", chain.repeat(100_000));
+            unchanged(&symbol);
+        }
+        // Each template argument list repeats the previous name twice
+        let mut symbol = "_ZN1aIS_S_E".to_owned();
+        for id in "0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ".chars() {
+            symbol.push_str(&format!("IS{0}_S{0}_E", id));
+        }
+        symbol.push_str("Ev");
+        unchanged(&symbol);
+    }
+}
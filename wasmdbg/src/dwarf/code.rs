@@ -0,0 +1,220 @@
+use super::{DwarfError, DwarfResult};
+use crate::vm::CodePosition;
+use crate::Module;
+
+const CODE_SECTION_ID: u8 = 10;
+
+struct FunctionBody {
+    func_index: u32,
+    start: u64,
+    end: u64,
+    instructions: Vec<u64>,
+}
+
+// Maps between the byte offsets of instructions relative to the start of the code section, which DWARF uses as
+// addresses, and code positions. The module only keeps the parsed instructions so the code section is decoded again.
+pub(crate) struct CodeOffsets {
+    bodies: Vec<FunctionBody>,
+}
+
+impl CodeOffsets {
+    pub fn load(module: &Module, bytes: &[u8]) -> DwarfResult<Self> {
+        let import_count = module.functions().iter().filter(|func| func.is_imported()).count() as u32;
+        let code = match find_code_section(bytes)? {
+            Some(code) => code,
+            None => return Ok(CodeOffsets { bodies: Vec::new() }),
+        };
+        let mut reader = Reader::new(code);
+        let count = reader.u32()?;
+        let mut bodies = Vec::with_capacity(count as usize);
+        for body_index in 0..count {
+            let size = reader.u32()? as usize;
+            let start = reader.pos;
+            let end = start + size;
+            let mut body = Reader::new(code.get(..end).ok_or(DwarfError::InvalidCode)?);
+            body.pos = start;
+            for _ in 0..body.u32()? {
+                body.u32()?;
+                body.byte()?;
+            }
+            let mut instructions = Vec::new();
+            while body.pos < end {
+                instructions.push(body.pos as u64);
+                skip_instruction(&mut body)?;
+            }
+            let func_index = import_count + body_index;
+            match module.get_func(func_index) {
+                Some(func) if func.instructions().len() == instructions.len() => (),
+                _ => return Err(DwarfError::InvalidCode),
+            }
+            bodies.push(FunctionBody {
+                func_index,
+                start: start as u64,
+                end: end as u64,
+                instructions,
+            });
+            reader.pos = end;
+        }
+        Ok(CodeOffsets { bodies })
+    }
+
+    // Addresses between the start of a body and its first instruction (i.e. in the locals) map to the first one
+    pub fn position(&self, address: u64) -> Option<CodePosition> {
+        let index = self
+            .bodies
+            .partition_point(|body| body.start <= address)
+            .checked_sub(1)?;
+        let body = &self.bodies[index];
+        if address >= body.end {
+            return None;
+        }
+        let instr_index = body
+            .instructions
+            .partition_point(|offset| *offset <= address)
+            .saturating_sub(1);
+        Some(CodePosition::new(body.func_index, instr_index as u32))
+    }
+
+    pub fn address(&self, pos: CodePosition) -> Option<u64> {
+        let first = self.bodies.first()?.func_index;
+        let body = self.bodies.get(pos.func_index.checked_sub(first)? as usize)?;
+        body.instructions.get(pos.instr_index as usize).copied()
+    }
+}
+
+fn find_code_section(bytes: &[u8]) -> DwarfResult<Option<&[u8]>> {
+    let mut reader = Reader::new(bytes);
+    reader.pos = 8;
+    while reader.pos < bytes.len() {
+        let id = reader.byte()?;
+        let size = reader.u32()? as usize;
+        let start = reader.pos;
+        let payload = bytes.get(start..start + size).ok_or(DwarfError::InvalidCode)?;
+        if id == CODE_SECTION_ID {
+            return Ok(Some(payload));
+        }
+        reader.pos = start + size;
+    }
+    Ok(None)
+}
+
+// Skips over an MVP or multi-value instruction and its immediates
+fn skip_instruction(reader: &mut Reader) -> DwarfResult<()> {
+    match reader.byte()? {
+        // block, loop, if with a value type or a type index as signed LEB
+        0x02..=0x04 => reader.skip_leb()?,
+        // br, br_if, call, local.*, global.*
+        0x0c | 0x0d | 0x10 | 0x20..=0x24 => {
+            reader.u32()?;
+        }
+        // br_table
+        0x0e => {
+            for _ in 0..=reader.u32()? {
+                reader.u32()?;
+            }
+        }
+        // call_indirect
+        0x11 => {
+            reader.u32()?;
+            reader.byte()?;
+        }
+        // loads and stores
+        0x28..=0x3e => {
+            reader.u32()?;
+            reader.u32()?;
+        }
+        // memory.size, memory.grow
+        0x3f | 0x40 => {
+            reader.byte()?;
+        }
+        0x41 | 0x42 => reader.skip_leb()?,
+        0x43 => reader.skip(4)?,
+        0x44 => reader.skip(8)?,
+        // saturating truncations
+        0xfc => {
+            reader.u32()?;
+        }
+        _ => (),
+    }
+    Ok(())
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    const fn new(bytes: &'a [u8]) -> Self {
+        Reader { bytes, pos: 0 }
+    }
+
+    fn byte(&mut self) -> DwarfResult<u8> {
+        let byte = *self.bytes.get(self.pos).ok_or(DwarfError::InvalidCode)?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn skip(&mut self, count: usize) -> DwarfResult<()> {
+        if self.pos + count > self.bytes.len() {
+            return Err(DwarfError::InvalidCode);
+        }
+        self.pos += count;
+        Ok(())
+    }
+
+    fn skip_leb(&mut self) -> DwarfResult<()> {
+        while self.byte()? & 0x80 != 0 {}
+        Ok(())
+    }
+
+    fn u32(&mut self) -> DwarfResult<u32> {
+        let mut result = 0u32;
+        let mut shift = 0;
+        loop {
+            let byte = self.byte()?;
+            if shift < 32 {
+                result |= u32::from(byte & 0x7f) << shift;
+            }
+            if byte & 0x80 == 0 {
+                return Ok(result);
+            }
+            shift += 7;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CodeOffsets;
+    use crate::vm::CodePosition;
+    use crate::Module;
+
+    #[test]
+    fn multi_value_block_types() {
+        // Type indices of 64 and above take more than one byte
+        let types = "(type (func))".repeat(64);
+        let wat = format!(
+            r#"(module
+                {}
+                (type $pair (func (result i32 i32)))
+                (func (result i32)
+                    (block (type $pair) (result i32 i32)
+                        (i32.const 1)
+                        (i32.const 2))
+                    i32.add))"#,
+            types
+        );
+        let bytes = wat::parse_str(wat).unwrap();
+        let code = CodeOffsets::load(&Module::from_bytes(&bytes).unwrap(), &bytes).unwrap();
+        let addresses: Vec<_> = (0..6).map(|index| code.address(CodePosition::new(0, index))).collect();
+        // The body starts with its size and the number of locals, the block with its opcode and two bytes
+        assert_eq!(addresses, [Some(3), Some(6), Some(8), Some(10), Some(11), Some(12)]);
+        for (index, address) in addresses.iter().enumerate() {
+            assert_eq!(
+                code.position(address.unwrap()),
+                Some(CodePosition::new(0, index as u32))
+            );
+        }
+    }
+}
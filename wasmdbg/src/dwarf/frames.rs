@@ -0,0 +1,83 @@
+use gimli::AttributeValue;
+
+use super::lines::{file_path, SourceLocation};
+use super::{DebugInfo, DieRef, DwarfResult};
+use crate::vm::CodePosition;
+
+// Functions inlined into a function frame are shown as virtual frames. The frame at inline depth 0 is the innermost
+// inlined function and the one at the highest depth is the function the code belongs to.
+impl DebugInfo {
+    fn is_function_scope(&self, die: DieRef) -> DwarfResult<bool> {
+        let tag = self.entry_tag(die)?;
+        Ok(tag == gimli::DW_TAG_subprogram || tag == gimli::DW_TAG_inlined_subroutine)
+    }
+
+    // The number of inlined functions containing pos
+    pub fn inline_depth(&self, pos: CodePosition) -> DwarfResult<usize> {
+        let mut depth = 0;
+        for scope in self.scopes(pos)? {
+            if self.entry_tag(scope)? == gimli::DW_TAG_inlined_subroutine {
+                depth += 1;
+            }
+        }
+        Ok(depth)
+    }
+
+    // Returns the subprogram providing the frame base and the scopes belonging to the function at inline_depth
+    pub(super) fn frame_scopes(
+        &self,
+        pos: CodePosition,
+        inline_depth: usize,
+    ) -> DwarfResult<(Option<DieRef>, Vec<DieRef>)> {
+        let scopes = self.scopes(pos)?;
+        let mut starts = Vec::new();
+        for (index, scope) in scopes.iter().enumerate() {
+            if self.is_function_scope(*scope)? {
+                starts.push(index);
+            }
+        }
+        let index = match starts.len().checked_sub(inline_depth + 1) {
+            Some(index) => index,
+            None => return Ok((None, Vec::new())),
+        };
+        let end = starts.get(index + 1).copied().unwrap_or(scopes.len());
+        Ok((scopes.first().copied(), scopes[starts[index]..end].to_vec()))
+    }
+
+    pub fn frame_function(&self, pos: CodePosition, inline_depth: usize) -> DwarfResult<Option<String>> {
+        match self.frame_scopes(pos, inline_depth)?.1.first() {
+            Some(function) => self.attr_string_with_origin(*function, gimli::DW_AT_name),
+            None => Ok(None),
+        }
+    }
+
+    // Functions containing an inlined call are at the line of the call
+    pub fn frame_location(&self, pos: CodePosition, inline_depth: usize) -> DwarfResult<Option<SourceLocation<'_>>> {
+        if inline_depth == 0 {
+            return Ok(self.location(pos));
+        }
+        match self.frame_scopes(pos, inline_depth - 1)?.1.first() {
+            Some(inlined) => self.call_location(*inlined),
+            None => Ok(None),
+        }
+    }
+
+    fn call_location(&self, inlined: DieRef) -> DwarfResult<Option<SourceLocation<'_>>> {
+        let file_index = match self.attr(inlined, gimli::DW_AT_call_file)? {
+            Some(AttributeValue::FileIndex(index)) => index,
+            _ => return Ok(None),
+        };
+        let line = self.attr_udata(inlined, gimli::DW_AT_call_line)?.unwrap_or(0);
+        let column = self.attr_udata(inlined, gimli::DW_AT_call_column)?.unwrap_or(0);
+        let unit = &self.units[inlined.unit];
+        let header = match &unit.line_program {
+            Some(program) => program.header(),
+            None => return Ok(None),
+        };
+        let path = match header.file(file_index) {
+            Some(file) => file_path(&self.dwarf, unit, header, file)?,
+            None => return Ok(None),
+        };
+        Ok(self.lines.source_location(&path, line as u32, column as u32))
+    }
+}
@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use gimli::{ColumnType, Dwarf, FileEntry, LineProgramHeader, Reader as _, Unit};
+
+use super::code::CodeOffsets;
+use super::{DwarfReader, DwarfResult};
+use crate::vm::CodePosition;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SourceLocation<'a> {
+    pub file: &'a str,
+    pub line: u32,
+    pub column: u32,
+}
+
+impl fmt::Display for SourceLocation<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.file, self.line)
+    }
+}
+
+struct LineRow {
+    pos: CodePosition,
+    file: usize,
+    line: u32,
+    column: u32,
+    is_stmt: bool,
+}
+
+// The rows of all line programs sorted by code position. Every row covers the instructions up to the next row
+// of the same function.
+pub(crate) struct LineTable {
+    files: Vec<String>,
+    rows: Vec<LineRow>,
+}
+
+impl LineTable {
+    pub fn load(dwarf: &Dwarf<DwarfReader>, code: &CodeOffsets) -> DwarfResult<Self> {
+        let mut table = LineTable {
+            files: Vec::new(),
+            rows: Vec::new(),
+        };
+        let mut units = dwarf.units();
+        while let Some(header) = units.next()? {
+            let unit = dwarf.unit(header)?;
+            let program = match unit.line_program.clone() {
+                Some(program) => program,
+                None => continue,
+            };
+            let mut files = HashMap::new();
+            let mut rows = program.rows();
+            while let Some((header, row)) = rows.next_row()? {
+                if row.end_sequence() {
+                    continue;
+                }
+                // Code removed by the linker has its address set to 0 or -1 which is not inside any function
+                let (pos, line) = match (code.position(row.address()), row.line()) {
+                    (Some(pos), Some(line)) => (pos, line.get() as u32),
+                    _ => continue,
+                };
+                let file = match files.get(&row.file_index()) {
+                    Some(file) => *file,
+                    None => {
+                        let path = match row.file(header) {
+                            Some(file) => file_path(dwarf, &unit, header, file)?,
+                            None => continue,
+                        };
+                        let file = table.intern_file(path);
+                        files.insert(row.file_index(), file);
+                        file
+                    }
+                };
+                let column = match row.column() {
+                    ColumnType::LeftEdge => 0,
+                    ColumnType::Column(column) => column.get() as u32,
+                };
+                table.rows.push(LineRow {
+                    pos,
+                    file,
+                    line,
+                    column,
+                    is_stmt: row.is_stmt(),
+                });
+            }
+        }
+
+        // Of multiple rows for the same instruction, the last one applies
+        table.rows.sort_by_key(|row| row.pos);
+        let mut rows: Vec<LineRow> = Vec::with_capacity(table.rows.len());
+        for row in table.rows.drain(..) {
+            match rows.last_mut() {
+                Some(last) if last.pos == row.pos => *last = row,
+                _ => rows.push(row),
+            }
+        }
+        table.rows = rows;
+        Ok(table)
+    }
+
+    fn intern_file(&mut self, path: String) -> usize {
+        match self.files.iter().position(|file| *file == path) {
+            Some(index) => index,
+            None => {
+                self.files.push(path);
+                self.files.len() - 1
+            }
+        }
+    }
+
+    pub fn files(&self) -> &[String] {
+        &self.files
+    }
+
+    fn to_location(&self, row: &LineRow) -> SourceLocation<'_> {
+        SourceLocation {
+            file: &self.files[row.file],
+            line: row.line,
+            column: row.column,
+        }
+    }
+
+    fn row_index(&self, pos: CodePosition) -> Option<usize> {
+        let index = self.rows.partition_point(|row| row.pos <= pos).checked_sub(1)?;
+        if self.rows[index].pos.func_index == pos.func_index {
+            Some(index)
+        } else {
+            None
+        }
+    }
+
+    pub fn location(&self, pos: CodePosition) -> Option<SourceLocation<'_>> {
+        self.row_index(pos).map(|index| self.to_location(&self.rows[index]))
+    }
+
+    // Like `location`, but only for rows that start a statement of a real line. Line 0 marks code that has no
+    // source line, e.g. code generated by the compiler.
+    pub fn statement_location(&self, pos: CodePosition) -> Option<SourceLocation<'_>> {
+        let row = &self.rows[self.row_index(pos)?];
+        if row.is_stmt && row.line != 0 {
+            Some(self.to_location(row))
+        } else {
+            None
+        }
+    }
+
+    // The position of the first row of the line containing pos or pos itself if it is not covered by a row
+    pub fn line_start(&self, pos: CodePosition) -> CodePosition {
+        match self.row_index(pos) {
+            Some(index) => self.rows[index].pos,
+            None => pos,
+        }
+    }
+
+    pub fn function_location(&self, func_index: u32) -> Option<SourceLocation<'_>> {
+        let index = self.rows.partition_point(|row| row.pos.func_index < func_index);
+        self.rows
+            .get(index)
+            .filter(|row| row.pos.func_index == func_index)
+            .map(|row| self.to_location(row))
+    }
+
+    pub fn source_location(&self, path: &str, line: u32, column: u32) -> Option<SourceLocation<'_>> {
+        let file = self.files.iter().find(|file| *file == path)?;
+        Some(SourceLocation { file, line, column })
+    }
+
+    pub fn find_file(&self, file: &str) -> Option<&str> {
+        self.files
+            .iter()
+            .find(|path| path_matches(path, file))
+            .map(String::as_str)
+    }
+
+    // Finds the first line at or after `line` in all files matching `file` that has code and returns it together
+    // with the first statement of that line in every function containing it
+    pub fn find_line(&self, file: &str, line: u32) -> Option<(u32, Vec<CodePosition>)> {
+        let files: Vec<bool> = self.files.iter().map(|path| path_matches(path, file)).collect();
+        let matching_rows = || {
+            self.rows
+                .iter()
+                .filter(|row| row.is_stmt && files[row.file] && row.line >= line)
+        };
+        let found_line = matching_rows().map(|row| row.line).min()?;
+        let mut positions: Vec<CodePosition> = Vec::new();
+        for row in matching_rows().filter(|row| row.line == found_line) {
+            match positions.last() {
+                Some(last) if last.func_index == row.pos.func_index => (),
+                _ => positions.push(row.pos),
+            }
+        }
+        Some((found_line, positions))
+    }
+}
+
+// Matches if `query` is the path or a suffix of it consisting of whole components, e.g. "src/main.rs"
+fn path_matches(path: &str, query: &str) -> bool {
+    Path::new(path).ends_with(query)
+}
+
+pub(super) fn file_path(
+    dwarf: &Dwarf<DwarfReader>,
+    unit: &Unit<DwarfReader>,
+    header: &LineProgramHeader<DwarfReader>,
+    file: &FileEntry<DwarfReader>,
+) -> DwarfResult<String> {
+    let mut path = PathBuf::new();
+    if let Some(comp_dir) = &unit.comp_dir {
+        path.push(&*comp_dir.to_string_lossy()?);
+    }
+    // Directory 0 is the compilation directory
+    if file.directory_index() != 0 {
+        if let Some(directory) = file.directory(header) {
+            path.push(&*dwarf.attr_string(unit, directory)?.to_string_lossy()?);
+        }
+    }
+    path.push(&*dwarf.attr_string(unit, file.path_name())?.to_string_lossy()?);
+    Ok(path.to_string_lossy().into_owned())
+}
@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use gimli::{Dwarf, EndianRcSlice, LittleEndian, Unit, UnitOffset, UnitRef};
+use thiserror::Error;
+
+use crate::vm::{CodePosition, Trap};
+use crate::Module;
+
+mod code;
+mod frames;
+mod lines;
+mod types;
+mod variables;
+
+pub(crate) use code::CodeOffsets;
+use lines::LineTable;
+pub use lines::SourceLocation;
+pub use variables::{FrameState, Variable, VariableKind};
+
+pub(crate) type DwarfReader = EndianRcSlice<LittleEndian>;
+
+#[derive(Error, Debug)]
+pub enum DwarfError {
+    #[error("Invalid DWARF: {0}")]
+    Gimli(#[from] gimli::Error),
+    #[error("Failed to decode the code section")]
+    InvalidCode,
+    #[error("No symbol \"{0}\" in the current context")]
+    UnknownVariable(String),
+    #[error("No type \"{0}\"")]
+    UnknownType(String),
+    #[error("Invalid expression: {0}")]
+    InvalidExpression(String),
+    #[error("optimized out")]
+    OptimizedOut,
+    #[error("Invalid DWARF type")]
+    InvalidType,
+    #[error("Unsupported DWARF location expression")]
+    UnsupportedExpression,
+    #[error("{0}")]
+    Trap(#[from] Trap),
+}
+
+pub type DwarfResult<T> = Result<T, DwarfError>;
+
+// A debugging information entry in one of the compilation units
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) struct DieRef {
+    unit: usize,
+    offset: UnitOffset,
+}
+
+impl DieRef {
+    const fn new(unit: usize, offset: UnitOffset) -> Self {
+        DieRef { unit, offset }
+    }
+}
+
+// Source information from the DWARF custom sections emitted by e.g. LLVM
+pub struct DebugInfo {
+    dwarf: Dwarf<DwarfReader>,
+    units: Vec<Unit<DwarfReader>>,
+    code: CodeOffsets,
+    lines: LineTable,
+    globals: Vec<Variable>,
+}
+
+impl DebugInfo {
+    // Returns None if the binary contains no line information
+    pub fn load(module: &Module, bytes: &[u8]) -> DwarfResult<Option<Self>> {
+        let sections: HashMap<&str, &[u8]> = module
+            .custom_sections()
+            .iter()
+            .map(|section| (section.name(), section.payload()))
+            .collect();
+        if !sections.contains_key(".debug_line") {
+            return Ok(None);
+        }
+        let dwarf = Dwarf::load(|id| -> DwarfResult<DwarfReader> {
+            let data = sections.get(id.name()).copied().unwrap_or_default();
+            Ok(DwarfReader::new(Rc::from(data), LittleEndian))
+        })?;
+        let code = CodeOffsets::load(module, bytes)?;
+        let lines = LineTable::load(&dwarf, &code)?;
+        let mut units = Vec::new();
+        let mut headers = dwarf.units();
+        while let Some(header) = headers.next()? {
+            units.push(dwarf.unit(header)?);
+        }
+        let mut debug_info = DebugInfo {
+            dwarf,
+            units,
+            code,
+            lines,
+            globals: Vec::new(),
+        };
+        debug_info.load_globals()?;
+        Ok(Some(debug_info))
+    }
+
+    fn unit_ref(&self, index: usize) -> UnitRef<'_, DwarfReader> {
+        self.units[index].unit_ref(&self.dwarf)
+    }
+
+    pub fn files(&self) -> &[String] {
+        self.lines.files()
+    }
+
+    pub fn location(&self, pos: CodePosition) -> Option<SourceLocation<'_>> {
+        self.lines.location(pos)
+    }
+
+    pub fn statement_location(&self, pos: CodePosition) -> Option<SourceLocation<'_>> {
+        self.lines.statement_location(pos)
+    }
+
+    pub fn line_start(&self, pos: CodePosition) -> CodePosition {
+        self.lines.line_start(pos)
+    }
+
+    pub fn function_location(&self, func_index: u32) -> Option<SourceLocation<'_>> {
+        self.lines.function_location(func_index)
+    }
+
+    // Returns the first file whose path ends with `file`
+    pub fn find_file(&self, file: &str) -> Option<&str> {
+        self.lines.find_file(file)
+    }
+
+    pub fn find_line(&self, file: &str, line: u32) -> Option<(u32, Vec<CodePosition>)> {
+        self.lines.find_line(file, line)
+    }
+}
@@ -0,0 +1,865 @@
+use std::borrow::Cow;
+
+use gimli::{AttributeValue, DwAte, DwTag, EntriesTreeNode};
+
+use super::variables::{FrameState, Place, Variable};
+use super::{DebugInfo, DieRef, DwarfError, DwarfReader, DwarfResult};
+
+const MAX_ARRAY_ELEMENTS: u64 = 64;
+const MAX_VALUE_DEPTH: u32 = 8;
+// Longer chains of typedefs and qualifiers are assumed to be cyclic
+const MAX_MODIFIER_DEPTH: u32 = 64;
+const POINTER_SIZE: u64 = 4;
+
+const TYPE_TAGS: [DwTag; 7] = [
+    gimli::DW_TAG_base_type,
+    gimli::DW_TAG_structure_type,
+    gimli::DW_TAG_class_type,
+    gimli::DW_TAG_union_type,
+    gimli::DW_TAG_enumeration_type,
+    gimli::DW_TAG_typedef,
+    gimli::DW_TAG_array_type,
+];
+
+const MODIFIER_TAGS: [DwTag; 5] = [
+    gimli::DW_TAG_typedef,
+    gimli::DW_TAG_const_type,
+    gimli::DW_TAG_volatile_type,
+    gimli::DW_TAG_restrict_type,
+    gimli::DW_TAG_atomic_type,
+];
+
+struct Member {
+    name: String,
+    type_die: Option<DieRef>,
+    offset: u64,
+}
+
+// A variant of a Rust enum, selected if the discriminant equals discr_value or if no other variant matches and it
+// has no discr_value
+struct Variant {
+    discr_value: Option<u64>,
+    members: Vec<Member>,
+}
+
+struct VariantPart {
+    discriminant: Option<Member>,
+    variants: Vec<Variant>,
+}
+
+enum TypeKind {
+    Void,
+    Base(DwAte),
+    Pointer(Option<DieRef>),
+    Struct {
+        is_union: bool,
+        members: Vec<Member>,
+        variant_part: Option<VariantPart>,
+    },
+    Array {
+        element: Option<DieRef>,
+        counts: Vec<u64>,
+    },
+    Enum(Vec<(String, u64)>),
+    Unsupported,
+}
+
+struct TypeInfo {
+    name: String,
+    size: u64,
+    kind: TypeKind,
+}
+
+// Where the value of a source expression is stored
+#[derive(Clone, Debug)]
+enum Location {
+    Memory(u32),
+    Bytes(Vec<u8>),
+}
+
+impl Location {
+    fn offset(&self, offset: u64, size: u64) -> Location {
+        match self {
+            Location::Memory(address) => Location::Memory(address.wrapping_add(offset as u32)),
+            Location::Bytes(bytes) => {
+                let start = (offset as usize).min(bytes.len());
+                let end = (offset as usize).saturating_add(size as usize).min(bytes.len());
+                Location::Bytes(bytes[start..end].to_vec())
+            }
+        }
+    }
+
+    // Values that don't fit into a wasm value are padded with zeros
+    fn read<'a>(&'a self, frame: &'a FrameState, size: u64) -> DwarfResult<Cow<'a, [u8]>> {
+        match self {
+            Location::Memory(address) => Ok(Cow::Borrowed(frame.vm.default_memory()?.read(*address, size as u32)?)),
+            Location::Bytes(bytes) => {
+                let mut bytes = bytes.clone();
+                bytes.resize(size as usize, 0);
+                Ok(Cow::Owned(bytes))
+            }
+        }
+    }
+
+    fn read_unsigned(&self, frame: &FrameState, size: u64) -> DwarfResult<u64> {
+        Ok(to_unsigned(&self.read(frame, size.min(8))?) as u64)
+    }
+}
+
+// The value of a source expression. The location is None if only the type is of interest.
+struct SourceValue {
+    type_die: Option<DieRef>,
+    location: Option<Location>,
+}
+
+impl DebugInfo {
+    fn is_rust(&self, unit_index: usize) -> bool {
+        let mut entries = self.units[unit_index].entries();
+        match entries.next_dfs() {
+            Ok(Some((_, root))) => {
+                matches!(
+                    root.attr_value(gimli::DW_AT_language),
+                    Ok(Some(AttributeValue::Language(gimli::DW_LANG_Rust)))
+                )
+            }
+            _ => false,
+        }
+    }
+
+    pub(super) fn attr_udata(&self, die: DieRef, name: gimli::DwAt) -> DwarfResult<Option<u64>> {
+        Ok(self.attr(die, name)?.and_then(|value| value.udata_value()))
+    }
+
+    fn name(&self, die: DieRef) -> DwarfResult<Option<String>> {
+        match self.attr(die, gimli::DW_AT_name)? {
+            Some(name) => Ok(Some(self.attr_string(die, name)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn type_ref(&self, die: DieRef) -> DwarfResult<Option<DieRef>> {
+        Ok(self
+            .attr(die, gimli::DW_AT_type)?
+            .and_then(|value| self.resolve_ref(die.unit, value)))
+    }
+
+    // Skips typedefs and qualifiers
+    fn strip_modifiers(&self, type_die: Option<DieRef>) -> DwarfResult<Option<DieRef>> {
+        let mut type_die = type_die;
+        for _ in 0..=MAX_MODIFIER_DEPTH {
+            match type_die {
+                Some(die) if MODIFIER_TAGS.contains(&self.entry_tag(die)?) => type_die = self.type_ref(die)?,
+                _ => return Ok(type_die),
+            }
+        }
+        Err(DwarfError::InvalidType)
+    }
+
+    fn type_name(&self, type_die: Option<DieRef>) -> DwarfResult<String> {
+        let die = match type_die {
+            Some(die) => die,
+            None => return Ok(String::from("void")),
+        };
+        let tag = self.entry_tag(die)?;
+        if let Some(name) = self.name(die)? {
+            if self.is_rust(die.unit) {
+                return Ok(name);
+            }
+            return Ok(match tag {
+                gimli::DW_TAG_structure_type => format!("struct {}", name),
+                gimli::DW_TAG_class_type => format!("class {}", name),
+                gimli::DW_TAG_union_type => format!("union {}", name),
+                gimli::DW_TAG_enumeration_type => format!("enum {}", name),
+                _ => name,
+            });
+        }
+        Ok(match tag {
+            gimli::DW_TAG_pointer_type => format!("{} *", self.type_name(self.type_ref(die)?)?),
+            gimli::DW_TAG_reference_type => format!("{} &", self.type_name(self.type_ref(die)?)?),
+            gimli::DW_TAG_const_type => format!("const {}", self.type_name(self.type_ref(die)?)?),
+            gimli::DW_TAG_volatile_type => format!("volatile {}", self.type_name(self.type_ref(die)?)?),
+            gimli::DW_TAG_array_type => {
+                let element = self.type_name(self.type_ref(die)?)?;
+                let counts = self.array_counts(die)?;
+                if self.is_rust(die.unit) {
+                    counts
+                        .iter()
+                        .rev()
+                        .fold(element, |name, count| format!("[{}; {}]", name, count))
+                } else {
+                    let dims: String = counts.iter().map(|count| format!("[{}]", count)).collect();
+                    format!("{}{}", element, dims)
+                }
+            }
+            gimli::DW_TAG_subroutine_type => String::from("fn"),
+            _ => String::from("<anonymous>"),
+        })
+    }
+
+    fn array_counts(&self, die: DieRef) -> DwarfResult<Vec<u64>> {
+        let mut counts = Vec::new();
+        let unit = &self.units[die.unit];
+        let mut tree = unit.entries_tree(Some(die.offset))?;
+        let mut children = tree.root()?.children();
+        while let Some(child) = children.next()? {
+            let entry = child.entry();
+            if entry.tag() != gimli::DW_TAG_subrange_type {
+                continue;
+            }
+            let count = match entry
+                .attr_value(gimli::DW_AT_count)?
+                .and_then(|value| value.udata_value())
+            {
+                Some(count) => count,
+                None => {
+                    let lower = entry.attr_value(gimli::DW_AT_lower_bound)?;
+                    let upper = entry.attr_value(gimli::DW_AT_upper_bound)?;
+                    match upper.and_then(|value| value.sdata_value()) {
+                        Some(upper) => {
+                            let lower = lower.and_then(|value| value.sdata_value()).unwrap_or(0);
+                            (upper - lower + 1).max(0) as u64
+                        }
+                        None => 0,
+                    }
+                }
+            };
+            counts.push(count);
+        }
+        Ok(counts)
+    }
+
+    fn members(&self, die: DieRef) -> DwarfResult<(Vec<Member>, Option<VariantPart>)> {
+        let mut members = Vec::new();
+        let mut variant_part = None;
+        let unit = &self.units[die.unit];
+        let mut tree = unit.entries_tree(Some(die.offset))?;
+        let mut children = tree.root()?.children();
+        while let Some(child) = children.next()? {
+            let child_die = DieRef::new(die.unit, child.entry().offset());
+            match child.entry().tag() {
+                gimli::DW_TAG_member => members.push(self.member(child_die)?),
+                gimli::DW_TAG_variant_part => variant_part = Some(self.variant_part(child_die)?),
+                _ => (),
+            }
+        }
+        Ok((members, variant_part))
+    }
+
+    fn member(&self, die: DieRef) -> DwarfResult<Member> {
+        Ok(Member {
+            name: self.name(die)?.unwrap_or_default(),
+            type_die: self.type_ref(die)?,
+            offset: self.attr_udata(die, gimli::DW_AT_data_member_location)?.unwrap_or(0),
+        })
+    }
+
+    fn variant_part(&self, die: DieRef) -> DwarfResult<VariantPart> {
+        let discriminant = match self.attr(die, gimli::DW_AT_discr)? {
+            Some(value) => match self.resolve_ref(die.unit, value) {
+                Some(discriminant) => Some(self.member(discriminant)?),
+                None => None,
+            },
+            None => None,
+        };
+        let mut variants = Vec::new();
+        let unit = &self.units[die.unit];
+        let mut tree = unit.entries_tree(Some(die.offset))?;
+        let mut children = tree.root()?.children();
+        while let Some(child) = children.next()? {
+            if child.entry().tag() != gimli::DW_TAG_variant {
+                continue;
+            }
+            let variant_die = DieRef::new(die.unit, child.entry().offset());
+            variants.push(Variant {
+                discr_value: self.attr_udata(variant_die, gimli::DW_AT_discr_value)?,
+                members: self.members(variant_die)?.0,
+            });
+        }
+        Ok(VariantPart { discriminant, variants })
+    }
+
+    fn enumerators(&self, die: DieRef) -> DwarfResult<Vec<(String, u64)>> {
+        let mut enumerators = Vec::new();
+        let unit = &self.units[die.unit];
+        let mut tree = unit.entries_tree(Some(die.offset))?;
+        let mut children = tree.root()?.children();
+        while let Some(child) = children.next()? {
+            if child.entry().tag() != gimli::DW_TAG_enumerator {
+                continue;
+            }
+            let enumerator_die = DieRef::new(die.unit, child.entry().offset());
+            let value = match self.attr(enumerator_die, gimli::DW_AT_const_value)? {
+                Some(AttributeValue::Sdata(value)) => value as u64,
+                Some(value) => value.udata_value().unwrap_or(0),
+                None => 0,
+            };
+            enumerators.push((self.name(enumerator_die)?.unwrap_or_default(), value));
+        }
+        Ok(enumerators)
+    }
+
+    fn type_info(&self, type_die: Option<DieRef>) -> DwarfResult<TypeInfo> {
+        let name = self.type_name(type_die)?;
+        let die = match self.strip_modifiers(type_die)? {
+            Some(die) => die,
+            None => {
+                return Ok(TypeInfo {
+                    name,
+                    size: 0,
+                    kind: TypeKind::Void,
+                })
+            }
+        };
+        let size = self.attr_udata(die, gimli::DW_AT_byte_size)?;
+        let (size, kind) = match self.entry_tag(die)? {
+            gimli::DW_TAG_base_type => {
+                let encoding = match self.attr(die, gimli::DW_AT_encoding)? {
+                    Some(AttributeValue::Encoding(encoding)) => encoding,
+                    _ => gimli::DW_ATE_unsigned,
+                };
+                (size.unwrap_or(0), TypeKind::Base(encoding))
+            }
+            gimli::DW_TAG_pointer_type | gimli::DW_TAG_reference_type | gimli::DW_TAG_rvalue_reference_type => {
+                (size.unwrap_or(POINTER_SIZE), TypeKind::Pointer(self.type_ref(die)?))
+            }
+            tag @ gimli::DW_TAG_structure_type | tag @ gimli::DW_TAG_class_type | tag @ gimli::DW_TAG_union_type => {
+                let (members, variant_part) = self.members(die)?;
+                let kind = TypeKind::Struct {
+                    is_union: tag == gimli::DW_TAG_union_type,
+                    members,
+                    variant_part,
+                };
+                (size.unwrap_or(0), kind)
+            }
+            gimli::DW_TAG_array_type => {
+                let element = self.type_ref(die)?;
+                let counts = self.array_counts(die)?;
+                let size = match size {
+                    Some(size) => size,
+                    None => array_size(&counts, self.type_info(element)?.size)?,
+                };
+                (size, TypeKind::Array { element, counts })
+            }
+            gimli::DW_TAG_enumeration_type => (size.unwrap_or(0), TypeKind::Enum(self.enumerators(die)?)),
+            _ => (size.unwrap_or(0), TypeKind::Unsupported),
+        };
+        Ok(TypeInfo { name, size, kind })
+    }
+
+    fn format_value(
+        &self,
+        frame: &FrameState,
+        type_die: Option<DieRef>,
+        location: &Location,
+        depth: u32,
+    ) -> DwarfResult<String> {
+        let info = self.type_info(type_die)?;
+        if depth > MAX_VALUE_DEPTH {
+            return Ok(String::from("{...}"));
+        }
+        Ok(match &info.kind {
+            TypeKind::Void => String::from("void"),
+            TypeKind::Base(encoding) => format_base(*encoding, &location.read(frame, info.size)?),
+            TypeKind::Pointer(_) => format!("0x{:08x}", location.read_unsigned(frame, info.size)?),
+            TypeKind::Enum(enumerators) => {
+                let value = location.read_unsigned(frame, info.size)?;
+                let mask = size_mask(info.size);
+                match enumerators.iter().find(|(_, enum_value)| enum_value & mask == value) {
+                    Some((name, _)) => name.clone(),
+                    None => value.to_string(),
+                }
+            }
+            TypeKind::Array { element, counts } => {
+                let element_size = self.type_info(*element)?.size;
+                self.format_array(frame, *element, element_size, counts, location, depth)?
+            }
+            TypeKind::Struct {
+                members, variant_part, ..
+            } => {
+                let mut fields: Vec<&Member> = members.iter().collect();
+                if let Some(variant_part) = variant_part {
+                    if let Some(variant) = self.select_variant(frame, variant_part, location)? {
+                        // A Rust enum is stored as a struct with one member per variant
+                        if members.is_empty() && variant.members.len() == 1 {
+                            let member = &variant.members[0];
+                            let member_size = self.type_info(member.type_die)?.size;
+                            let location = location.offset(member.offset, member_size);
+                            return self.format_value(frame, member.type_die, &location, depth + 1);
+                        }
+                        fields.extend(variant.members.iter());
+                    }
+                }
+                let name = self.name(self.strip_modifiers(type_die)?.unwrap())?.unwrap_or_default();
+                let mut values = Vec::with_capacity(fields.len());
+                for member in &fields {
+                    let member_size = self.type_info(member.type_die)?.size;
+                    let member_location = location.offset(member.offset, member_size);
+                    values.push(self.format_value(frame, member.type_die, &member_location, depth + 1)?);
+                }
+                // Rust tuples and tuple structs have members named __0, __1 and so on
+                let is_tuple = !fields.is_empty() && fields.iter().all(|member| member.name.starts_with("__"));
+                if fields.is_empty() {
+                    name
+                } else if is_tuple {
+                    format!("{}({})", name, values.join(", "))
+                } else {
+                    let fields: Vec<String> = fields
+                        .iter()
+                        .zip(values)
+                        .map(|(member, value)| format!("{}: {}", member.name, value))
+                        .collect();
+                    format!("{} {{ {} }}", name, fields.join(", ")).trim_start().to_string()
+                }
+            }
+            TypeKind::Unsupported => format!("<{}>", info.name),
+        })
+    }
+
+    fn format_array(
+        &self,
+        frame: &FrameState,
+        element: Option<DieRef>,
+        element_size: u64,
+        counts: &[u64],
+        location: &Location,
+        depth: u32,
+    ) -> DwarfResult<String> {
+        let (count, inner_counts) = match counts.split_first() {
+            Some(split) => split,
+            None => return Ok(String::from("[]")),
+        };
+        let stride = array_size(inner_counts, element_size)?;
+        let mut values = Vec::new();
+        for index in 0..(*count).min(MAX_ARRAY_ELEMENTS) {
+            let offset = index.checked_mul(stride).ok_or(DwarfError::InvalidType)?;
+            let location = location.offset(offset, stride);
+            values.push(if inner_counts.is_empty() {
+                self.format_value(frame, element, &location, depth + 1)?
+            } else {
+                self.format_array(frame, element, element_size, inner_counts, &location, depth + 1)?
+            });
+        }
+        if *count > MAX_ARRAY_ELEMENTS {
+            values.push(String::from("..."));
+        }
+        Ok(format!("[{}]", values.join(", ")))
+    }
+
+    fn select_variant<'v>(
+        &self,
+        frame: &FrameState,
+        variant_part: &'v VariantPart,
+        location: &Location,
+    ) -> DwarfResult<Option<&'v Variant>> {
+        let default = variant_part
+            .variants
+            .iter()
+            .find(|variant| variant.discr_value.is_none());
+        let discriminant = match &variant_part.discriminant {
+            Some(discriminant) => discriminant,
+            None => return Ok(default),
+        };
+        let size = self.type_info(discriminant.type_die)?.size;
+        let value = location.offset(discriminant.offset, size).read_unsigned(frame, size)?;
+        let mask = size_mask(size);
+        Ok(variant_part
+            .variants
+            .iter()
+            .find(|variant| variant.discr_value.map(|discr_value| discr_value & mask) == Some(value))
+            .or(default))
+    }
+
+    fn variable_location(&self, frame: &FrameState, variable: &Variable) -> DwarfResult<Location> {
+        match self.variable_place(frame, variable)? {
+            Place::Memory(address) => Ok(Location::Memory(address)),
+            Place::Bits(bits) => Ok(Location::Bytes(bits.to_le_bytes().to_vec())),
+            Place::Unavailable => Err(DwarfError::OptimizedOut),
+        }
+    }
+
+    pub fn format_variable(&self, frame: &FrameState, variable: &Variable) -> DwarfResult<String> {
+        let location = self.variable_location(frame, variable)?;
+        self.format_value(frame, variable.type_die, &location, 0)
+    }
+
+    pub fn variable_type_name(&self, variable: &Variable) -> DwarfResult<String> {
+        self.type_name(variable.type_die)
+    }
+
+    // Evaluates a source expression like "data.pos.x", "*ptr", "values[2]" or "node->next" and formats its value
+    pub fn format_expression(&self, frame: &FrameState, expression: &str) -> DwarfResult<String> {
+        let value = self.evaluate_expression(Some(frame), true, expression)?;
+        let location = value.location.ok_or(DwarfError::OptimizedOut)?;
+        self.format_value(frame, value.type_die, &location, 0)
+    }
+
+    // Only the type is evaluated if read_values is false
+    fn evaluate_expression(
+        &self,
+        scope: Option<&FrameState>,
+        read_values: bool,
+        expression: &str,
+    ) -> DwarfResult<SourceValue> {
+        let frame = scope.filter(|_| read_values);
+        let invalid = || DwarfError::InvalidExpression(expression.to_string());
+        let expression = expression.trim();
+        let derefs = expression.chars().take_while(|c| *c == '*').count();
+        let rest = expression[derefs..].trim_start();
+        let name_len = rest
+            .find(|c: char| !c.is_alphanumeric() && c != '_' && c != '$')
+            .unwrap_or(rest.len());
+        if name_len == 0 {
+            return Err(invalid());
+        }
+        let variable = self.find_variable(scope, &rest[..name_len])?;
+        let mut value = SourceValue {
+            type_die: variable.type_die,
+            location: match frame {
+                Some(frame) => Some(self.variable_location(frame, &variable)?),
+                None => None,
+            },
+        };
+
+        let mut rest = rest[name_len..].trim_start();
+        while !rest.is_empty() {
+            if rest.starts_with('[') {
+                let end = rest.find(']').ok_or_else(invalid)?;
+                let index: u64 = rest[1..end].trim().parse().map_err(|_| invalid())?;
+                value = self.index(frame, value, index)?;
+                rest = &rest[end + 1..];
+            } else {
+                let is_arrow = rest.starts_with("->");
+                if is_arrow {
+                    rest = &rest[2..];
+                } else if rest.starts_with('.') {
+                    rest = &rest[1..];
+                } else {
+                    return Err(invalid());
+                }
+                rest = rest.trim_start();
+                let len = rest
+                    .find(|c: char| !c.is_alphanumeric() && c != '_')
+                    .unwrap_or(rest.len());
+                if len == 0 {
+                    return Err(invalid());
+                }
+                if is_arrow {
+                    value = self.deref(frame, value)?;
+                }
+                value = self.member_value(frame, value, &rest[..len])?;
+                rest = &rest[len..];
+            }
+            rest = rest.trim_start();
+        }
+        for _ in 0..derefs {
+            value = self.deref(frame, value)?;
+        }
+        Ok(value)
+    }
+
+    // Prints the type of a source expression or the definition of a named type
+    pub fn describe_type(&self, frame: Option<&FrameState>, query: &str) -> DwarfResult<String> {
+        let type_die = match self.evaluate_expression(frame, false, query) {
+            Ok(value) => value.type_die,
+            Err(DwarfError::UnknownVariable(_)) | Err(DwarfError::InvalidExpression(_)) => {
+                Some(self.find_type(query.trim())?)
+            }
+            Err(error) => return Err(error),
+        };
+        let info = self.type_info(type_die)?;
+        let die = match self.strip_modifiers(type_die)? {
+            Some(die) => die,
+            None => return Ok(info.name),
+        };
+        let is_rust = self.is_rust(die.unit);
+        let header = if is_rust {
+            let keyword = match &info.kind {
+                TypeKind::Struct { is_union: true, .. } => "union",
+                TypeKind::Struct {
+                    variant_part: Some(_), ..
+                }
+                | TypeKind::Enum(_) => "enum",
+                _ => "struct",
+            };
+            format!("{} {}", keyword, self.name(die)?.unwrap_or_default())
+        } else {
+            self.type_name(Some(die))?
+        };
+        let mut lines = Vec::new();
+        match info.kind {
+            TypeKind::Enum(enumerators) => {
+                for (name, value) in enumerators {
+                    lines.push(format!("{} = {},", name, value));
+                }
+            }
+            TypeKind::Struct {
+                members, variant_part, ..
+            } => {
+                for member in &members {
+                    lines.push(self.format_member(member, is_rust)?);
+                }
+                for variant in variant_part
+                    .iter()
+                    .flat_map(|variant_part| variant_part.variants.iter())
+                {
+                    for member in &variant.members {
+                        lines.push(self.format_variant(member)?);
+                    }
+                }
+            }
+            _ => return Ok(info.name),
+        }
+        let body: String = lines.iter().map(|line| format!("    {}\n", line)).collect();
+        Ok(format!("{} {{\n{}}}", header, body))
+    }
+
+    fn format_member(&self, member: &Member, is_rust: bool) -> DwarfResult<String> {
+        let type_name = self.type_name(member.type_die)?;
+        if is_rust {
+            Ok(format!("{}: {},", member.name, type_name))
+        } else {
+            Ok(format!("{} {};", type_name, member.name))
+        }
+    }
+
+    // Every variant of a Rust enum is a member whose type is a struct with the fields of the variant
+    fn format_variant(&self, member: &Member) -> DwarfResult<String> {
+        let fields = match self.type_info(member.type_die)?.kind {
+            TypeKind::Struct { members, .. } => members,
+            _ => return Ok(format!("{}({}),", member.name, self.type_name(member.type_die)?)),
+        };
+        let mut types = Vec::with_capacity(fields.len());
+        for field in &fields {
+            types.push(self.type_name(field.type_die)?);
+        }
+        if fields.is_empty() {
+            Ok(format!("{},", member.name))
+        } else if fields.iter().all(|field| field.name.starts_with("__")) {
+            Ok(format!("{}({}),", member.name, types.join(", ")))
+        } else {
+            let fields: Vec<String> = fields
+                .iter()
+                .zip(types)
+                .map(|(field, type_name)| format!("{}: {}", field.name, type_name))
+                .collect();
+            Ok(format!("{} {{ {} }},", member.name, fields.join(", ")))
+        }
+    }
+
+    // Finds the definition of a type by its name, optionally qualified with its namespaces like "vars::Data"
+    fn find_type(&self, name: &str) -> DwarfResult<DieRef> {
+        for unit_index in 0..self.units.len() {
+            let mut tree = self.units[unit_index].entries_tree(None)?;
+            let mut path = Vec::new();
+            if let Some(die) = self.find_type_in(unit_index, tree.root()?, name, &mut path)? {
+                return Ok(die);
+            }
+        }
+        Err(DwarfError::UnknownType(name.to_string()))
+    }
+
+    fn find_type_in(
+        &self,
+        unit_index: usize,
+        node: EntriesTreeNode<DwarfReader>,
+        name: &str,
+        path: &mut Vec<String>,
+    ) -> DwarfResult<Option<DieRef>> {
+        let mut children = node.children();
+        while let Some(child) = children.next()? {
+            let die = DieRef::new(unit_index, child.entry().offset());
+            let tag = child.entry().tag();
+            let is_type = TYPE_TAGS.contains(&tag) && child.entry().attr_value(gimli::DW_AT_declaration)?.is_none();
+            let is_namespace = tag == gimli::DW_TAG_namespace;
+            if !is_type && !is_namespace {
+                continue;
+            }
+            let die_name = match self.name(die)? {
+                Some(die_name) => die_name,
+                None => continue,
+            };
+            path.push(die_name);
+            if is_type && (path.last().unwrap() == name || path.join("::").ends_with(&format!("::{}", name))) {
+                return Ok(Some(die));
+            }
+            if let Some(die) = self.find_type_in(unit_index, child, name, path)? {
+                return Ok(Some(die));
+            }
+            path.pop();
+        }
+        Ok(None)
+    }
+
+    fn deref(&self, frame: Option<&FrameState>, value: SourceValue) -> DwarfResult<SourceValue> {
+        let info = self.type_info(value.type_die)?;
+        let pointee = match info.kind {
+            TypeKind::Pointer(pointee) => pointee,
+            _ => {
+                return Err(DwarfError::InvalidExpression(format!(
+                    "\"{}\" is not a pointer",
+                    info.name
+                )))
+            }
+        };
+        let location = match (frame, value.location) {
+            (Some(frame), Some(location)) => Some(Location::Memory(location.read_unsigned(frame, info.size)? as u32)),
+            _ => None,
+        };
+        Ok(SourceValue {
+            type_die: pointee,
+            location,
+        })
+    }
+
+    fn index(&self, frame: Option<&FrameState>, value: SourceValue, index: u64) -> DwarfResult<SourceValue> {
+        let info = self.type_info(value.type_die)?;
+        match info.kind {
+            TypeKind::Array { element, counts } => {
+                let count = counts.first().copied().unwrap_or(0);
+                if index >= count {
+                    return Err(DwarfError::InvalidExpression(format!(
+                        "Index {} is out of range for \"{}\"",
+                        index, info.name
+                    )));
+                }
+                // The rows of a multi-dimensional array have no type DIE of their own
+                if counts.len() > 1 {
+                    return Err(DwarfError::InvalidExpression(String::from(
+                        "Indexing multi-dimensional arrays is not supported",
+                    )));
+                }
+                let element_size = self.type_info(element)?.size;
+                let offset = index.checked_mul(element_size).ok_or(DwarfError::InvalidType)?;
+                let location = value.location.map(|location| location.offset(offset, element_size));
+                Ok(SourceValue {
+                    type_die: element,
+                    location,
+                })
+            }
+            TypeKind::Pointer(pointee) => {
+                let pointee_size = self.type_info(pointee)?.size;
+                let location = match (frame, value.location) {
+                    (Some(frame), Some(location)) => {
+                        let address = location.read_unsigned(frame, info.size)?;
+                        Some(Location::Memory(address.wrapping_add(index * pointee_size) as u32))
+                    }
+                    _ => None,
+                };
+                Ok(SourceValue {
+                    type_die: pointee,
+                    location,
+                })
+            }
+            _ => Err(DwarfError::InvalidExpression(format!(
+                "\"{}\" is not an array or pointer",
+                info.name
+            ))),
+        }
+    }
+
+    // References and pointers are dereferenced automatically like in Rust
+    fn member_value(&self, frame: Option<&FrameState>, value: SourceValue, name: &str) -> DwarfResult<SourceValue> {
+        let info = self.type_info(value.type_die)?;
+        let (members, variant_part) = match &info.kind {
+            TypeKind::Struct {
+                members, variant_part, ..
+            } => (members, variant_part),
+            TypeKind::Pointer(_) => {
+                let value = self.deref(frame, value)?;
+                return self.member_value(frame, value, name);
+            }
+            _ => {
+                return Err(DwarfError::InvalidExpression(format!(
+                    "\"{}\" has no members",
+                    info.name
+                )))
+            }
+        };
+        let variant_members = variant_part
+            .iter()
+            .flat_map(|variant_part| variant_part.variants.iter())
+            .flat_map(|variant| variant.members.iter());
+        let member = members
+            .iter()
+            .chain(variant_members)
+            .find(|member| member.name == name)
+            .ok_or_else(|| DwarfError::InvalidExpression(format!("\"{}\" has no member \"{}\"", info.name, name)))?;
+        let member_size = self.type_info(member.type_die)?.size;
+        Ok(SourceValue {
+            type_die: member.type_die,
+            location: value
+                .location
+                .map(|location| location.offset(member.offset, member_size)),
+        })
+    }
+}
+
+// The size of an array with the given dimensions, which can overflow for invalid DWARF
+fn array_size(counts: &[u64], element_size: u64) -> DwarfResult<u64> {
+    counts
+        .iter()
+        .try_fold(element_size, |size, count| size.checked_mul(*count))
+        .ok_or(DwarfError::InvalidType)
+}
+
+fn size_mask(size: u64) -> u64 {
+    if size >= 8 {
+        u64::MAX
+    } else {
+        (1 << (size * 8)) - 1
+    }
+}
+
+fn to_unsigned(bytes: &[u8]) -> u128 {
+    bytes
+        .iter()
+        .take(16)
+        .rev()
+        .fold(0, |value, byte| (value << 8) | u128::from(*byte))
+}
+
+fn format_base(encoding: DwAte, bytes: &[u8]) -> String {
+    let value = to_unsigned(bytes);
+    let bits = (bytes.len().min(16) * 8) as u32;
+    let signed = if bits == 0 {
+        0
+    } else {
+        ((value << (128 - bits)) as i128) >> (128 - bits)
+    };
+    match encoding {
+        gimli::DW_ATE_boolean => (value != 0).to_string(),
+        gimli::DW_ATE_float if bytes.len() == 4 => f32::from_bits(value as u32).to_string(),
+        gimli::DW_ATE_float if bytes.len() == 8 => f64::from_bits(value as u64).to_string(),
+        gimli::DW_ATE_signed => signed.to_string(),
+        gimli::DW_ATE_unsigned => value.to_string(),
+        gimli::DW_ATE_signed_char | gimli::DW_ATE_unsigned_char => {
+            let number = if encoding == gimli::DW_ATE_signed_char {
+                signed.to_string()
+            } else {
+                value.to_string()
+            };
+            match value as u8 {
+                byte @ 0x20..=0x7e if bytes.len() == 1 => format!("{} '{}'", number, byte as char),
+                _ => number,
+            }
+        }
+        gimli::DW_ATE_UTF => match std::char::from_u32(value as u32) {
+            Some(c) => format!("{:?}", c),
+            None => format!("0x{:x}", value),
+        },
+        _ => format!("0x{:x}", value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::array_size;
+
+    #[test]
+    fn array_sizes() {
+        assert_eq!(array_size(&[], 4).unwrap(), 4);
+        assert_eq!(array_size(&[3, 5], 4).unwrap(), 60);
+        assert!(array_size(&[u64::MAX, 2], 1).is_err());
+        assert!(array_size(&[1 << 32], 1 << 32).is_err());
+    }
+}
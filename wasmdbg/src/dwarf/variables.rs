@@ -0,0 +1,434 @@
+use gimli::{AttributeValue, DwAt, DwTag, EntriesTreeNode, Expression, Operation, Reader as _, UnitOffset};
+
+use super::{DebugInfo, DieRef, DwarfError, DwarfReader, DwarfResult};
+use crate::vm::{CodePosition, VM};
+use crate::Value;
+
+// The state of a function frame that is needed to find and read its variables
+#[derive(Clone, Copy)]
+pub struct FrameState<'a> {
+    pub vm: &'a VM,
+    // The position whose scope is used. For callers, this is the call instruction.
+    pub pos: CodePosition,
+    pub locals: &'a [Value],
+    // The number of inlined functions at pos that are called by the function of this frame
+    pub inline_depth: usize,
+}
+
+impl<'a> FrameState<'a> {
+    pub fn current(vm: &'a VM) -> Option<Self> {
+        Some(FrameState {
+            vm,
+            pos: vm.ip(),
+            locals: vm.locals().ok()?,
+            inline_depth: 0,
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum VariableKind {
+    Parameter,
+    Local,
+    Global,
+}
+
+#[derive(Clone, Debug)]
+pub struct Variable {
+    name: String,
+    kind: VariableKind,
+    pub(super) die: DieRef,
+    pub(super) type_die: Option<DieRef>,
+    // The subprogram whose DW_AT_frame_base is used for DW_OP_fbreg
+    frame_base: Option<DieRef>,
+}
+
+impl Variable {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub const fn kind(&self) -> VariableKind {
+        self.kind
+    }
+}
+
+// Where the value of a variable is stored. Values in wasm locals, globals or on the value stack are stored as the
+// bits of the value.
+#[derive(Clone, Copy, Debug)]
+pub(super) enum Place {
+    Memory(u32),
+    Bits(u64),
+    Unavailable,
+}
+
+const SCOPE_TAGS: [DwTag; 3] = [
+    gimli::DW_TAG_subprogram,
+    gimli::DW_TAG_lexical_block,
+    gimli::DW_TAG_inlined_subroutine,
+];
+
+// Subprograms can also be nested in namespaces and types
+const CONTAINER_TAGS: [DwTag; 5] = [
+    gimli::DW_TAG_namespace,
+    gimli::DW_TAG_structure_type,
+    gimli::DW_TAG_class_type,
+    gimli::DW_TAG_union_type,
+    gimli::DW_TAG_module,
+];
+
+impl DebugInfo {
+    // Returns the scopes containing pos from the outermost subprogram to the innermost block
+    pub(super) fn scopes(&self, pos: CodePosition) -> DwarfResult<Vec<DieRef>> {
+        let address = match self.code.address(pos) {
+            Some(address) => address,
+            None => return Ok(Vec::new()),
+        };
+        for (unit_index, unit) in self.units.iter().enumerate() {
+            let mut tree = unit.entries_tree(None)?;
+            let mut path = Vec::new();
+            if self.find_scopes(unit_index, tree.root()?, address, &mut path)? {
+                return Ok(path);
+            }
+        }
+        Ok(Vec::new())
+    }
+
+    fn find_scopes(
+        &self,
+        unit_index: usize,
+        node: EntriesTreeNode<DwarfReader>,
+        address: u64,
+        path: &mut Vec<DieRef>,
+    ) -> DwarfResult<bool> {
+        let unit = self.unit_ref(unit_index);
+        let mut children = node.children();
+        while let Some(child) = children.next()? {
+            let entry = child.entry();
+            let tag = entry.tag();
+            if SCOPE_TAGS.contains(&tag) {
+                let mut ranges = unit.die_ranges(entry)?;
+                let mut contains = false;
+                while let Some(range) = ranges.next()? {
+                    contains |= range.begin <= address && address < range.end;
+                }
+                if contains {
+                    path.push(DieRef::new(unit_index, entry.offset()));
+                    self.find_scopes(unit_index, child, address, path)?;
+                    return Ok(true);
+                }
+            } else if CONTAINER_TAGS.contains(&tag) && self.find_scopes(unit_index, child, address, path)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    // The variables visible in the frame from the outermost to the innermost scope. Variables of functions that
+    // were inlined into the function of the frame are not visible.
+    pub fn variables(&self, frame: &FrameState) -> DwarfResult<Vec<Variable>> {
+        let (frame_base, scopes) = self.frame_scopes(frame.pos, frame.inline_depth)?;
+        let mut variables = Vec::new();
+        for scope in &scopes {
+            let unit = &self.units[scope.unit];
+            let mut tree = unit.entries_tree(Some(scope.offset))?;
+            let mut children = tree.root()?.children();
+            while let Some(child) = children.next()? {
+                let kind = match child.entry().tag() {
+                    gimli::DW_TAG_formal_parameter => VariableKind::Parameter,
+                    gimli::DW_TAG_variable => VariableKind::Local,
+                    _ => continue,
+                };
+                let die = DieRef::new(scope.unit, child.entry().offset());
+                if let Some(variable) = self.variable(die, kind, frame_base)? {
+                    variables.push(variable);
+                }
+            }
+        }
+        Ok(variables)
+    }
+
+    pub(super) fn load_globals(&mut self) -> DwarfResult<()> {
+        for unit_index in 0..self.units.len() {
+            let mut globals = Vec::new();
+            let mut tree = self.units[unit_index].entries_tree(None)?;
+            self.find_globals(unit_index, tree.root()?, &mut globals)?;
+            self.globals.extend(globals);
+        }
+        Ok(())
+    }
+
+    fn find_globals(
+        &self,
+        unit_index: usize,
+        node: EntriesTreeNode<DwarfReader>,
+        globals: &mut Vec<Variable>,
+    ) -> DwarfResult<()> {
+        let mut children = node.children();
+        while let Some(child) = children.next()? {
+            let tag = child.entry().tag();
+            if tag == gimli::DW_TAG_variable && child.entry().attr_value(gimli::DW_AT_location)?.is_some() {
+                let die = DieRef::new(unit_index, child.entry().offset());
+                if let Some(variable) = self.variable(die, VariableKind::Global, None)? {
+                    globals.push(variable);
+                }
+            } else if CONTAINER_TAGS.contains(&tag) {
+                self.find_globals(unit_index, child, globals)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn globals(&self) -> &[Variable] {
+        &self.globals
+    }
+
+    // Searches the innermost scope first and the global variables last. Without a frame only the global variables
+    // are searched.
+    pub fn find_variable(&self, frame: Option<&FrameState>, name: &str) -> DwarfResult<Variable> {
+        let variables = match frame {
+            Some(frame) => self.variables(frame)?,
+            None => Vec::new(),
+        };
+        variables
+            .iter()
+            .rev()
+            .chain(self.globals.iter())
+            .find(|variable| variable.name == name)
+            .cloned()
+            .ok_or_else(|| DwarfError::UnknownVariable(name.to_owned()))
+    }
+
+    fn variable(&self, die: DieRef, kind: VariableKind, frame_base: Option<DieRef>) -> DwarfResult<Option<Variable>> {
+        let name = match self.attr_string_with_origin(die, gimli::DW_AT_name)? {
+            Some(name) => name,
+            None => return Ok(None),
+        };
+        Ok(Some(Variable {
+            name,
+            kind,
+            die,
+            type_die: self.attr_ref_with_origin(die, gimli::DW_AT_type)?,
+            frame_base,
+        }))
+    }
+
+    pub(super) fn entry_tag(&self, die: DieRef) -> DwarfResult<DwTag> {
+        Ok(self.units[die.unit].entry(die.offset)?.tag())
+    }
+
+    pub(super) fn attr(&self, die: DieRef, name: DwAt) -> DwarfResult<Option<AttributeValue<DwarfReader>>> {
+        Ok(self.units[die.unit].entry(die.offset)?.attr_value(name)?)
+    }
+
+    // Concrete instances of inlined or abstract functions refer to the DIE with the name and type
+    fn attr_with_origin(&self, die: DieRef, name: DwAt) -> DwarfResult<Option<(DieRef, AttributeValue<DwarfReader>)>> {
+        let mut die = die;
+        for _ in 0..8 {
+            if let Some(value) = self.attr(die, name)? {
+                return Ok(Some((die, value)));
+            }
+            let origin = match self.attr(die, gimli::DW_AT_abstract_origin)? {
+                Some(origin) => Some(origin),
+                None => self.attr(die, gimli::DW_AT_specification)?,
+            };
+            match origin.and_then(|origin| self.resolve_ref(die.unit, origin)) {
+                Some(origin) => die = origin,
+                None => return Ok(None),
+            }
+        }
+        Ok(None)
+    }
+
+    pub(super) fn attr_string_with_origin(&self, die: DieRef, name: DwAt) -> DwarfResult<Option<String>> {
+        match self.attr_with_origin(die, name)? {
+            Some((die, value)) => Ok(Some(self.attr_string(die, value)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub(super) fn attr_ref_with_origin(&self, die: DieRef, name: DwAt) -> DwarfResult<Option<DieRef>> {
+        Ok(self
+            .attr_with_origin(die, name)?
+            .and_then(|(die, value)| self.resolve_ref(die.unit, value)))
+    }
+
+    pub(super) fn attr_string(&self, die: DieRef, value: AttributeValue<DwarfReader>) -> DwarfResult<String> {
+        let string = self.unit_ref(die.unit).attr_string(value)?;
+        Ok(string.to_string_lossy()?.into_owned())
+    }
+
+    pub(super) fn resolve_ref(&self, unit_index: usize, value: AttributeValue<DwarfReader>) -> Option<DieRef> {
+        match value {
+            AttributeValue::UnitRef(offset) => Some(DieRef::new(unit_index, offset)),
+            AttributeValue::DebugInfoRef(offset) => self.units.iter().enumerate().find_map(|(index, unit)| {
+                offset
+                    .to_unit_offset(&unit.header)
+                    .map(|offset: UnitOffset| DieRef::new(index, offset))
+            }),
+            _ => None,
+        }
+    }
+
+    pub(super) fn variable_place(&self, frame: &FrameState, variable: &Variable) -> DwarfResult<Place> {
+        match self.attr(variable.die, gimli::DW_AT_location)? {
+            Some(location) => self.evaluate_location(frame, variable.die.unit, location, variable.frame_base),
+            None => Ok(Place::Unavailable),
+        }
+    }
+
+    fn evaluate_location(
+        &self,
+        frame: &FrameState,
+        unit_index: usize,
+        location: AttributeValue<DwarfReader>,
+        frame_base: Option<DieRef>,
+    ) -> DwarfResult<Place> {
+        if let AttributeValue::Exprloc(expr) = location {
+            return self.evaluate(frame, unit_index, expr, frame_base);
+        }
+        let address = match self.code.address(frame.pos) {
+            Some(address) => address,
+            None => return Ok(Place::Unavailable),
+        };
+        let mut locations = match self.unit_ref(unit_index).attr_locations(location)? {
+            Some(locations) => locations,
+            None => return Ok(Place::Unavailable),
+        };
+        while let Some(entry) = locations.next()? {
+            if entry.range.begin <= address && address < entry.range.end {
+                return self.evaluate(frame, unit_index, entry.data, frame_base);
+            }
+        }
+        Ok(Place::Unavailable)
+    }
+
+    // Evaluates a DWARF expression including the DW_OP_WASM_location extension. Expressions ending with a wasm
+    // location or DW_OP_stack_value describe the value itself, otherwise the result is a memory address.
+    fn evaluate(
+        &self,
+        frame: &FrameState,
+        unit_index: usize,
+        expr: Expression<DwarfReader>,
+        frame_base: Option<DieRef>,
+    ) -> DwarfResult<Place> {
+        let mut operations = expr.operations(self.units[unit_index].encoding());
+        let mut stack: Vec<u64> = Vec::new();
+        let mut is_value = false;
+        let mut is_empty = true;
+        while let Some(operation) = operations.next()? {
+            is_empty = false;
+            is_value = false;
+            match operation {
+                Operation::WasmLocal { index } => {
+                    let local = frame
+                        .locals
+                        .get(index as usize)
+                        .ok_or(DwarfError::UnsupportedExpression)?;
+                    stack.push(value_bits(local));
+                    is_value = true;
+                }
+                Operation::WasmGlobal { index } => {
+                    let global = frame.vm.globals().get(index as usize);
+                    stack.push(value_bits(global.ok_or(DwarfError::UnsupportedExpression)?));
+                    is_value = true;
+                }
+                Operation::WasmStack { index } => {
+                    let value_stack = frame.vm.value_stack();
+                    let value = value_stack.iter().rev().nth(index as usize);
+                    stack.push(value_bits(value.ok_or(DwarfError::UnsupportedExpression)?));
+                    is_value = true;
+                }
+                Operation::StackValue => is_value = true,
+                Operation::Address { address } => stack.push(address),
+                Operation::UnsignedConstant { value } => stack.push(value),
+                Operation::SignedConstant { value } => stack.push(value as u64),
+                Operation::PlusConstant { value } => {
+                    let top = pop(&mut stack)?;
+                    stack.push(top.wrapping_add(value));
+                }
+                Operation::FrameOffset { offset } => {
+                    let frame_base = self.frame_base(frame, frame_base)?;
+                    stack.push(frame_base.wrapping_add(offset as u64));
+                }
+                Operation::Deref { size, space: false, .. } => {
+                    // Values on the stack are at most 8 bytes
+                    if size > 8 {
+                        return Err(DwarfError::UnsupportedExpression);
+                    }
+                    let address = pop(&mut stack)?;
+                    let bytes = frame.vm.default_memory()?.read(address as u32, u32::from(size))?;
+                    let mut value = [0; 8];
+                    value[..bytes.len()].copy_from_slice(bytes);
+                    stack.push(u64::from_le_bytes(value));
+                }
+                Operation::Drop => {
+                    pop(&mut stack)?;
+                }
+                Operation::Pick { index } => {
+                    let index = stack.len().checked_sub(index as usize + 1);
+                    let value = index.and_then(|index| stack.get(index));
+                    stack.push(*value.ok_or(DwarfError::UnsupportedExpression)?);
+                }
+                Operation::Swap => {
+                    let (a, b) = (pop(&mut stack)?, pop(&mut stack)?);
+                    stack.push(a);
+                    stack.push(b);
+                }
+                Operation::Plus | Operation::Minus | Operation::Mul | Operation::And | Operation::Or => {
+                    let (b, a) = (pop(&mut stack)?, pop(&mut stack)?);
+                    stack.push(match operation {
+                        Operation::Plus => a.wrapping_add(b),
+                        Operation::Minus => a.wrapping_sub(b),
+                        Operation::Mul => a.wrapping_mul(b),
+                        Operation::And => a & b,
+                        _ => a | b,
+                    });
+                }
+                Operation::ImplicitValue { data } => {
+                    let mut value = [0; 8];
+                    let len = data.len().min(8);
+                    value[..len].copy_from_slice(&data.to_slice()?[..len]);
+                    return Ok(Place::Bits(u64::from_le_bytes(value)));
+                }
+                // Variables split into pieces are not supported
+                Operation::Piece { .. } => return Ok(Place::Unavailable),
+                _ => return Err(DwarfError::UnsupportedExpression),
+            }
+        }
+        if is_empty {
+            return Ok(Place::Unavailable);
+        }
+        let top = pop(&mut stack)?;
+        if is_value {
+            Ok(Place::Bits(top))
+        } else {
+            Ok(Place::Memory(top as u32))
+        }
+    }
+
+    fn frame_base(&self, frame: &FrameState, subprogram: Option<DieRef>) -> DwarfResult<u64> {
+        let subprogram = subprogram.ok_or(DwarfError::UnsupportedExpression)?;
+        let location = self
+            .attr(subprogram, gimli::DW_AT_frame_base)?
+            .ok_or(DwarfError::UnsupportedExpression)?;
+        match self.evaluate_location(frame, subprogram.unit, location, None)? {
+            Place::Memory(address) => Ok(u64::from(address)),
+            Place::Bits(bits) => Ok(bits),
+            Place::Unavailable => Err(DwarfError::OptimizedOut),
+        }
+    }
+}
+
+fn pop(stack: &mut Vec<u64>) -> DwarfResult<u64> {
+    stack.pop().ok_or(DwarfError::UnsupportedExpression)
+}
+
+fn value_bits(value: &Value) -> u64 {
+    match *value {
+        Value::I32(val) => u64::from(val as u32),
+        Value::I64(val) => val as u64,
+        Value::F32(val) => u64::from(val.to_bits()),
+        Value::F64(val) => val.to_bits(),
+    }
+}
@@ -0,0 +1,653 @@
+use std::fmt;
+use std::iter::Peekable;
+use std::str::{Chars, FromStr};
+
+use thiserror::Error;
+
+use crate::vm::{Trap, VM};
+use crate::{Value, ValueType, F32, F64};
+
+#[derive(Error, Clone, Debug)]
+pub enum ExprError {
+    #[error("Empty expression")]
+    Empty,
+    #[error("Unexpected end of expression")]
+    UnexpectedEnd,
+    #[error("Unexpected \"{0}\" in expression")]
+    UnexpectedToken(String),
+    #[error("Invalid number \"{0}\"")]
+    InvalidNumber(String),
+    #[error("Unknown identifier \"{0}\"")]
+    UnknownIdentifier(String),
+    #[error("No local with index {0}")]
+    NoLocal(u32),
+    #[error("No global with index {0}")]
+    NoGlobal(u32),
+    #[error("No value at stack slot {0}")]
+    NoStackValue(u32),
+    #[error("Operator \"{op}\" is not defined for \"{value_type}\"")]
+    InvalidOperand { op: &'static str, value_type: ValueType },
+    #[error("Division by zero")]
+    DivisionByZero,
+    #[error("{0}")]
+    Trap(Trap),
+}
+
+impl From<Trap> for ExprError {
+    fn from(trap: Trap) -> Self {
+        ExprError::Trap(trap)
+    }
+}
+
+pub type ExprResult<T> = Result<T, ExprError>;
+
+#[derive(Clone, Copy, Debug)]
+enum Load {
+    I8,
+    I16,
+    I32,
+    I64,
+    F32,
+    F64,
+}
+
+#[derive(Clone, Debug)]
+enum Node {
+    Const(Value),
+    Local(u32),
+    Global(u32),
+    Stack(u32),
+    Load(Load, Box<Node>),
+    Cast(ValueType, Box<Node>),
+    Unary(&'static str, Box<Node>),
+    Binary(&'static str, Box<Node>, Box<Node>),
+}
+
+// Binary operators ordered by precedence, from weakest to strongest binding
+const BINARY_OPS: &[&[&str]] = &[
+    &["||"],
+    &["&&"],
+    &["|"],
+    &["^"],
+    &["&"],
+    &["==", "!="],
+    &["<=", ">=", "<", ">", "<=u", ">=u", "<u", ">u"],
+    &["<<", ">>", ">>u"],
+    &["+", "-"],
+    &["*", "/", "%", "/u", "%u"],
+];
+
+// Longer operators come first so that e.g. "<=u" isn't tokenized as "<=" followed by "u"
+const OPERATORS: &[&str] = &[
+    "<=u", ">=u", ">>u", "<u", ">u", "/u", "%u", "||", "&&", "==", "!=", "<=", ">=", "<<", ">>", "|", "^", "&", "<",
+    ">", "+", "-", "*", "/", "%", "!", "~", "(", ")",
+];
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(String),
+    Ident(String),
+    Op(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(val) | Token::Ident(val) => write!(f, "{}", val),
+            Token::Op(op) => write!(f, "{}", op),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> ExprResult<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars: Peekable<Chars<'_>> = source.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut number = String::new();
+            while let Some(&c) = chars.peek() {
+                let is_exponent_sign = (c == '+' || c == '-') && number.ends_with(['e', 'E']);
+                let is_hex = number.starts_with("0x") || number.starts_with("0X");
+                if c.is_ascii_alphanumeric() || c == '.' || c == '_' || (is_exponent_sign && !is_hex) {
+                    number.push(c);
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            tokens.push(Token::Number(number));
+        } else if c.is_alphabetic() || c == '_' {
+            let mut ident = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_alphanumeric() || c == '_' {
+                    ident.push(c);
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            tokens.push(Token::Ident(ident));
+        } else {
+            let rest: String = chars.clone().take(3).collect();
+            match OPERATORS.iter().find(|op| rest.starts_with(*op)) {
+                Some(op) => {
+                    tokens.push(Token::Op(op));
+                    for _ in 0..op.len() {
+                        chars.next();
+                    }
+                }
+                None => return Err(ExprError::UnexpectedToken(c.to_string())),
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn parse_number(number: &str) -> ExprResult<Value> {
+    let digits = number.replace('_', "");
+    let invalid = || ExprError::InvalidNumber(number.to_string());
+    let int_val = if let Some(hex) = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        u64::from_str_radix(hex, 16).map_err(|_| invalid())?
+    } else if digits.contains(['.', 'e', 'E']) {
+        return f64::from_str(&digits).map(Value::from).map_err(|_| invalid());
+    } else {
+        u64::from_str(&digits).map_err(|_| invalid())?
+    };
+    if int_val <= u64::from(u32::MAX) {
+        Ok(Value::from(int_val as u32))
+    } else {
+        Ok(Value::from(int_val))
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> ExprResult<Token> {
+        let token = self.tokens.get(self.pos).cloned().ok_or(ExprError::UnexpectedEnd)?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, op: &'static str) -> ExprResult<()> {
+        match self.next()? {
+            Token::Op(found) if found == op => Ok(()),
+            other => Err(ExprError::UnexpectedToken(other.to_string())),
+        }
+    }
+
+    fn parse_binary(&mut self, level: usize) -> ExprResult<Node> {
+        if level == BINARY_OPS.len() {
+            return self.parse_unary();
+        }
+        let mut lhs = self.parse_binary(level + 1)?;
+        while let Some(Token::Op(op)) = self.peek() {
+            let op = *op;
+            if !BINARY_OPS[level].contains(&op) {
+                break;
+            }
+            self.pos += 1;
+            let rhs = self.parse_binary(level + 1)?;
+            lhs = Node::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> ExprResult<Node> {
+        match self.next()? {
+            Token::Op(op @ "-") | Token::Op(op @ "!") | Token::Op(op @ "~") => {
+                Ok(Node::Unary(op, Box::new(self.parse_unary()?)))
+            }
+            Token::Op("(") => {
+                let node = self.parse_binary(0)?;
+                self.expect(")")?;
+                Ok(node)
+            }
+            Token::Number(number) => Ok(Node::Const(parse_number(&number)?)),
+            Token::Ident(ident) => self.parse_ident(&ident),
+            other => Err(ExprError::UnexpectedToken(other.to_string())),
+        }
+    }
+
+    fn parse_index(&mut self) -> ExprResult<u32> {
+        match self.next()? {
+            Token::Number(number) => match parse_number(&number)? {
+                Value::I32(index) => Ok(index as u32),
+                _ => Err(ExprError::InvalidNumber(number)),
+            },
+            other => Err(ExprError::UnexpectedToken(other.to_string())),
+        }
+    }
+
+    fn parse_call(&mut self) -> ExprResult<Box<Node>> {
+        self.expect("(")?;
+        let arg = self.parse_binary(0)?;
+        self.expect(")")?;
+        Ok(Box::new(arg))
+    }
+
+    fn parse_ident(&mut self, ident: &str) -> ExprResult<Node> {
+        let node = match ident {
+            "local" => Node::Local(self.parse_index()?),
+            "global" => Node::Global(self.parse_index()?),
+            "stack" => Node::Stack(self.parse_index()?),
+            "load8" => Node::Load(Load::I8, self.parse_call()?),
+            "load16" => Node::Load(Load::I16, self.parse_call()?),
+            "load32" => Node::Load(Load::I32, self.parse_call()?),
+            "load64" => Node::Load(Load::I64, self.parse_call()?),
+            "loadf32" => Node::Load(Load::F32, self.parse_call()?),
+            "loadf64" => Node::Load(Load::F64, self.parse_call()?),
+            "i32" => Node::Cast(ValueType::I32, self.parse_call()?),
+            "i64" => Node::Cast(ValueType::I64, self.parse_call()?),
+            "f32" => Node::Cast(ValueType::F32, self.parse_call()?),
+            "f64" => Node::Cast(ValueType::F64, self.parse_call()?),
+            _ => return Err(ExprError::UnknownIdentifier(ident.to_string())),
+        };
+        Ok(node)
+    }
+}
+
+// An expression over the state of a paused VM, e.g. "local 2 == 0x1000" or "load32(global 0 + 8) > 5"
+#[derive(Clone, Debug)]
+pub struct Expr {
+    source: String,
+    root: Node,
+}
+
+impl Expr {
+    pub fn parse(source: &str) -> ExprResult<Expr> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            pos: 0,
+        };
+        if parser.tokens.is_empty() {
+            return Err(ExprError::Empty);
+        }
+        let root = parser.parse_binary(0)?;
+        if let Some(token) = parser.peek() {
+            return Err(ExprError::UnexpectedToken(token.to_string()));
+        }
+        Ok(Expr {
+            source: source.trim().to_string(),
+            root,
+        })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn eval(&self, vm: &VM) -> ExprResult<Value> {
+        eval_node(&self.root, vm)
+    }
+
+    pub fn eval_condition(&self, vm: &VM) -> ExprResult<bool> {
+        Ok(is_true(self.eval(vm)?))
+    }
+}
+
+impl FromStr for Expr {
+    type Err = ExprError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Expr::parse(s)
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+fn eval_node(node: &Node, vm: &VM) -> ExprResult<Value> {
+    let val = match node {
+        Node::Const(val) => *val,
+        Node::Local(index) => *vm.locals()?.get(*index as usize).ok_or(ExprError::NoLocal(*index))?,
+        Node::Global(index) => *vm.globals().get(*index as usize).ok_or(ExprError::NoGlobal(*index))?,
+        Node::Stack(index) => {
+            let stack = vm.value_stack();
+            *stack
+                .len()
+                .checked_sub(*index as usize + 1)
+                .and_then(|slot| stack.get(slot))
+                .ok_or(ExprError::NoStackValue(*index))?
+        }
+        Node::Load(load, address) => {
+            let address = match eval_node(address, vm)? {
+                Value::I32(address) => address as u32,
+                other => {
+                    return Err(ExprError::InvalidOperand {
+                        op: "load",
+                        value_type: other.value_type(),
+                    })
+                }
+            };
+            let memory = vm.default_memory()?;
+            match load {
+                Load::I8 => Value::from(u32::from(memory.load::<u8>(address)?)),
+                Load::I16 => Value::from(u32::from(memory.load::<u16>(address)?)),
+                Load::I32 => Value::from(memory.load::<u32>(address)?),
+                Load::I64 => Value::from(memory.load::<u64>(address)?),
+                Load::F32 => Value::from(memory.load::<F32>(address)?),
+                Load::F64 => Value::from(memory.load::<F64>(address)?),
+            }
+        }
+        Node::Cast(value_type, val) => cast(eval_node(val, vm)?, *value_type),
+        Node::Unary(op, val) => unary(op, eval_node(val, vm)?)?,
+        Node::Binary("&&", lhs, rhs) => {
+            Value::from((is_true(eval_node(lhs, vm)?) && is_true(eval_node(rhs, vm)?)) as u32)
+        }
+        Node::Binary("||", lhs, rhs) => {
+            Value::from((is_true(eval_node(lhs, vm)?) || is_true(eval_node(rhs, vm)?)) as u32)
+        }
+        Node::Binary(op, lhs, rhs) => binary(op, eval_node(lhs, vm)?, eval_node(rhs, vm)?)?,
+    };
+    Ok(val)
+}
+
+fn is_true(val: Value) -> bool {
+    match val {
+        Value::I32(val) => val != 0,
+        Value::I64(val) => val != 0,
+        Value::F32(val) => val.to_float() != 0.0,
+        Value::F64(val) => val.to_float() != 0.0,
+    }
+}
+
+fn cast(val: Value, value_type: ValueType) -> Value {
+    match (val, value_type) {
+        (Value::I32(val), ValueType::I64) => Value::from(i64::from(val)),
+        (Value::I32(val), ValueType::F32) => Value::from(val as f32),
+        (Value::I32(val), ValueType::F64) => Value::from(f64::from(val)),
+        (Value::I64(val), ValueType::I32) => Value::from(val as i32),
+        (Value::I64(val), ValueType::F32) => Value::from(val as f32),
+        (Value::I64(val), ValueType::F64) => Value::from(val as f64),
+        (Value::F32(val), ValueType::I32) => Value::from(val.to_float() as i32),
+        (Value::F32(val), ValueType::I64) => Value::from(val.to_float() as i64),
+        (Value::F32(val), ValueType::F64) => Value::from(f64::from(val.to_float())),
+        (Value::F64(val), ValueType::I32) => Value::from(val.to_float() as i32),
+        (Value::F64(val), ValueType::I64) => Value::from(val.to_float() as i64),
+        (Value::F64(val), ValueType::F32) => Value::from(val.to_float() as f32),
+        (val, _) => val,
+    }
+}
+
+fn unary(op: &'static str, val: Value) -> ExprResult<Value> {
+    let result = match (op, val) {
+        ("!", val) => Value::from(!is_true(val) as u32),
+        ("-", Value::I32(val)) => Value::from(val.wrapping_neg()),
+        ("-", Value::I64(val)) => Value::from(val.wrapping_neg()),
+        ("-", Value::F32(val)) => Value::from(-val.to_float()),
+        ("-", Value::F64(val)) => Value::from(-val.to_float()),
+        ("~", Value::I32(val)) => Value::from(!val),
+        ("~", Value::I64(val)) => Value::from(!val),
+        (op, val) => {
+            return Err(ExprError::InvalidOperand {
+                op,
+                value_type: val.value_type(),
+            })
+        }
+    };
+    Ok(result)
+}
+
+// Mixed operands are widened to i64, or to f64 if either of them is a float
+fn promote(lhs: Value, rhs: Value) -> (Value, Value) {
+    if lhs.value_type() == rhs.value_type() {
+        return (lhs, rhs);
+    }
+    let is_float = |val: Value| matches!(val, Value::F32(_) | Value::F64(_));
+    if is_float(lhs) || is_float(rhs) {
+        (cast(lhs, ValueType::F64), cast(rhs, ValueType::F64))
+    } else {
+        (cast(lhs, ValueType::I64), cast(rhs, ValueType::I64))
+    }
+}
+
+macro_rules! int_binop {
+    ($op:expr, $lhs:expr, $rhs:expr) => {{
+        let (lhs, rhs) = ($lhs, $rhs);
+        match $op {
+            "+" => Value::from(lhs.wrapping_add(rhs)),
+            "-" => Value::from(lhs.wrapping_sub(rhs)),
+            "*" => Value::from(lhs.wrapping_mul(rhs)),
+            "/" if rhs == 0 => return Err(ExprError::DivisionByZero),
+            "/" => Value::from(lhs.wrapping_div(rhs)),
+            "%" if rhs == 0 => return Err(ExprError::DivisionByZero),
+            "%" => Value::from(lhs.wrapping_rem(rhs)),
+            "&" => Value::from(lhs & rhs),
+            "|" => Value::from(lhs | rhs),
+            "^" => Value::from(lhs ^ rhs),
+            "<<" => Value::from(lhs.wrapping_shl(rhs as u32)),
+            ">>" => Value::from(lhs.wrapping_shr(rhs as u32)),
+            op => compare(op, lhs.partial_cmp(&rhs)),
+        }
+    }};
+}
+
+macro_rules! float_binop {
+    ($op:expr, $value_type:expr, $lhs:expr, $rhs:expr) => {{
+        let (lhs, rhs) = ($lhs, $rhs);
+        match $op {
+            "+" => Value::from(lhs + rhs),
+            "-" => Value::from(lhs - rhs),
+            "*" => Value::from(lhs * rhs),
+            "/" => Value::from(lhs / rhs),
+            "%" => Value::from(lhs % rhs),
+            "&" | "|" | "^" | "<<" | ">>" => {
+                return Err(ExprError::InvalidOperand {
+                    op: $op,
+                    value_type: $value_type,
+                })
+            }
+            op => compare(op, lhs.partial_cmp(&rhs)),
+        }
+    }};
+}
+
+fn binary(op: &'static str, lhs: Value, rhs: Value) -> ExprResult<Value> {
+    if let Some(unsigned_op) = op.strip_suffix('u') {
+        return unsigned_binary(op, unsigned_op, lhs, rhs);
+    }
+    let result = match promote(lhs, rhs) {
+        (Value::I32(lhs), Value::I32(rhs)) => int_binop!(op, lhs, rhs),
+        (Value::I64(lhs), Value::I64(rhs)) => int_binop!(op, lhs, rhs),
+        (Value::F32(lhs), Value::F32(rhs)) => float_binop!(op, ValueType::F32, lhs.to_float(), rhs.to_float()),
+        (Value::F64(lhs), Value::F64(rhs)) => float_binop!(op, ValueType::F64, lhs.to_float(), rhs.to_float()),
+        _ => unreachable!(),
+    };
+    Ok(result)
+}
+
+// Integers are signed by default, the "u" operators reinterpret both operands as unsigned
+fn unsigned_binary(op: &'static str, unsigned_op: &'static str, lhs: Value, rhs: Value) -> ExprResult<Value> {
+    let result = match (lhs, rhs) {
+        (Value::I32(lhs), Value::I32(rhs)) => int_binop!(unsigned_op, lhs as u32, rhs as u32),
+        (Value::I64(lhs), Value::I64(rhs)) => int_binop!(unsigned_op, lhs as u64, rhs as u64),
+        (Value::I32(lhs), Value::I64(rhs)) => int_binop!(unsigned_op, u64::from(lhs as u32), rhs as u64),
+        (Value::I64(lhs), Value::I32(rhs)) => int_binop!(unsigned_op, lhs as u64, u64::from(rhs as u32)),
+        (Value::F32(_), _) | (Value::F64(_), _) => {
+            return Err(ExprError::InvalidOperand {
+                op,
+                value_type: lhs.value_type(),
+            })
+        }
+        (_, rhs) => {
+            return Err(ExprError::InvalidOperand {
+                op,
+                value_type: rhs.value_type(),
+            })
+        }
+    };
+    Ok(result)
+}
+
+fn compare(op: &str, ordering: Option<std::cmp::Ordering>) -> Value {
+    use std::cmp::Ordering::*;
+    let result = match (op, ordering) {
+        (_, None) => op == "!=",
+        ("==", Some(ordering)) => ordering == Equal,
+        ("!=", Some(ordering)) => ordering != Equal,
+        ("<", Some(ordering)) => ordering == Less,
+        ("<=", Some(ordering)) => ordering != Greater,
+        (">", Some(ordering)) => ordering == Greater,
+        (">=", Some(ordering)) => ordering != Less,
+        _ => unreachable!(),
+    };
+    Value::from(result as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Expr, ExprError, ExprResult};
+    use crate::test_utils::load_wat;
+    use crate::vm::Trap;
+    use crate::{Debugger, Value};
+
+    // Paused at the start of a function with locals 7 and -3 and the value 42 on the stack
+    fn paused_debugger() -> Debugger {
+        let mut dbg = load_wat(
+            r#"(module
+                (memory 1)
+                (data (i32.const 16) "\01\02\03\04\05\06\07\08")
+                (data (i32.const 32) "\f0\ff\ff\ff\ff\ff\ff\ff\00\00\00\80")
+                (global i32 (i32.const 100))
+                (global f64 (f64.const 1.5))
+                (func (local i32 i64) nop))"#,
+        );
+        assert!(dbg.start(Some(0)).unwrap().is_none());
+        let vm = dbg.get_vm_mut().unwrap();
+        vm.locals_mut()
+            .unwrap()
+            .copy_from_slice(&[Value::I32(7), Value::I64(-3)]);
+        vm.value_stack_mut().push(Value::I32(42));
+        dbg
+    }
+
+    fn eval(dbg: &Debugger, source: &str) -> ExprResult<Value> {
+        Expr::parse(source)?.eval(dbg.vm().unwrap())
+    }
+
+    fn check(dbg: &Debugger, source: &str, expected: Value) {
+        assert_eq!(eval(dbg, source).unwrap(), expected, "{}", source);
+    }
+
+    #[test]
+    fn precedence() {
+        let dbg = paused_debugger();
+        check(&dbg, "1 + 2 * 3", Value::I32(7));
+        check(&dbg, "(1 + 2) * 3", Value::I32(9));
+        check(&dbg, "10 - 4 - 3", Value::I32(3));
+        check(&dbg, "2 * 3 % 4", Value::I32(2));
+        check(&dbg, "1 << 2 + 1", Value::I32(8));
+        check(&dbg, "1 | 2 ^ 3 & 1", Value::I32(3));
+        check(&dbg, "1 < 2 == 1", Value::I32(1));
+        check(&dbg, "0 || 1 && 0", Value::I32(0));
+        check(&dbg, "1 + 1 == 2 && 3 > 2", Value::I32(1));
+    }
+
+    #[test]
+    fn unary_operators() {
+        let dbg = paused_debugger();
+        check(&dbg, "-5", Value::I32(-5));
+        check(&dbg, "--5", Value::I32(5));
+        check(&dbg, "-2.5", Value::from(-2.5f64));
+        check(&dbg, "!0", Value::I32(1));
+        check(&dbg, "!7", Value::I32(0));
+        check(&dbg, "~0", Value::I32(-1));
+        check(&dbg, "-(1 + 2) * 2", Value::I32(-6));
+        check(&dbg, "0x1_0000_0000", Value::I64(0x1_0000_0000));
+        check(&dbg, "-0x1_0000_0000", Value::I64(-0x1_0000_0000));
+    }
+
+    #[test]
+    fn operands() {
+        let dbg = paused_debugger();
+        check(&dbg, "local 0", Value::I32(7));
+        check(&dbg, "local 1 + local 0", Value::I64(4));
+        check(&dbg, "global 0 * 2", Value::I32(200));
+        check(&dbg, "global 1 + 1", Value::from(2.5f64));
+        check(&dbg, "stack 0", Value::I32(42));
+        check(&dbg, "load8(16)", Value::I32(1));
+        check(&dbg, "load16(16)", Value::I32(0x0201));
+        check(&dbg, "load32(16 + 4)", Value::I32(0x0807_0605));
+        check(&dbg, "load64(16)", Value::I64(0x0807_0605_0403_0201));
+        check(&dbg, "i64(local 0)", Value::I64(7));
+        check(&dbg, "i32(global 1)", Value::I32(1));
+        check(&dbg, "f32(local 0)", Value::from(7.0f32));
+    }
+
+    #[test]
+    fn signedness() {
+        let dbg = paused_debugger();
+        check(&dbg, "load8(32)", Value::I32(0xf0));
+        check(&dbg, "load16(32)", Value::I32(0xfff0));
+        check(&dbg, "load32(32)", Value::I32(-16));
+        check(&dbg, "load32(32) == 0xfffffff0", Value::I32(1));
+        check(&dbg, "load32(32) > 5", Value::I32(0));
+        check(&dbg, "load32(32) >u 5", Value::I32(1));
+        check(&dbg, "load32(40) == 0x80000000", Value::I32(1));
+        check(&dbg, "load64(32) <u 0", Value::I32(0));
+        check(&dbg, "load64(32) >=u 0xfffffff0", Value::I32(1));
+        check(&dbg, "0x7fffffff", Value::I32(i32::MAX));
+        check(&dbg, "0x80000000", Value::I32(i32::MIN));
+        check(&dbg, "0xffffffff", Value::I32(-1));
+        check(&dbg, "4294967295", Value::I32(-1));
+        check(&dbg, "4294967296", Value::I64(0x1_0000_0000));
+        check(&dbg, "0x80000000 < 0x7fffffff", Value::I32(1));
+        check(&dbg, "0x80000000 <u 0x7fffffff", Value::I32(0));
+        check(&dbg, "0x80000000 >u 0x7fffffff", Value::I32(1));
+        check(&dbg, "-1 <=u 0xffffffff", Value::I32(1));
+        check(&dbg, "1<=u2", Value::I32(1));
+        check(&dbg, "-1 / 2", Value::I32(0));
+        check(&dbg, "-1 /u 2", Value::I32(i32::MAX));
+        check(&dbg, "-1 % 10", Value::I32(-1));
+        check(&dbg, "-1 %u 10", Value::I32(5));
+        check(&dbg, "-8 >> 1", Value::I32(-4));
+        check(&dbg, "-8 >>u 1", Value::I32(0x7fff_fffc));
+        check(&dbg, "local 1 >>u 62", Value::I64(3));
+        check(&dbg, "local 1 <u local 0", Value::I32(0));
+        check(&dbg, "local 1 < local 0", Value::I32(1));
+    }
+
+    #[test]
+    fn errors() {
+        let dbg = paused_debugger();
+        let error = |source| eval(&dbg, source).unwrap_err();
+        assert!(matches!(error(""), ExprError::Empty));
+        assert!(matches!(error("1 +"), ExprError::UnexpectedEnd));
+        assert!(matches!(error("(1"), ExprError::UnexpectedEnd));
+        assert!(matches!(error("1 2"), ExprError::UnexpectedToken(token) if token == "2"));
+        assert!(matches!(error("1 $ 2"), ExprError::UnexpectedToken(token) if token == "$"));
+        assert!(matches!(error("1x"), ExprError::InvalidNumber(number) if number == "1x"));
+        assert!(matches!(error("foo"), ExprError::UnknownIdentifier(ident) if ident == "foo"));
+        assert!(matches!(error("local 9"), ExprError::NoLocal(9)));
+        assert!(matches!(error("global 9"), ExprError::NoGlobal(9)));
+        assert!(matches!(error("stack 1"), ExprError::NoStackValue(1)));
+        assert!(matches!(error("1 / 0"), ExprError::DivisionByZero));
+        assert!(matches!(error("local 1 % 0"), ExprError::DivisionByZero));
+        assert!(matches!(error("~1.0"), ExprError::InvalidOperand { op: "~", .. }));
+        assert!(matches!(error("1.0 << 1"), ExprError::InvalidOperand { op: "<<", .. }));
+        assert!(matches!(error("1.0 <u 1"), ExprError::InvalidOperand { op: "<u", .. }));
+        assert!(matches!(error("1 /u 2.0"), ExprError::InvalidOperand { op: "/u", .. }));
+        assert!(matches!(error("1 %u 0"), ExprError::DivisionByZero));
+        assert!(matches!(
+            error("load32(1.0)"),
+            ExprError::InvalidOperand { op: "load", .. }
+        ));
+        assert!(matches!(
+            error("load32(65535)"),
+            ExprError::Trap(Trap::MemoryAccessOutOfRange(_))
+        ));
+    }
+}
@@ -1,22 +1,33 @@
-use std::cell::RefCell;
+use std::borrow::Cow;
+use std::cell::{OnceCell, RefCell};
 use std::rc::Rc;
 
-use bwasm::Module;
-
-use crate::Breakpoints;
+use crate::{demangle, Breakpoints, DebugInfo, DemangleStyle, DwarfError, Internal, Module};
 
 pub struct File {
     file_path: String,
     module: Rc<Module>,
     breakpoints: Rc<RefCell<Breakpoints>>,
+    debug_info: Result<Option<DebugInfo>, DwarfError>,
+    function_names: OnceCell<Vec<(String, u32)>>,
 }
 
 impl File {
     pub fn new(file_path: String, module: Module) -> Self {
+        File::with_debug_info(file_path, module, Ok(None))
+    }
+
+    pub fn with_debug_info(
+        file_path: String,
+        module: Module,
+        debug_info: Result<Option<DebugInfo>, DwarfError>,
+    ) -> Self {
         File {
             file_path,
             module: Rc::new(module),
             breakpoints: Rc::new(RefCell::new(Breakpoints::new())),
+            debug_info,
+            function_names: OnceCell::new(),
         }
     }
 
@@ -31,4 +42,74 @@ impl File {
     pub const fn breakpoints(&self) -> &Rc<RefCell<Breakpoints>> {
         &self.breakpoints
     }
+
+    pub fn debug_info(&self) -> Option<&DebugInfo> {
+        self.debug_info.as_ref().ok().and_then(Option::as_ref)
+    }
+
+    // Set if the binary contains DWARF sections which couldn't be read
+    pub fn debug_info_error(&self) -> Option<&DwarfError> {
+        self.debug_info.as_ref().err()
+    }
+
+    pub fn function_name(&self, func_index: u32, style: DemangleStyle) -> Cow<'_, str> {
+        demangle(self.module.func(func_index).name(), style)
+    }
+
+    // Functions can be referred to by their name, its demangled forms and by every name they are exported under.
+    // Demangling every function is slow for large binaries, so the names are only collected once.
+    pub fn function_names(&self) -> &[(String, u32)] {
+        self.function_names.get_or_init(|| {
+            let mut names = Vec::new();
+            for (index, func) in self.module.functions().iter().enumerate() {
+                let index = index as u32;
+                names.push((func.name().to_owned(), index));
+                for &style in &[DemangleStyle::On, DemangleStyle::Short] {
+                    if let Cow::Owned(demangled) = demangle(func.name(), style) {
+                        names.push((demangled, index));
+                    }
+                }
+            }
+            for export in self.module.exports() {
+                if let Internal::Function(index) = *export.internal() {
+                    if self.module.func(index).name() != export.field() {
+                        names.push((export.field().to_owned(), index));
+                    }
+                }
+            }
+            names
+        })
+    }
+
+    // Falls back to functions whose short demangled name is qualified with a path ending in name, e.g. "area"
+    // finds "vars::area"
+    pub fn find_function(&self, name: &str) -> Option<u32> {
+        let names = self.function_names();
+        if let Some((_, index)) = names.iter().find(|(func_name, _)| func_name == name) {
+            return Some(*index);
+        }
+        let suffix = format!("::{}", name);
+        names
+            .iter()
+            .find(|(func_name, _)| func_name.ends_with(&suffix))
+            .map(|(_, index)| *index)
+    }
+
+    // WASI commands export "_start" and other binaries commonly export "main"
+    pub fn default_entry(&self) -> Option<u32> {
+        ["_start", "main"]
+            .iter()
+            .filter_map(|name| self.exported_function(name))
+            .find(|index| self.module.func(*index).func_type().params().is_empty())
+    }
+
+    fn exported_function(&self, name: &str) -> Option<u32> {
+        self.module
+            .exports()
+            .iter()
+            .find_map(|export| match *export.internal() {
+                Internal::Function(index) if export.field() == name => Some(index),
+                _ => None,
+            })
+    }
 }
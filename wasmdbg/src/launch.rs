@@ -0,0 +1,54 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Default, Debug)]
+pub struct LaunchConfig {
+    args: Vec<String>,
+    env: BTreeMap<String, String>,
+    stdin: Option<PathBuf>,
+}
+
+impl LaunchConfig {
+    pub fn new() -> Self {
+        LaunchConfig::default()
+    }
+
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    pub fn set_args(&mut self, args: Vec<String>) {
+        self.args = args;
+    }
+
+    pub const fn env(&self) -> &BTreeMap<String, String> {
+        &self.env
+    }
+
+    pub fn set_env(&mut self, key: &str, value: &str) {
+        self.env.insert(key.to_owned(), value.to_owned());
+    }
+
+    pub fn unset_env(&mut self, key: &str) -> bool {
+        self.env.remove(key).is_some()
+    }
+
+    pub fn clear_env(&mut self) {
+        self.env.clear();
+    }
+
+    pub fn env_strings(&self) -> Vec<String> {
+        self.env
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect()
+    }
+
+    pub fn stdin(&self) -> Option<&Path> {
+        self.stdin.as_deref()
+    }
+
+    pub fn set_stdin<P: Into<PathBuf>>(&mut self, stdin: Option<P>) {
+        self.stdin = stdin.map(Into::into);
+    }
+}
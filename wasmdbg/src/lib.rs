@@ -1,11 +1,29 @@
 mod breakpoints;
+mod coverage;
 mod debugger;
+mod demangle;
+mod dwarf;
+mod expr;
 mod file;
+mod launch;
+mod profiler;
+mod replay;
+#[cfg(test)]
+mod test_utils;
+mod tracer;
 pub mod vm;
-// mod wasi;
+pub mod wasi;
 mod wasm;
 
 pub use breakpoints::*;
+pub use coverage::*;
 pub use debugger::*;
+pub use demangle::*;
+pub use dwarf::*;
+pub use expr::*;
 pub use file::*;
+pub use launch::*;
+pub use profiler::*;
+pub use replay::*;
+pub use tracer::*;
 pub use wasm::*;
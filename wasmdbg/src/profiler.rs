@@ -0,0 +1,378 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::coverage::json_escape;
+use crate::Module;
+
+// The timeline of the exported traces stops after this many events to bound the memory use of long runs. The call
+// tree is not affected.
+const MAX_EVENTS: usize = 1 << 20;
+
+// A node of the call tree. The root node (index 0) has no function.
+struct CallNode {
+    func_index: Option<u32>,
+    parent: usize,
+    children: HashMap<u32, usize>,
+    self_count: u64,
+}
+
+#[derive(Clone, Copy)]
+enum ProfileEvent {
+    Open { func_index: u32, at: u64 },
+    Close { func_index: u32, at: u64 },
+}
+
+pub struct FunctionProfile {
+    pub func_index: u32,
+    pub self_count: u64,
+    pub inclusive_count: u64,
+    pub calls: u64,
+}
+
+// Counts the executed instructions per call stack. Time is measured in executed instructions.
+pub struct Profiler {
+    nodes: Vec<CallNode>,
+    current: usize,
+    calls: HashMap<u32, u64>,
+    events: Vec<ProfileEvent>,
+    max_events: usize,
+    // The instruction count at which the timeline stopped
+    events_end: Option<u64>,
+    instruction_count: u64,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Profiler {
+            nodes: vec![CallNode {
+                func_index: None,
+                parent: 0,
+                children: HashMap::new(),
+                self_count: 0,
+            }],
+            current: 0,
+            calls: HashMap::new(),
+            events: Vec::new(),
+            max_events: MAX_EVENTS,
+            events_end: None,
+            instruction_count: 0,
+        }
+    }
+
+    pub const fn instruction_count(&self) -> u64 {
+        self.instruction_count
+    }
+
+    // The end of the timeline, which is before the last instruction if the timeline was cut off
+    pub fn timeline_end(&self) -> u64 {
+        self.events_end.unwrap_or(self.instruction_count)
+    }
+
+    pub(crate) fn step(&mut self) {
+        self.nodes[self.current].self_count += 1;
+        self.instruction_count += 1;
+    }
+
+    pub(crate) fn enter(&mut self, func_index: u32) {
+        *self.calls.entry(func_index).or_insert(0) += 1;
+        self.open(func_index);
+    }
+
+    fn open(&mut self, func_index: u32) {
+        let next_index = self.nodes.len();
+        let current = &mut self.nodes[self.current];
+        let child = *current.children.entry(func_index).or_insert(next_index);
+        if child == next_index {
+            self.nodes.push(CallNode {
+                func_index: Some(func_index),
+                parent: self.current,
+                children: HashMap::new(),
+                self_count: 0,
+            });
+        }
+        self.current = child;
+        self.push_event(ProfileEvent::Open {
+            func_index,
+            at: self.instruction_count,
+        });
+    }
+
+    pub(crate) fn leave(&mut self) {
+        if let Some(func_index) = self.nodes[self.current].func_index {
+            self.push_event(ProfileEvent::Close {
+                func_index,
+                at: self.instruction_count,
+            });
+            self.current = self.nodes[self.current].parent;
+        }
+    }
+
+    fn push_event(&mut self, event: ProfileEvent) {
+        if self.events_end.is_some() {
+            return;
+        }
+        if self.events.len() >= self.max_events {
+            self.events_end = Some(self.instruction_count);
+            return;
+        }
+        self.events.push(event);
+    }
+
+    // Moves to the given call stack without counting calls, e.g. after the VM state was replaced
+    pub(crate) fn sync(&mut self, stack: &[u32]) {
+        if self.stack() == stack {
+            return;
+        }
+        while self.current != 0 {
+            self.leave();
+        }
+        for func_index in stack {
+            self.open(*func_index);
+        }
+    }
+
+    fn stack(&self) -> Vec<u32> {
+        let mut stack = Vec::new();
+        let mut node = self.current;
+        while let Some(func_index) = self.nodes[node].func_index {
+            stack.push(func_index);
+            node = self.nodes[node].parent;
+        }
+        stack.reverse();
+        stack
+    }
+
+    // Returns the nodes in depth-first order together with their call stack and inclusive count
+    fn walk(&self) -> Vec<(usize, Vec<u32>, u64)> {
+        let mut result = Vec::new();
+        self.walk_node(0, &mut Vec::new(), &mut result);
+        result
+    }
+
+    fn walk_node(&self, node: usize, stack: &mut Vec<u32>, result: &mut Vec<(usize, Vec<u32>, u64)>) -> u64 {
+        let position = result.len();
+        result.push((node, stack.clone(), 0));
+        let mut children: Vec<(&u32, &usize)> = self.nodes[node].children.iter().collect();
+        children.sort_unstable();
+        let mut total = self.nodes[node].self_count;
+        for (func_index, child) in children {
+            stack.push(*func_index);
+            total += self.walk_node(*child, stack, result);
+            stack.pop();
+        }
+        result[position].2 = total;
+        total
+    }
+
+    // Recursive calls only count once towards the inclusive count of a function
+    pub fn function_profiles(&self) -> Vec<FunctionProfile> {
+        let mut profiles: HashMap<u32, FunctionProfile> = HashMap::new();
+        for (node, stack, total) in self.walk() {
+            let func_index = match self.nodes[node].func_index {
+                Some(func_index) => func_index,
+                None => continue,
+            };
+            let profile = profiles.entry(func_index).or_insert_with(|| FunctionProfile {
+                func_index,
+                self_count: 0,
+                inclusive_count: 0,
+                calls: self.calls.get(&func_index).copied().unwrap_or(0),
+            });
+            profile.self_count += self.nodes[node].self_count;
+            if !stack[..stack.len() - 1].contains(&func_index) {
+                profile.inclusive_count += total;
+            }
+        }
+        let mut profiles: Vec<FunctionProfile> = profiles.into_values().collect();
+        profiles.sort_by(|a, b| b.self_count.cmp(&a.self_count).then(a.func_index.cmp(&b.func_index)));
+        profiles
+    }
+
+    // One line per call stack with the function names separated by semicolons, as used by flamegraph tools
+    pub fn write_collapsed(&self, module: &Module, output: &mut dyn Write) -> io::Result<()> {
+        for (node, stack, _) in self.walk() {
+            let self_count = self.nodes[node].self_count;
+            if stack.is_empty() || self_count == 0 {
+                continue;
+            }
+            let names: Vec<&str> = stack.iter().map(|index| module.func(*index).name()).collect();
+            writeln!(output, "{} {}", names.join(";"), self_count)?;
+        }
+        Ok(())
+    }
+
+    // Frames that are still open are closed at the end of the timeline
+    fn closed_events(&self) -> Vec<ProfileEvent> {
+        let mut events = self.events.clone();
+        let mut open = Vec::new();
+        for event in &self.events {
+            match *event {
+                ProfileEvent::Open { func_index, .. } => open.push(func_index),
+                ProfileEvent::Close { .. } => {
+                    open.pop();
+                }
+            }
+        }
+        for func_index in open.into_iter().rev() {
+            events.push(ProfileEvent::Close {
+                func_index,
+                at: self.timeline_end(),
+            });
+        }
+        events
+    }
+
+    pub fn write_chrome_trace(&self, module: &Module, output: &mut dyn Write) -> io::Result<()> {
+        write!(output, "{{\"traceEvents\":[")?;
+        for (i, event) in self.closed_events().iter().enumerate() {
+            let (func_index, at, phase) = match *event {
+                ProfileEvent::Open { func_index, at } => (func_index, at, "B"),
+                ProfileEvent::Close { func_index, at } => (func_index, at, "E"),
+            };
+            if i > 0 {
+                write!(output, ",")?;
+            }
+            write!(
+                output,
+                "{{\"name\":\"{}\",\"ph\":\"{}\",\"ts\":{},\"pid\":1,\"tid\":1}}",
+                json_escape(module.func(func_index).name()),
+                phase,
+                at
+            )?;
+        }
+        writeln!(output, "]}}")
+    }
+
+    // The evented profile format of speedscope (https://www.speedscope.app). Frames are the functions of the module.
+    pub fn write_speedscope(&self, module: &Module, output: &mut dyn Write) -> io::Result<()> {
+        write!(
+            output,
+            "{{\"$schema\":\"https://www.speedscope.app/file-format-schema.json\",\"shared\":{{\"frames\":["
+        )?;
+        for (i, func) in module.functions().iter().enumerate() {
+            if i > 0 {
+                write!(output, ",")?;
+            }
+            write!(output, "{{\"name\":\"{}\"}}", json_escape(func.name()))?;
+        }
+        write!(
+            output,
+            "]}},\"profiles\":[{{\"type\":\"evented\",\"name\":\"wasmdbg\",\"unit\":\"none\",\"startValue\":0,\
+             \"endValue\":{},\"events\":[",
+            self.timeline_end()
+        )?;
+        for (i, event) in self.closed_events().iter().enumerate() {
+            let (func_index, at, kind) = match *event {
+                ProfileEvent::Open { func_index, at } => (func_index, at, "O"),
+                ProfileEvent::Close { func_index, at } => (func_index, at, "C"),
+            };
+            if i > 0 {
+                write!(output, ",")?;
+            }
+            write!(
+                output,
+                "{{\"type\":\"{}\",\"frame\":{},\"at\":{}}}",
+                kind, func_index, at
+            )?;
+        }
+        writeln!(output, "]}}],\"exporter\":\"wasmdbg\"}}")
+    }
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Profiler::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::Profiler;
+    use crate::test_utils::load_wat;
+    use crate::Module;
+
+    type Export = fn(&Profiler, &Module, &mut dyn io::Write) -> io::Result<()>;
+
+    fn export(profiler: &Profiler, module: &Module, write: Export) -> String {
+        let mut output = Vec::new();
+        write(profiler, module, &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn exports() {
+        let mut dbg = load_wat(
+            r#"(module
+                (func $main
+                    call $leaf
+                    call $leaf)
+                (func $leaf
+                    nop))"#,
+        );
+        dbg.start_profiling();
+        dbg.call(0, &[]).unwrap();
+        let profiler = dbg.profiler().unwrap();
+        let module = dbg.get_file().unwrap().module();
+        assert_eq!(profiler.instruction_count(), 7);
+        assert_eq!(
+            export(&profiler, module, Profiler::write_collapsed),
+            "main 3\nmain;leaf 4\n"
+        );
+        assert_eq!(
+            export(&profiler, module, Profiler::write_chrome_trace),
+            concat!(
+                r#"{"traceEvents":[{"name":"main","ph":"B","ts":0,"pid":1,"tid":1},"#,
+                r#"{"name":"leaf","ph":"B","ts":1,"pid":1,"tid":1},{"name":"leaf","ph":"E","ts":3,"pid":1,"tid":1},"#,
+                r#"{"name":"leaf","ph":"B","ts":4,"pid":1,"tid":1},{"name":"leaf","ph":"E","ts":6,"pid":1,"tid":1},"#,
+                r#"{"name":"main","ph":"E","ts":7,"pid":1,"tid":1}]}"#,
+                "\n"
+            )
+        );
+        assert_eq!(
+            export(&profiler, module, Profiler::write_speedscope),
+            concat!(
+                r#"{"$schema":"https://www.speedscope.app/file-format-schema.json","#,
+                r#""shared":{"frames":[{"name":"main"},{"name":"leaf"}]},"#,
+                r#""profiles":[{"type":"evented","name":"wasmdbg","unit":"none","startValue":0,"endValue":7,"#,
+                r#""events":[{"type":"O","frame":0,"at":0},{"type":"O","frame":1,"at":1},"#,
+                r#"{"type":"C","frame":1,"at":3},{"type":"O","frame":1,"at":4},"#,
+                r#"{"type":"C","frame":1,"at":6},{"type":"C","frame":0,"at":7}]}],"exporter":"wasmdbg"}"#,
+                "\n"
+            )
+        );
+    }
+
+    #[test]
+    fn timeline_limit() {
+        let dbg = load_wat("(module (func $main) (func $leaf))");
+        let module = dbg.get_file().unwrap().module();
+        let mut profiler = Profiler::new();
+        profiler.max_events = 3;
+        profiler.enter(0);
+        profiler.step();
+        for _ in 0..2 {
+            profiler.enter(1);
+            profiler.step();
+            profiler.leave();
+        }
+        profiler.step();
+        assert_eq!(profiler.instruction_count(), 4);
+        assert_eq!(profiler.timeline_end(), 2);
+        // The second call of leaf is past the limit, main is still open
+        assert_eq!(
+            export(&profiler, module, Profiler::write_chrome_trace),
+            concat!(
+                r#"{"traceEvents":[{"name":"main","ph":"B","ts":0,"pid":1,"tid":1},"#,
+                r#"{"name":"leaf","ph":"B","ts":1,"pid":1,"tid":1},{"name":"leaf","ph":"E","ts":2,"pid":1,"tid":1},"#,
+                r#"{"name":"main","ph":"E","ts":2,"pid":1,"tid":1}]}"#,
+                "\n"
+            )
+        );
+        assert_eq!(
+            export(&profiler, module, Profiler::write_collapsed),
+            "main 2\nmain;leaf 2\n"
+        );
+    }
+}
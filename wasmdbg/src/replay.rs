@@ -0,0 +1,560 @@
+use std::fs;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use thiserror::Error;
+
+use crate::vm::CodePosition;
+use crate::{Value, F32, F64};
+
+// Trace files start with the magic and the format version, followed by the path and hash of the recorded binary
+// and a sequence of events. All integers are unsigned LEB128 except for the hash and the bits of values.
+const TRACE_MAGIC: &[u8; 8] = b"WDBGTRC\0";
+pub const TRACE_VERSION: u32 = 2;
+
+const EVENT_RUN: u8 = 1;
+const EVENT_HOST_CALL: u8 = 2;
+
+#[derive(Error, Debug)]
+pub enum TraceError {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("Not a wasmdbg trace file")]
+    InvalidMagic,
+    #[error("Unsupported trace version {0} (supported: {})", TRACE_VERSION)]
+    UnsupportedVersion(u32),
+    #[error("Corrupt trace file")]
+    Corrupt,
+}
+
+pub type TraceResult<T> = Result<T, TraceError>;
+
+// A change made by a host function. Memory writes store the data after the call and resizes the new page count.
+#[derive(Clone, PartialEq, Debug)]
+pub enum HostEffect {
+    MemoryWrite { memory: u32, address: u32, bytes: Vec<u8> },
+    MemoryResize { memory: u32, pages: u32 },
+    SetGlobal { index: u32, value: Value },
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum HostOutcome {
+    Results(Vec<Value>),
+    Exit(u32),
+    Error(String),
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct HostCall {
+    pub func_index: u32,
+    pub effects: Vec<HostEffect>,
+    pub outcome: HostOutcome,
+}
+
+// Consecutive instructions of one function executed as consecutive steps
+struct Run {
+    first_step: u64,
+    start: CodePosition,
+    len: u32,
+}
+
+pub struct Trace {
+    module_path: String,
+    module_hash: u64,
+    runs: Vec<Run>,
+    host_calls: Vec<HostCall>,
+    step_count: u64,
+}
+
+impl Trace {
+    pub fn load<P: AsRef<Path>>(path: P) -> TraceResult<Trace> {
+        let mut reader = BufReader::new(fs::File::open(path)?);
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != TRACE_MAGIC {
+            return Err(TraceError::InvalidMagic);
+        }
+        let version = read_u32(&mut reader)?;
+        if version != TRACE_VERSION {
+            return Err(TraceError::UnsupportedVersion(version));
+        }
+        let module_path = String::from_utf8(read_bytes(&mut reader)?).map_err(|_| TraceError::Corrupt)?;
+        let mut hash = [0; 8];
+        reader.read_exact(&mut hash)?;
+
+        let mut trace = Trace {
+            module_path,
+            module_hash: u64::from_le_bytes(hash),
+            runs: Vec::new(),
+            host_calls: Vec::new(),
+            step_count: 0,
+        };
+        while let Some(event) = read_event_tag(&mut reader)? {
+            match trace.read_event(event, &mut reader) {
+                Ok(()) => (),
+                // A trace that was cut short, e.g. because the debugger was killed, is usable up to its last event
+                Err(TraceError::Io(error)) if error.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(error) => return Err(error),
+            }
+        }
+        Ok(trace)
+    }
+
+    fn read_event<R: Read>(&mut self, event: u8, reader: &mut R) -> TraceResult<()> {
+        match event {
+            EVENT_RUN => {
+                let start = CodePosition::new(read_u32(reader)?, read_u32(reader)?);
+                let len = read_u32(reader)?;
+                self.runs.push(Run {
+                    first_step: self.step_count,
+                    start,
+                    len,
+                });
+                self.step_count += u64::from(len);
+            }
+            EVENT_HOST_CALL => self.host_calls.push(read_host_call(reader)?),
+            _ => return Err(TraceError::Corrupt),
+        }
+        Ok(())
+    }
+
+    pub fn module_path(&self) -> &str {
+        &self.module_path
+    }
+
+    pub const fn module_hash(&self) -> u64 {
+        self.module_hash
+    }
+
+    pub const fn step_count(&self) -> u64 {
+        self.step_count
+    }
+
+    pub fn host_calls(&self) -> &[HostCall] {
+        &self.host_calls
+    }
+
+    pub fn host_call(&self, index: u64) -> Option<&HostCall> {
+        self.host_calls.get(index as usize)
+    }
+
+    // The position of the instruction executed in the given step
+    pub fn position(&self, step: u64) -> Option<CodePosition> {
+        let run_index = self.runs.partition_point(|run| run.first_step <= step).checked_sub(1)?;
+        let run = &self.runs[run_index];
+        let offset = step - run.first_step;
+        if offset < u64::from(run.len) {
+            Some(CodePosition::new(
+                run.start.func_index,
+                run.start.instr_index + offset as u32,
+            ))
+        } else {
+            None
+        }
+    }
+}
+
+pub struct TraceWriter {
+    out: BufWriter<fs::File>,
+    run: Option<(CodePosition, u32)>,
+}
+
+impl TraceWriter {
+    pub fn create<P: AsRef<Path>>(path: P, module_path: &str, module_hash: u64) -> TraceResult<TraceWriter> {
+        let mut out = BufWriter::new(fs::File::create(path)?);
+        out.write_all(TRACE_MAGIC)?;
+        write_u64(&mut out, u64::from(TRACE_VERSION))?;
+        write_bytes(&mut out, module_path.as_bytes())?;
+        out.write_all(&module_hash.to_le_bytes())?;
+        Ok(TraceWriter { out, run: None })
+    }
+
+    pub fn write_step(&mut self, ip: CodePosition) -> io::Result<()> {
+        if let Some((start, len)) = &mut self.run {
+            if start.func_index == ip.func_index && start.instr_index + *len == ip.instr_index {
+                *len += 1;
+                return Ok(());
+            }
+        }
+        self.finish_run()?;
+        self.run = Some((ip, 1));
+        Ok(())
+    }
+
+    pub fn write_host_call(&mut self, call: &HostCall) -> io::Result<()> {
+        let out = &mut self.out;
+        out.write_all(&[EVENT_HOST_CALL])?;
+        write_u64(out, u64::from(call.func_index))?;
+        write_u64(out, call.effects.len() as u64)?;
+        for effect in &call.effects {
+            match effect {
+                HostEffect::MemoryWrite { memory, address, bytes } => {
+                    out.write_all(&[0])?;
+                    write_u64(out, u64::from(*memory))?;
+                    write_u64(out, u64::from(*address))?;
+                    write_bytes(out, bytes)?;
+                }
+                HostEffect::MemoryResize { memory, pages } => {
+                    out.write_all(&[1])?;
+                    write_u64(out, u64::from(*memory))?;
+                    write_u64(out, u64::from(*pages))?;
+                }
+                HostEffect::SetGlobal { index, value } => {
+                    out.write_all(&[2])?;
+                    write_u64(out, u64::from(*index))?;
+                    write_value(out, *value)?;
+                }
+            }
+        }
+        match &call.outcome {
+            HostOutcome::Results(results) => {
+                out.write_all(&[0])?;
+                write_u64(out, results.len() as u64)?;
+                for result in results {
+                    write_value(out, *result)?;
+                }
+            }
+            HostOutcome::Exit(code) => {
+                out.write_all(&[1])?;
+                write_u64(out, u64::from(*code))?;
+            }
+            HostOutcome::Error(message) => {
+                out.write_all(&[2])?;
+                write_bytes(out, message.as_bytes())?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.finish_run()?;
+        self.out.flush()
+    }
+
+    fn finish_run(&mut self) -> io::Result<()> {
+        if let Some((start, len)) = self.run.take() {
+            self.out.write_all(&[EVENT_RUN])?;
+            write_u64(&mut self.out, u64::from(start.func_index))?;
+            write_u64(&mut self.out, u64::from(start.instr_index))?;
+            write_u64(&mut self.out, u64::from(len))?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for TraceWriter {
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+// FNV-1a, used to check that a trace is replayed with the binary it was recorded with
+pub fn module_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn write_u64<W: Write>(out: &mut W, mut val: u64) -> io::Result<()> {
+    loop {
+        let byte = (val & 0x7f) as u8;
+        val >>= 7;
+        if val == 0 {
+            return out.write_all(&[byte]);
+        }
+        out.write_all(&[byte | 0x80])?;
+    }
+}
+
+fn write_bytes<W: Write>(out: &mut W, bytes: &[u8]) -> io::Result<()> {
+    write_u64(out, bytes.len() as u64)?;
+    out.write_all(bytes)
+}
+
+fn write_value<W: Write>(out: &mut W, value: Value) -> io::Result<()> {
+    match value {
+        Value::I32(val) => {
+            out.write_all(&[0])?;
+            out.write_all(&val.to_le_bytes())
+        }
+        Value::I64(val) => {
+            out.write_all(&[1])?;
+            out.write_all(&val.to_le_bytes())
+        }
+        Value::F32(val) => {
+            out.write_all(&[2])?;
+            out.write_all(&val.to_bits().to_le_bytes())
+        }
+        Value::F64(val) => {
+            out.write_all(&[3])?;
+            out.write_all(&val.to_bits().to_le_bytes())
+        }
+    }
+}
+
+fn read_event_tag<R: Read>(reader: &mut R) -> TraceResult<Option<u8>> {
+    let mut tag = [0];
+    match reader.read(&mut tag)? {
+        0 => Ok(None),
+        _ => Ok(Some(tag[0])),
+    }
+}
+
+fn read_byte<R: Read>(reader: &mut R) -> TraceResult<u8> {
+    let mut byte = [0];
+    reader.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> TraceResult<u64> {
+    let mut result = 0;
+    let mut shift = 0;
+    loop {
+        let byte = read_byte(reader)?;
+        if shift >= 64 {
+            return Err(TraceError::Corrupt);
+        }
+        result |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(result);
+        }
+        shift += 7;
+    }
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> TraceResult<u32> {
+    let val = read_u64(reader)?;
+    if val > u64::from(u32::MAX) {
+        return Err(TraceError::Corrupt);
+    }
+    Ok(val as u32)
+}
+
+fn read_bytes<R: Read>(reader: &mut R) -> TraceResult<Vec<u8>> {
+    let len = read_u64(reader)?;
+    let mut bytes = Vec::new();
+    reader.take(len).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != len {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+    Ok(bytes)
+}
+
+fn read_value<R: Read>(reader: &mut R) -> TraceResult<Value> {
+    let value_type = read_byte(reader)?;
+    let mut bytes = [0; 8];
+    Ok(match value_type {
+        0 | 2 => {
+            reader.read_exact(&mut bytes[..4])?;
+            let bits = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+            if value_type == 0 {
+                Value::I32(bits as i32)
+            } else {
+                Value::F32(F32::from_bits(bits))
+            }
+        }
+        1 | 3 => {
+            reader.read_exact(&mut bytes)?;
+            let bits = u64::from_le_bytes(bytes);
+            if value_type == 1 {
+                Value::I64(bits as i64)
+            } else {
+                Value::F64(F64::from_bits(bits))
+            }
+        }
+        _ => return Err(TraceError::Corrupt),
+    })
+}
+
+fn read_host_call<R: Read>(reader: &mut R) -> TraceResult<HostCall> {
+    let func_index = read_u32(reader)?;
+    let effect_count = read_u64(reader)?;
+    let mut effects = Vec::new();
+    for _ in 0..effect_count {
+        effects.push(match read_byte(reader)? {
+            0 => HostEffect::MemoryWrite {
+                memory: read_u32(reader)?,
+                address: read_u32(reader)?,
+                bytes: read_bytes(reader)?,
+            },
+            1 => HostEffect::MemoryResize {
+                memory: read_u32(reader)?,
+                pages: read_u32(reader)?,
+            },
+            2 => HostEffect::SetGlobal {
+                index: read_u32(reader)?,
+                value: read_value(reader)?,
+            },
+            _ => return Err(TraceError::Corrupt),
+        });
+    }
+    let outcome = match read_byte(reader)? {
+        0 => {
+            let count = read_u64(reader)?;
+            let mut results = Vec::new();
+            for _ in 0..count {
+                results.push(read_value(reader)?);
+            }
+            HostOutcome::Results(results)
+        }
+        1 => HostOutcome::Exit(read_u32(reader)?),
+        2 => HostOutcome::Error(String::from_utf8(read_bytes(reader)?).map_err(|_| TraceError::Corrupt)?),
+        _ => return Err(TraceError::Corrupt),
+    };
+    Ok(HostCall {
+        func_index,
+        effects,
+        outcome,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::fs;
+    use std::rc::Rc;
+
+    use super::{module_hash, HostCall, HostEffect, HostOutcome, Trace, TraceError, TraceWriter, TRACE_MAGIC};
+    use crate::test_utils::temp_path;
+    use crate::vm::{CodePosition, HostContext, Trap};
+    use crate::{Debugger, Value, F32};
+
+    #[test]
+    fn round_trip() {
+        let path = temp_path("round_trip.trace");
+        let calls = vec![
+            HostCall {
+                func_index: 0,
+                effects: vec![
+                    HostEffect::MemoryWrite {
+                        memory: 0,
+                        address: 0xffff_fff0,
+                        bytes: vec![1, 2, 3],
+                    },
+                    HostEffect::MemoryResize {
+                        memory: 1,
+                        pages: 65536,
+                    },
+                    HostEffect::SetGlobal {
+                        index: 200,
+                        value: Value::F32(F32::from_bits(0xbfc0_0000)),
+                    },
+                ],
+                outcome: HostOutcome::Results(vec![Value::I32(-1), Value::I64(i64::MIN), Value::from(0.5f64)]),
+            },
+            HostCall {
+                func_index: 1,
+                effects: Vec::new(),
+                outcome: HostOutcome::Error(String::from("failed")),
+            },
+            HostCall {
+                func_index: 2,
+                effects: Vec::new(),
+                outcome: HostOutcome::Exit(3),
+            },
+        ];
+        let mut writer = TraceWriter::create(&path, "dir/module.wasm", 0x0123_4567_89ab_cdef).unwrap();
+        for instr_index in 0..3 {
+            writer.write_step(CodePosition::new(5, instr_index)).unwrap();
+        }
+        writer.write_host_call(&calls[0]).unwrap();
+        writer.write_step(CodePosition::new(6, 0)).unwrap();
+        writer.write_step(CodePosition::new(5, 10)).unwrap();
+        writer.write_host_call(&calls[1]).unwrap();
+        writer.write_host_call(&calls[2]).unwrap();
+        drop(writer);
+
+        let trace = Trace::load(&path).unwrap();
+        assert_eq!(trace.module_path(), "dir/module.wasm");
+        assert_eq!(trace.module_hash(), 0x0123_4567_89ab_cdef);
+        assert_eq!(trace.step_count(), 5);
+        let positions: Vec<_> = (0..6).map(|step| trace.position(step)).collect();
+        assert_eq!(
+            positions,
+            [
+                Some(CodePosition::new(5, 0)),
+                Some(CodePosition::new(5, 1)),
+                Some(CodePosition::new(5, 2)),
+                Some(CodePosition::new(6, 0)),
+                Some(CodePosition::new(5, 10)),
+                None,
+            ]
+        );
+        assert_eq!(trace.host_calls(), calls.as_slice());
+
+        // A trace that was cut off is readable up to its last complete event, here the run pending at the end
+        let bytes = fs::read(&path).unwrap();
+        fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+        let trace = Trace::load(&path).unwrap();
+        assert_eq!(trace.step_count(), 4);
+        assert_eq!(trace.host_calls(), calls.as_slice());
+
+        let mut bytes = bytes;
+        bytes[TRACE_MAGIC.len()] = 99;
+        fs::write(&path, &bytes).unwrap();
+        assert!(matches!(Trace::load(&path), Err(TraceError::UnsupportedVersion(99))));
+        fs::write(&path, b"not a trace").unwrap();
+        assert!(matches!(Trace::load(&path), Err(TraceError::InvalidMagic)));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn record_and_replay() {
+        // The host function writes to memory, grows it, sets a global and returns a different value each call
+        let module = temp_path("replay.wasm");
+        let bytes = wat::parse_str(
+            r#"(module
+                (import "env" "tick" (func $tick (result i32)))
+                (memory 1)
+                (global $g (mut i32) (i32.const 0))
+                (func (result i32)
+                    (i32.add (call $tick) (call $tick))
+                    (i32.add (i32.load (i32.const 0)))
+                    (i32.add (memory.size))
+                    (i32.add (global.get $g))))"#,
+        )
+        .unwrap();
+        fs::write(&module, &bytes).unwrap();
+        let path = temp_path("replay.trace");
+        let ticks = Rc::new(Cell::new(0));
+
+        let mut dbg = Debugger::new();
+        let counter = Rc::clone(&ticks);
+        dbg.add_host_function("env", "tick", move |ctx: &mut HostContext<'_>, _: &[Value]| {
+            counter.set(counter.get() + 1);
+            let tick = counter.get() * 10;
+            let memory = ctx.memory_mut()?;
+            memory.store(0, tick)?;
+            memory.grow(1);
+            ctx.globals[0] = Value::I32(tick);
+            Ok(vec![Value::I32(tick)])
+        });
+        dbg.load_file(module.to_str().unwrap()).unwrap();
+        dbg.set_trace_output(Some(path.clone()));
+        assert!(matches!(dbg.call(1, &[]).unwrap(), Trap::ExecutionFinished));
+        dbg.set_trace_output(None);
+        // 10 + 20 from the calls, 20 from memory, 3 pages and 20 from the global
+        assert_eq!(dbg.vm().unwrap().value_stack(), &[Value::I32(73)]);
+        assert_eq!(ticks.get(), 2);
+
+        let trace = Trace::load(&path).unwrap();
+        assert_eq!(trace.module_hash(), module_hash(&bytes));
+        assert_eq!(trace.host_calls().len(), 2);
+        assert!(trace.host_calls()[1]
+            .effects
+            .contains(&HostEffect::MemoryResize { memory: 0, pages: 3 }));
+
+        // Replaying doesn't need the host function
+        let mut dbg = Debugger::new();
+        dbg.load_file(module.to_str().unwrap()).unwrap();
+        dbg.set_replay(Some(trace)).unwrap();
+        assert!(matches!(dbg.call(1, &[]).unwrap(), Trap::ExecutionFinished));
+        assert_eq!(dbg.vm().unwrap().value_stack(), &[Value::I32(73)]);
+        assert_eq!(dbg.memory().unwrap().page_count(), 3);
+        assert_eq!(dbg.globals().unwrap(), &[Value::I32(20)]);
+        assert_eq!(ticks.get(), 2);
+
+        fs::remove_file(path).unwrap();
+        fs::remove_file(module).unwrap();
+    }
+}
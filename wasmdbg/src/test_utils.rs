@@ -0,0 +1,103 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use gimli::write::{Address, DwarfUnit, EndianVec, LineProgram, LineString, Sections};
+use gimli::{Encoding, Format, LineEncoding, LittleEndian};
+
+use crate::dwarf::CodeOffsets;
+use crate::vm::CodePosition;
+use crate::{Debugger, Module};
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+// A path in the temp directory that is unique for each call
+pub fn temp_path(name: &str) -> PathBuf {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    env::temp_dir().join(format!("wasmdbg-test-{}-{}-{}", process::id(), id, name))
+}
+
+pub fn load_wat(wat: &str) -> Debugger {
+    load_bytes(&wat::parse_str(wat).unwrap())
+}
+
+// Loads the module with a line table in "main.c" that has a row (position, line, is_stmt) for each entry. The
+// rows have to be sorted by position.
+pub fn load_wat_with_lines(wat: &str, rows: &[(CodePosition, u64, bool)]) -> Debugger {
+    let mut bytes = wat::parse_str(wat).unwrap();
+    let code = CodeOffsets::load(&Module::from_bytes(&bytes).unwrap(), &bytes).unwrap();
+    let encoding = Encoding {
+        format: Format::Dwarf32,
+        version: 4,
+        address_size: 4,
+    };
+    let mut program = LineProgram::new(
+        encoding,
+        LineEncoding::default(),
+        LineString::String(b"/src".to_vec()),
+        None,
+        LineString::String(b"main.c".to_vec()),
+        None,
+    );
+    let file = program.add_file(
+        LineString::String(b"main.c".to_vec()),
+        program.default_directory(),
+        None,
+    );
+    program.begin_sequence(Some(Address::Constant(0)));
+    let mut end = 0;
+    for (pos, line, is_stmt) in rows {
+        let address = code.address(*pos).unwrap();
+        let row = program.row();
+        row.address_offset = address;
+        row.file = file;
+        row.line = *line;
+        row.is_statement = *is_stmt;
+        program.generate_row();
+        end = address + 1;
+    }
+    program.end_sequence(end);
+    let mut dwarf = DwarfUnit::new(encoding);
+    dwarf.unit.line_program = program;
+    let mut sections = Sections::new(EndianVec::new(LittleEndian));
+    dwarf.write(&mut sections).unwrap();
+    sections
+        .for_each(|id, section| -> Result<(), ()> {
+            if !section.slice().is_empty() {
+                let mut payload = Vec::new();
+                write_leb(&mut payload, id.name().len());
+                payload.extend_from_slice(id.name().as_bytes());
+                payload.extend_from_slice(section.slice());
+                bytes.push(0);
+                write_leb(&mut bytes, payload.len());
+                bytes.extend_from_slice(&payload);
+            }
+            Ok(())
+        })
+        .unwrap();
+    load_bytes(&bytes)
+}
+
+fn write_leb(out: &mut Vec<u8>, mut value: usize) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn load_bytes(bytes: &[u8]) -> Debugger {
+    let path = temp_path("module.wasm");
+    fs::write(&path, bytes).unwrap();
+    let mut dbg = Debugger::new();
+    let result = dbg.load_file(path.to_str().unwrap());
+    fs::remove_file(&path).unwrap();
+    result.unwrap();
+    dbg
+}
@@ -0,0 +1,220 @@
+use std::collections::HashSet;
+use std::io::{self, Write};
+
+use crate::vm::{CodePosition, VM};
+use crate::{demangle, BlockType, DemangleStyle, Instruction, Module, Value};
+
+// Writes a line for every executed instruction containing its position, the function name, the instruction and
+// the top of the value stack after executing it
+pub struct InstructionTracer {
+    output: Box<dyn Write>,
+    functions: Option<HashSet<u32>>,
+    demangle_style: DemangleStyle,
+}
+
+impl InstructionTracer {
+    pub fn new(output: Box<dyn Write>) -> Self {
+        InstructionTracer {
+            output,
+            functions: None,
+            demangle_style: DemangleStyle::default(),
+        }
+    }
+
+    pub fn set_demangle_style(&mut self, style: DemangleStyle) {
+        self.demangle_style = style;
+    }
+
+    pub const fn functions(&self) -> Option<&HashSet<u32>> {
+        self.functions.as_ref()
+    }
+
+    // Only instructions of these functions are traced. None traces all functions.
+    pub fn set_functions(&mut self, functions: Option<HashSet<u32>>) {
+        self.functions = functions;
+    }
+
+    pub(crate) fn trace(&mut self, vm: &VM, ip: CodePosition) -> io::Result<()> {
+        if let Some(functions) = &self.functions {
+            if !functions.contains(&ip.func_index) {
+                return Ok(());
+            }
+        }
+        let module = vm.module();
+        let func = module.get_func(ip.func_index).unwrap();
+        let instr = if func.is_imported() {
+            "<host function>".to_owned()
+        } else {
+            format_instr(
+                module,
+                &func.instructions()[ip.instr_index as usize],
+                self.demangle_style,
+            )
+        };
+        let name = demangle(func.name(), self.demangle_style);
+        match vm.value_stack().last() {
+            Some(top) => writeln!(self.output, "{}\t{}\t{}\t{}", ip, name, instr, top),
+            None => writeln!(self.output, "{}\t{}\t{}\t-", ip, name, instr),
+        }
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+}
+
+impl Drop for InstructionTracer {
+    fn drop(&mut self) {
+        self.flush().ok();
+    }
+}
+
+// Writes an indented line for every call with its arguments and result. The line of a call is only completed
+// when it returns without making traced calls itself. Otherwise, the result is written on a separate line.
+pub struct CallTracer {
+    output: Box<dyn Write>,
+    imports_only: bool,
+    include: Option<HashSet<u32>>,
+    exclude: HashSet<u32>,
+    max_depth: Option<usize>,
+    pending: Option<(usize, String)>,
+    demangle_style: DemangleStyle,
+}
+
+impl CallTracer {
+    pub fn new(output: Box<dyn Write>, imports_only: bool) -> Self {
+        CallTracer {
+            output,
+            imports_only,
+            include: None,
+            exclude: HashSet::new(),
+            max_depth: None,
+            pending: None,
+            demangle_style: DemangleStyle::default(),
+        }
+    }
+
+    pub fn set_demangle_style(&mut self, style: DemangleStyle) {
+        self.demangle_style = style;
+    }
+
+    pub const fn imports_only(&self) -> bool {
+        self.imports_only
+    }
+
+    // Only calls to these functions are traced. None traces all functions.
+    pub fn set_include(&mut self, functions: Option<HashSet<u32>>) {
+        self.include = functions;
+    }
+
+    pub fn set_exclude(&mut self, functions: HashSet<u32>) {
+        self.exclude = functions;
+    }
+
+    // Calls nested deeper than this are not traced. The outermost call has depth 0.
+    pub fn set_max_depth(&mut self, depth: Option<usize>) {
+        self.max_depth = depth;
+    }
+
+    fn is_traced(&self, module: &Module, func_index: u32, depth: usize) -> bool {
+        if self.max_depth.is_some_and(|max_depth| depth > max_depth) {
+            return false;
+        }
+        if self.imports_only && !module.func(func_index).is_imported() {
+            return false;
+        }
+        if let Some(include) = &self.include {
+            if !include.contains(&func_index) {
+                return false;
+            }
+        }
+        !self.exclude.contains(&func_index)
+    }
+
+    pub(crate) fn enter(&mut self, module: &Module, func_index: u32, depth: usize, args: &[Value]) -> io::Result<()> {
+        if !self.is_traced(module, func_index, depth) {
+            return Ok(());
+        }
+        self.write_pending()?;
+        let args: Vec<String> = args.iter().map(format_value).collect();
+        let name = demangle(module.func(func_index).name(), self.demangle_style);
+        let line = format!("{}({})", name, args.join(", "));
+        self.pending = Some((depth, line));
+        Ok(())
+    }
+
+    pub(crate) fn leave(
+        &mut self,
+        module: &Module,
+        func_index: u32,
+        depth: usize,
+        results: &[Value],
+    ) -> io::Result<()> {
+        if !self.is_traced(module, func_index, depth) {
+            return Ok(());
+        }
+        let line = match self.pending.take() {
+            Some((pending_depth, line)) if pending_depth == depth => line,
+            pending => {
+                self.pending = pending;
+                self.write_pending()?;
+                format!(
+                    "<... {} resumed>",
+                    demangle(module.func(func_index).name(), self.demangle_style)
+                )
+            }
+        };
+        let indent = "  ".repeat(depth);
+        match results {
+            [] => writeln!(self.output, "{}{}", indent, line),
+            [result] => writeln!(self.output, "{}{} -> {}", indent, line, format_value(result)),
+            results => {
+                let results: Vec<String> = results.iter().map(format_value).collect();
+                writeln!(self.output, "{}{} -> ({})", indent, line, results.join(", "))
+            }
+        }
+    }
+
+    fn write_pending(&mut self) -> io::Result<()> {
+        match self.pending.take() {
+            Some((depth, line)) => writeln!(self.output, "{}{}", "  ".repeat(depth), line),
+            None => Ok(()),
+        }
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.write_pending()?;
+        self.output.flush()
+    }
+}
+
+impl Drop for CallTracer {
+    fn drop(&mut self) {
+        self.flush().ok();
+    }
+}
+
+fn format_value(value: &Value) -> String {
+    match value {
+        Value::I32(val) => val.to_string(),
+        Value::I64(val) => val.to_string(),
+        Value::F32(val) => val.to_float().to_string(),
+        Value::F64(val) => val.to_float().to_string(),
+    }
+}
+
+pub fn format_instr(module: &Module, instr: &Instruction, demangle_style: DemangleStyle) -> String {
+    match instr {
+        Instruction::Call(index) => {
+            let name = demangle(module.get_func(*index).unwrap().name(), demangle_style);
+            format!("{} <{}>", instr, name)
+        }
+        Instruction::Block(BlockType::TypeIndex(index))
+        | Instruction::Loop(BlockType::TypeIndex(index))
+        | Instruction::If(BlockType::TypeIndex(index)) => {
+            let signature = module.types()[*index as usize].to_string();
+            format!("{} <{}>", instr, &signature[3..])
+        }
+        _ => instr.to_string(),
+    }
+}
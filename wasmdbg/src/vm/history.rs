@@ -0,0 +1,78 @@
+use std::collections::VecDeque;
+
+use super::{CodePosition, FunctionFrame, Label, MemoryChange, PendingEntry};
+use crate::Value;
+
+pub const HISTORY_LIMIT: usize = 1_000_000;
+
+// Undo information for a single state mutation. `Step` marks the start of an executed step and stores the
+// instruction pointer before it.
+pub(crate) enum Change {
+    Step(CodePosition),
+    Push,
+    Pop(Value),
+    PushLabel,
+    PopLabel(Label),
+    PushFrame,
+    PopFrame(FunctionFrame),
+    SetLocal(u32, Value),
+    SetGlobal(u32, Value),
+    Memory(u32, MemoryChange),
+    TakeEntry(PendingEntry),
+    HostCall,
+}
+
+pub(crate) struct History {
+    changes: VecDeque<Change>,
+    step_count: usize,
+}
+
+impl History {
+    pub fn new() -> Self {
+        History {
+            changes: VecDeque::new(),
+            step_count: 0,
+        }
+    }
+
+    pub const fn step_count(&self) -> usize {
+        self.step_count
+    }
+
+    pub fn clear(&mut self) {
+        self.changes.clear();
+        self.step_count = 0;
+    }
+
+    pub fn begin_step(&mut self, ip: CodePosition) {
+        if self.step_count >= HISTORY_LIMIT {
+            self.changes.pop_front();
+            while let Some(change) = self.changes.front() {
+                if let Change::Step(_) = change {
+                    break;
+                }
+                self.changes.pop_front();
+            }
+            self.step_count -= 1;
+        }
+        self.changes.push_back(Change::Step(ip));
+        self.step_count += 1;
+    }
+
+    pub fn record(&mut self, change: Change) {
+        self.changes.push_back(change);
+    }
+
+    // Returns the changes of the last step (most recent first) and the instruction pointer before it
+    pub fn pop_step(&mut self) -> Option<(CodePosition, Vec<Change>)> {
+        let mut changes = Vec::new();
+        while let Some(change) = self.changes.pop_back() {
+            if let Change::Step(ip) = change {
+                self.step_count -= 1;
+                return Some((ip, changes));
+            }
+            changes.push(change);
+        }
+        None
+    }
+}
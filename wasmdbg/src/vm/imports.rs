@@ -0,0 +1,223 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use super::{InitError, Memory, Table, Trap, VMResult};
+use crate::{External, ImportEntry, Module, ResizableLimits, Value};
+
+pub struct HostContext<'a> {
+    pub memories: &'a mut [Memory],
+    pub globals: &'a mut [Value],
+}
+
+impl<'a> HostContext<'a> {
+    pub fn memory(&self) -> VMResult<&Memory> {
+        self.memories.first().ok_or(Trap::NoMemory)
+    }
+
+    pub fn memory_mut(&mut self) -> VMResult<&mut Memory> {
+        self.memories.first_mut().ok_or(Trap::NoMemory)
+    }
+}
+
+pub trait HostFunction {
+    fn call(&self, ctx: &mut HostContext<'_>, args: &[Value]) -> VMResult<Vec<Value>>;
+}
+
+impl<F> HostFunction for F
+where
+    F: Fn(&mut HostContext<'_>, &[Value]) -> VMResult<Vec<Value>>,
+{
+    fn call(&self, ctx: &mut HostContext<'_>, args: &[Value]) -> VMResult<Vec<Value>> {
+        self(ctx, args)
+    }
+}
+
+#[derive(Default, Clone)]
+pub struct Imports {
+    functions: HashMap<(String, String), Rc<dyn HostFunction>>,
+    memories: HashMap<(String, String), Memory>,
+    tables: HashMap<(String, String), Table>,
+    globals: HashMap<(String, String), Value>,
+}
+
+impl Imports {
+    pub fn new() -> Self {
+        Imports::default()
+    }
+
+    pub fn add_function<F: HostFunction + 'static>(&mut self, module: &str, field: &str, function: F) {
+        self.functions
+            .insert((module.to_owned(), field.to_owned()), Rc::new(function));
+    }
+
+    pub fn remove_function(&mut self, module: &str, field: &str) -> bool {
+        self.functions.remove(&(module.to_owned(), field.to_owned())).is_some()
+    }
+
+    pub fn get_function(&self, module: &str, field: &str) -> Option<&Rc<dyn HostFunction>> {
+        self.functions.get(&(module.to_owned(), field.to_owned()))
+    }
+
+    pub fn has_function(&self, module: &str, field: &str) -> bool {
+        self.get_function(module, field).is_some()
+    }
+
+    pub(crate) fn resolve_functions(&self, module: &Module) -> Vec<Option<Rc<dyn HostFunction>>> {
+        module
+            .imports()
+            .iter()
+            .filter(|entry| matches!(entry.external(), External::Function(_)))
+            .map(|entry| self.get_function(entry.module(), entry.field()).cloned())
+            .collect()
+    }
+
+    pub fn add_memory(&mut self, module: &str, field: &str, memory: Memory) {
+        self.memories.insert((module.to_owned(), field.to_owned()), memory);
+    }
+
+    pub fn get_memory(&self, module: &str, field: &str) -> Option<&Memory> {
+        self.memories.get(&(module.to_owned(), field.to_owned()))
+    }
+
+    pub fn has_memory(&self, module: &str, field: &str) -> bool {
+        self.get_memory(module, field).is_some()
+    }
+
+    pub fn add_table(&mut self, module: &str, field: &str, table: Table) {
+        self.tables.insert((module.to_owned(), field.to_owned()), table);
+    }
+
+    pub fn get_table(&self, module: &str, field: &str) -> Option<&Table> {
+        self.tables.get(&(module.to_owned(), field.to_owned()))
+    }
+
+    pub fn has_table(&self, module: &str, field: &str) -> bool {
+        self.get_table(module, field).is_some()
+    }
+
+    pub fn add_global(&mut self, module: &str, field: &str, value: Value) {
+        self.globals.insert((module.to_owned(), field.to_owned()), value);
+    }
+
+    pub fn get_global(&self, module: &str, field: &str) -> Option<Value> {
+        self.globals.get(&(module.to_owned(), field.to_owned())).copied()
+    }
+
+    pub fn has_global(&self, module: &str, field: &str) -> bool {
+        self.get_global(module, field).is_some()
+    }
+
+    // Defines every undefined memory and table import with a fresh one of the declared initial size
+    pub fn add_default_definitions(&mut self, module: &Module) {
+        for entry in module.imports() {
+            let key = (entry.module().to_owned(), entry.field().to_owned());
+            match entry.external() {
+                External::Memory(memory_type) => {
+                    let limits = memory_type.limits();
+                    self.memories
+                        .entry(key)
+                        .or_insert_with(|| Memory::with_limits(limits.initial(), limits.maximum()));
+                }
+                External::Table(table_type) => {
+                    let limits = table_type.limits();
+                    self.tables
+                        .entry(key)
+                        .or_insert_with(|| Table::with_limits(limits.initial(), limits.maximum()));
+                }
+                _ => (),
+            }
+        }
+    }
+
+    pub(crate) fn resolve_memories(&self, module: &Module) -> Result<Vec<Memory>, InitError> {
+        let mut memories = Vec::new();
+        for entry in module.imports() {
+            if let External::Memory(memory_type) = entry.external() {
+                let memory = self
+                    .get_memory(entry.module(), entry.field())
+                    .ok_or_else(|| missing_import("memory", entry))?;
+                check_limits(
+                    "memory",
+                    entry,
+                    memory_type.limits(),
+                    memory.page_count(),
+                    memory.limits(),
+                )?;
+                memories.push(memory.clone());
+            }
+        }
+        Ok(memories)
+    }
+
+    pub(crate) fn resolve_tables(&self, module: &Module) -> Result<Vec<Table>, InitError> {
+        let mut tables = Vec::new();
+        for entry in module.imports() {
+            if let External::Table(table_type) = entry.external() {
+                let table = self
+                    .get_table(entry.module(), entry.field())
+                    .ok_or_else(|| missing_import("table", entry))?;
+                check_limits("table", entry, table_type.limits(), table.size(), table.limits())?;
+                tables.push(table.clone());
+            }
+        }
+        Ok(tables)
+    }
+
+    pub(crate) fn resolve_globals(&self, module: &Module) -> Result<Vec<Value>, InitError> {
+        let mut globals = Vec::new();
+        for entry in module.imports() {
+            if let External::Global(global_type) = entry.external() {
+                let value = self
+                    .get_global(entry.module(), entry.field())
+                    .ok_or_else(|| missing_import("global", entry))?;
+                if value.value_type() != global_type.content_type() {
+                    return Err(InitError::ImportTypeMismatch {
+                        name: format!("{}.{}", entry.module(), entry.field()),
+                        expected: global_type.content_type(),
+                        found: value.value_type(),
+                    });
+                }
+                globals.push(value);
+            }
+        }
+        Ok(globals)
+    }
+}
+
+fn missing_import(kind: &'static str, entry: &ImportEntry) -> InitError {
+    InitError::MissingImport {
+        kind,
+        module: entry.module().to_owned(),
+        field: entry.field().to_owned(),
+    }
+}
+
+fn check_limits(
+    kind: &'static str,
+    entry: &ImportEntry,
+    declared: &ResizableLimits,
+    size: u32,
+    limits: &ResizableLimits,
+) -> Result<(), InitError> {
+    let max_matches = match (declared.maximum(), limits.maximum()) {
+        (None, _) => true,
+        (Some(declared_max), Some(max)) => max <= declared_max,
+        (Some(_), None) => false,
+    };
+    if size >= declared.initial() && max_matches {
+        return Ok(());
+    }
+    Err(InitError::IncompatibleLimits {
+        kind,
+        name: format!("{}.{}", entry.module(), entry.field()),
+        expected: format_limits(declared.initial(), declared.maximum()),
+        found: format_limits(size, limits.maximum()),
+    })
+}
+
+fn format_limits(initial: u32, maximum: Option<u32>) -> String {
+    match maximum {
+        Some(maximum) => format!("{}..{}", initial, maximum),
+        None => format!("{}..", initial),
+    }
+}
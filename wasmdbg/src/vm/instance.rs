@@ -1,29 +1,80 @@
 use std::cell::RefCell;
 use std::rc::Rc;
 
-use bwasm::{Function, Instruction, Module};
-
 use crate::value::{ExtendTo, Integer, LittleEndianConvert, Number, WrapTo};
-use crate::{Breakpoints, Value, F32, F64};
+use crate::{
+    BlockType, Breakpoints, CallTracer, Coverage, Function, HostCall, HostEffect, HostOutcome, Instruction,
+    InstructionTracer, Module, Profiler, Trace, TraceWriter, Value, F32, F64,
+};
 
-use super::{eval_init_expr, CodePosition, InitError, Memory, Table, TableElement, Trap, VMResult};
+use super::{
+    eval_init_expr, Change, CodePosition, History, HostContext, HostFunction, Imports, InitError, Memory, MemoryChange,
+    Table, TableElement, Trap, VMResult,
+};
 
 pub const VALUE_STACK_LIMIT: usize = 1024 * 1024;
 pub const LABEL_STACK_LIMIT: usize = 64 * 1024;
 pub const FUNCTION_STACK_LIMIT: usize = 1024;
 
-#[derive(Debug)]
-pub enum Label {
+#[derive(Debug, Clone, Copy)]
+pub enum LabelKind {
     Bound(u32),
     Unbound,
     Return,
 }
 
+// A branch to a label keeps `arity` values from the top of the value stack and drops everything between them and
+// `stack_height`, the height of the stack below the params of the block
+#[derive(Debug, Clone, Copy)]
+pub struct Label {
+    pub kind: LabelKind,
+    pub arity: usize,
+    pub stack_height: usize,
+}
+
+#[derive(Clone)]
 pub struct FunctionFrame {
     pub ret_addr: CodePosition,
     pub locals: Vec<Value>,
 }
 
+// An entry function that is entered once the start function finishes
+#[derive(Clone, Copy)]
+pub(crate) struct PendingEntry {
+    func_index: u32,
+    pause: bool,
+}
+
+// A copy of the complete state of a VM. It can only be restored into an instance of the same module.
+#[derive(Clone)]
+pub struct Snapshot {
+    memories: Vec<Memory>,
+    tables: Vec<Table>,
+    ip: CodePosition,
+    globals: Vec<Value>,
+    value_stack: Vec<Value>,
+    label_stack: Vec<Label>,
+    function_stack: Vec<FunctionFrame>,
+    trap: Option<Trap>,
+    pending_entry: Option<PendingEntry>,
+    step_count: u64,
+    host_call_count: u64,
+}
+
+impl Snapshot {
+    pub const fn ip(&self) -> CodePosition {
+        self.ip
+    }
+
+    pub fn function_stack(&self) -> &[FunctionFrame] {
+        &self.function_stack
+    }
+
+    pub fn trap(&self) -> Option<&Trap> {
+        self.trap.as_ref()
+    }
+}
+
 pub struct VM {
     module: Rc<Module>,
     memories: Vec<Memory>,
@@ -35,13 +86,24 @@ pub struct VM {
     function_stack: Vec<FunctionFrame>,
     trap: Option<Trap>,
     breakpoints: Rc<RefCell<Breakpoints>>,
+    host_functions: Vec<Option<Rc<dyn HostFunction>>>,
+    pending_entry: Option<PendingEntry>,
+    history: Option<History>,
+    step_count: u64,
+    host_call_count: u64,
+    trace_writer: Option<TraceWriter>,
+    replay: Option<Rc<Trace>>,
+    instruction_tracer: Option<Rc<RefCell<InstructionTracer>>>,
+    call_tracer: Option<Rc<RefCell<CallTracer>>>,
+    profiler: Option<Rc<RefCell<Profiler>>>,
+    coverage: Option<Rc<RefCell<Coverage>>>,
 }
 
 impl VM {
-    pub fn new(module: Rc<Module>, breakpoints: Rc<RefCell<Breakpoints>>) -> Result<VM, InitError> {
-        let mut globals = Vec::with_capacity(module.globals().len());
-        for global in module.globals() {
-            let val = eval_init_expr(global.init_expr())?;
+    pub fn new(module: Rc<Module>, breakpoints: Rc<RefCell<Breakpoints>>, imports: &Imports) -> Result<VM, InitError> {
+        let mut globals = imports.resolve_globals(&module)?;
+        for global in module.globals().iter().filter(|global| !global.is_imported()) {
+            let val = eval_init_expr(global.init_expr(), &globals)?;
             if val.value_type() != global.value_type() {
                 return Err(InitError::MismatchedType {
                     expected: global.value_type(),
@@ -50,8 +112,9 @@ impl VM {
             }
             globals.push(val);
         }
-        let memories = Memory::from_module(&module)?;
-        let tables = Table::from_module(&module)?;
+        let memories = Memory::from_module(&module, imports.resolve_memories(&module)?, &globals)?;
+        let tables = Table::from_module(&module, imports.resolve_tables(&module)?, &globals)?;
+        let host_functions = imports.resolve_functions(&module);
 
         Ok(VM {
             module,
@@ -64,14 +127,30 @@ impl VM {
             function_stack: Vec::new(),
             trap: None,
             breakpoints,
+            host_functions,
+            pending_entry: None,
+            history: None,
+            step_count: 0,
+            host_call_count: 0,
+            trace_writer: None,
+            replay: None,
+            instruction_tracer: None,
+            call_tracer: None,
+            profiler: None,
+            coverage: None,
         })
     }
 
+    pub const fn module(&self) -> &Rc<Module> {
+        &self.module
+    }
+
     pub fn value_stack(&self) -> &[Value] {
         &self.value_stack
     }
 
     pub fn value_stack_mut(&mut self) -> &mut Vec<Value> {
+        self.clear_history();
         &mut self.value_stack
     }
 
@@ -96,6 +175,7 @@ impl VM {
     }
 
     pub fn globals_mut(&mut self) -> &mut [Value] {
+        self.clear_history();
         &mut self.globals
     }
 
@@ -104,6 +184,7 @@ impl VM {
     }
 
     pub fn memories_mut(&mut self) -> &mut [Memory] {
+        self.clear_history();
         &mut self.memories
     }
 
@@ -112,11 +193,14 @@ impl VM {
             return Err(Trap::ValueStackOverflow);
         }
         self.value_stack.push(val);
+        self.record(Change::Push);
         Ok(())
     }
 
     pub(crate) fn pop(&mut self) -> VMResult<Value> {
-        self.value_stack.pop().ok_or(Trap::PopFromEmptyStack)
+        let val = self.value_stack.pop().ok_or(Trap::PopFromEmptyStack)?;
+        self.record(Change::Pop(val));
+        Ok(val)
     }
 
     pub(crate) fn pop_as<T: Number>(&mut self) -> VMResult<T> {
@@ -128,42 +212,140 @@ impl VM {
     }
 
     pub fn locals(&self) -> VMResult<&[Value]> {
-        if let Some(frame) = self.function_stack.last() {
-            return Ok(&frame.locals);
-        }
-        Err(Trap::NoFunctionFrame)
+        self.frame_locals(0)
     }
 
     pub fn locals_mut(&mut self) -> VMResult<&mut [Value]> {
-        if let Some(frame) = self.function_stack.last_mut() {
-            return Ok(&mut frame.locals);
+        self.frame_locals_mut(0)
+    }
+
+    // The locals of the function frame `depth` frames below the innermost one
+    pub fn frame_locals(&self, depth: usize) -> VMResult<&[Value]> {
+        let index = self.function_stack.len().checked_sub(depth + 1);
+        match index.and_then(|index| self.function_stack.get(index)) {
+            Some(frame) => Ok(&frame.locals),
+            None => Err(Trap::NoFunctionFrame),
+        }
+    }
+
+    pub fn frame_locals_mut(&mut self, depth: usize) -> VMResult<&mut [Value]> {
+        self.clear_history();
+        let index = self.function_stack.len().checked_sub(depth + 1);
+        match index.and_then(move |index| self.function_stack.get_mut(index)) {
+            Some(frame) => Ok(&mut frame.locals),
+            None => Err(Trap::NoFunctionFrame),
         }
-        Err(Trap::NoFunctionFrame)
+    }
+
+    fn push_label(&mut self, label: Label) {
+        self.label_stack.push(label);
+        self.record(Change::PushLabel);
+    }
+
+    fn pop_label(&mut self) -> Option<Label> {
+        let label = self.label_stack.pop()?;
+        self.record(Change::PopLabel(label));
+        Some(label)
+    }
+
+    fn push_frame(&mut self, frame: FunctionFrame) {
+        self.function_stack.push(frame);
+        self.record(Change::PushFrame);
+    }
+
+    // Returns the return address of the popped frame
+    fn pop_frame(&mut self) -> CodePosition {
+        let frame = self.function_stack.pop().unwrap();
+        let ret_addr = frame.ret_addr;
+        self.record(Change::PopFrame(frame));
+        ret_addr
+    }
+
+    fn set_local(&mut self, index: u32, val: Value) -> VMResult<()> {
+        let frame = self.function_stack.last_mut().ok_or(Trap::NoFunctionFrame)?;
+        let old = std::mem::replace(&mut frame.locals[index as usize], val);
+        self.record(Change::SetLocal(index, old));
+        Ok(())
+    }
+
+    fn set_global(&mut self, index: u32, val: Value) {
+        let old = std::mem::replace(&mut self.globals[index as usize], val);
+        self.record(Change::SetGlobal(index, old));
     }
 
     fn curr_func(&self) -> VMResult<&Function> {
         self.module
             .get_func(self.ip.func_index)
-            .ok_or_else(|| Trap::NoFunctionWithIndex(self.ip.func_index))
+            .ok_or(Trap::NoFunctionWithIndex(self.ip.func_index))
     }
 
     pub fn default_memory(&self) -> VMResult<&Memory> {
-        self.memories.get(0).ok_or(Trap::NoMemory)
+        self.memories.first().ok_or(Trap::NoMemory)
     }
 
     pub fn default_memory_mut(&mut self) -> VMResult<&mut Memory> {
+        self.clear_history();
         self.memories.get_mut(0).ok_or(Trap::NoMemory)
     }
 
+    pub fn is_host_function_defined(&self, index: u32) -> bool {
+        matches!(self.host_functions.get(index as usize), Some(Some(_)))
+    }
+
     pub fn default_table(&self) -> VMResult<&Table> {
-        self.tables.get(0).ok_or(Trap::NoTable)
+        self.tables.first().ok_or(Trap::NoTable)
+    }
+
+    fn push_block_label(&mut self, kind: LabelKind, block_type: &BlockType) -> VMResult<()> {
+        let (params, results) = self.module.block_signature(block_type);
+        let (params, results) = (params.len(), results.len());
+        let arity = match kind {
+            LabelKind::Bound(_) => params,
+            _ => results,
+        };
+        let stack_height = self
+            .value_stack
+            .len()
+            .checked_sub(params)
+            .ok_or(Trap::PopFromEmptyStack)?;
+        self.push_label(Label {
+            kind,
+            arity,
+            stack_height,
+        });
+        Ok(())
+    }
+
+    // Moves the values a branch to the label carries down to the stack height of the label
+    fn unwind(&mut self, label: Label) -> VMResult<()> {
+        if self.value_stack.len() <= label.stack_height + label.arity {
+            return Ok(());
+        }
+        let mut values = Vec::with_capacity(label.arity);
+        for _ in 0..label.arity {
+            values.push(self.pop()?);
+        }
+        while self.value_stack.len() > label.stack_height {
+            self.pop()?;
+        }
+        for value in values.into_iter().rev() {
+            self.push(value)?;
+        }
+        Ok(())
     }
 
     fn branch(&mut self, mut index: u32) -> VMResult<()> {
-        self.label_stack.truncate(self.label_stack.len() - index as usize);
-        match self.label_stack.last().unwrap() {
-            Label::Bound(target) => self.ip.instr_index = *target,
-            Label::Unbound => {
+        for _ in 0..index {
+            self.pop_label();
+        }
+        let label = *self.label_stack.last().ok_or(Trap::InvalidBranchIndex)?;
+        match label.kind {
+            LabelKind::Bound(target) => {
+                self.unwind(label)?;
+                self.ip.instr_index = target;
+            }
+            LabelKind::Unbound => {
+                self.unwind(label)?;
                 index += 1;
                 loop {
                     let curr_code = self.curr_func()?.instructions();
@@ -182,7 +364,8 @@ impl VM {
                     self.ip.instr_index += 1;
                 }
             }
-            _ => return Err(Trap::InvalidBranchIndex),
+            // A branch to the outermost label of a function returns from it
+            LabelKind::Return => self.return_from_func()?,
         }
         Ok(())
     }
@@ -195,11 +378,9 @@ impl VM {
                 Instruction::Block(_) => index += 1,
                 Instruction::Loop(_) => index += 1,
                 Instruction::If(_) => index += 1,
-                Instruction::Else => {
-                    if index == 1 {
-                        self.ip.instr_index += 1;
-                        break;
-                    }
+                Instruction::Else if index == 1 => {
+                    self.ip.instr_index += 1;
+                    break;
                 }
                 Instruction::End => index -= 1,
                 _ => (),
@@ -218,22 +399,22 @@ impl VM {
         let address = self.pop_as::<u32>()? + offset;
         self.push(self.default_memory()?.load::<T>(address)?.into())?;
         let size = core::mem::size_of::<T>() as u32;
-        if let Some(break_index) = self.breakpoints.borrow().find_memory(address, size, false) {
+        if let Some(break_index) = self.find_memory_watchpoint(address, size, false)? {
             return Err(Trap::WatchpointReached(break_index));
         }
         Ok(())
     }
 
-    fn perform_load_extend<T: LittleEndianConvert, U: Number>(&mut self, offset: u32) -> VMResult<()>
+    fn perform_load_extend<T, U: Number>(&mut self, offset: u32) -> VMResult<()>
     where
-        T: ExtendTo<U>,
+        T: LittleEndianConvert + ExtendTo<U>,
     {
         let address = self.pop_as::<u32>()? + offset;
         let val: T = self.default_memory()?.load(address)?;
         let val: U = val.extend_to();
         self.push(val.into())?;
         let size = core::mem::size_of::<T>() as u32;
-        if let Some(break_index) = self.breakpoints.borrow().find_memory(address, size, false) {
+        if let Some(break_index) = self.find_memory_watchpoint(address, size, false)? {
             return Err(Trap::WatchpointReached(break_index));
         }
         Ok(())
@@ -242,24 +423,24 @@ impl VM {
     fn perform_store<T: Number + LittleEndianConvert>(&mut self, offset: u32) -> VMResult<()> {
         let value = self.pop_as::<T>()?;
         let address = self.pop_as::<u32>()? + offset;
-        self.default_memory_mut()?.store(address, value)?;
+        self.memories.get_mut(0).ok_or(Trap::NoMemory)?.store(address, value)?;
         let size = core::mem::size_of::<T>() as u32;
-        if let Some(break_index) = self.breakpoints.borrow().find_memory(address, size, true) {
+        if let Some(break_index) = self.find_memory_watchpoint(address, size, true)? {
             return Err(Trap::WatchpointReached(break_index));
         }
         Ok(())
     }
 
-    fn perform_store_wrap<T: LittleEndianConvert, U: Number>(&mut self, offset: u32) -> VMResult<()>
+    fn perform_store_wrap<T: LittleEndianConvert, U>(&mut self, offset: u32) -> VMResult<()>
     where
-        U: WrapTo<T>,
+        U: Number + WrapTo<T>,
     {
         let value: U = self.pop_as()?;
         let value: T = value.wrap_to();
         let address = self.pop_as::<u32>()? + offset;
-        self.default_memory_mut()?.store(address, value)?;
+        self.memories.get_mut(0).ok_or(Trap::NoMemory)?.store(address, value)?;
         let size = core::mem::size_of::<T>() as u32;
-        if let Some(break_index) = self.breakpoints.borrow().find_memory(address, size, true) {
+        if let Some(break_index) = self.find_memory_watchpoint(address, size, true)? {
             return Err(Trap::WatchpointReached(break_index));
         }
         Ok(())
@@ -292,12 +473,10 @@ impl VM {
     }
 
     fn call(&mut self, index: u32) -> VMResult<()> {
-        let func = self
-            .module
-            .get_func(index)
-            .ok_or_else(|| Trap::NoFunctionWithIndex(index))?;
+        let func = self.module.get_func(index).ok_or(Trap::NoFunctionWithIndex(index))?;
 
         let params_count = func.func_type().params().len();
+        let results_count = func.results().len();
         let mut locals = Vec::new();
 
         for _ in 0..params_count {
@@ -312,12 +491,16 @@ impl VM {
         if self.label_stack.len() >= LABEL_STACK_LIMIT {
             return Err(Trap::LabelStackOverflow);
         }
-        self.label_stack.push(Label::Return);
+        self.push_label(Label {
+            kind: LabelKind::Return,
+            arity: results_count,
+            stack_height: self.value_stack.len(),
+        });
 
         if self.function_stack.len() >= FUNCTION_STACK_LIMIT {
             return Err(Trap::FunctionStackOverflow);
         }
-        self.function_stack.push(FunctionFrame {
+        self.push_frame(FunctionFrame {
             ret_addr: self.ip,
             locals,
         });
@@ -327,45 +510,271 @@ impl VM {
             instr_index: 0,
         };
 
+        if let Some(profiler) = &self.profiler {
+            profiler.borrow_mut().enter(index);
+        }
+        if let Some(tracer) = &self.call_tracer {
+            let frame = self.function_stack.last().unwrap();
+            let args = &frame.locals[..params_count];
+            tracer
+                .borrow_mut()
+                .enter(&self.module, index, self.function_stack.len() - 1, args)
+                .map_err(|error| Trap::TraceWriteFailed(error.to_string()))?;
+        }
+
         Ok(())
     }
 
-    pub fn start(&mut self) -> VMResult<()> {
-        if let Some(start_function) = self.module.start_func() {
-            self.run_func_paused(start_function, &[])
-        } else {
-            Err(Trap::NoStartFunction)
+    fn return_from_func(&mut self) -> VMResult<()> {
+        while let Some(label) = self.pop_label() {
+            if let LabelKind::Return = label.kind {
+                self.unwind(label)?;
+                return self.leave_func();
+            }
+        }
+        Err(Trap::NoFunctionFrame)
+    }
+
+    // Called after the return label of the current function was popped
+    fn leave_func(&mut self) -> VMResult<()> {
+        let func_index = self.ip.func_index;
+        let depth = self.function_stack.len() - 1;
+        if !self.label_stack.is_empty() {
+            self.ip = self.pop_frame();
+        }
+
+        if let Some(profiler) = &self.profiler {
+            profiler.borrow_mut().leave();
+        }
+        if let Some(tracer) = &self.call_tracer {
+            let results_count = self.module.func(func_index).results().len();
+            let results = &self.value_stack[self.value_stack.len().saturating_sub(results_count)..];
+            tracer
+                .borrow_mut()
+                .leave(&self.module, func_index, depth, results)
+                .map_err(|error| Trap::TraceWriteFailed(error.to_string()))?;
+        }
+        Ok(())
+    }
+
+    fn call_host_function(&mut self) -> VMResult<()> {
+        let index = self.ip.func_index;
+        let call_index = self.host_call_count;
+        self.host_call_count += 1;
+        self.record(Change::HostCall);
+        let results = match self.replay.clone() {
+            Some(trace) => self.replay_host_function(&trace, call_index)?,
+            None => self.call_live_host_function(index)?,
+        };
+
+        let module = Rc::clone(&self.module);
+        let expected_types = module.func(index).results();
+        if results.len() != expected_types.len() {
+            return Err(Trap::InvalidHostResultCount {
+                expected: expected_types.len(),
+                found: results.len(),
+            });
+        }
+        for (result, &expected) in results.into_iter().zip(expected_types) {
+            if result.value_type() != expected {
+                return Err(Trap::TypeError {
+                    expected,
+                    found: result.value_type(),
+                });
+            }
+            self.push(result)?;
+        }
+
+        self.return_from_func()
+    }
+
+    fn call_live_host_function(&mut self, index: u32) -> VMResult<Vec<Value>> {
+        let host_function = match self.host_functions.get(index as usize) {
+            Some(Some(host_function)) => Rc::clone(host_function),
+            _ => return Err(Trap::UnsupportedCallToImportedFunction(index)),
+        };
+        let args = self.locals()?.to_vec();
+        // Memory changes are journaled by the memories themselves but globals have to be compared afterwards
+        let tracking = self.history.is_some() || self.trace_writer.is_some();
+        let old_globals = if tracking { Some(self.globals.clone()) } else { None };
+        let mut ctx = HostContext {
+            memories: &mut self.memories,
+            globals: &mut self.globals,
+        };
+        let results = host_function.call(&mut ctx, &args);
+        let old_globals = match old_globals {
+            Some(old_globals) => old_globals,
+            None => return results,
+        };
+
+        let mut effects = Vec::new();
+        for memory_index in 0..self.memories.len() {
+            for change in self.memories[memory_index].take_journal() {
+                if self.trace_writer.is_some() {
+                    let memory = &self.memories[memory_index];
+                    effects.push(match &change {
+                        MemoryChange::Write { address, old_bytes } => HostEffect::MemoryWrite {
+                            memory: memory_index as u32,
+                            address: *address,
+                            bytes: memory.read(*address, old_bytes.len() as u32)?.to_vec(),
+                        },
+                        MemoryChange::Grow { .. } => HostEffect::MemoryResize {
+                            memory: memory_index as u32,
+                            pages: memory.page_count(),
+                        },
+                    });
+                }
+                self.record(Change::Memory(memory_index as u32, change));
+            }
+        }
+        for (global_index, old) in old_globals.into_iter().enumerate() {
+            let value = self.globals[global_index];
+            if value != old {
+                effects.push(HostEffect::SetGlobal {
+                    index: global_index as u32,
+                    value,
+                });
+                self.record(Change::SetGlobal(global_index as u32, old));
+            }
+        }
+
+        if let Some(writer) = &mut self.trace_writer {
+            let outcome = match &results {
+                Ok(results) => HostOutcome::Results(results.clone()),
+                Err(Trap::WasiExit(code)) => HostOutcome::Exit(*code),
+                Err(trap) => HostOutcome::Error(trap.to_string()),
+            };
+            let call = HostCall {
+                func_index: index,
+                effects,
+                outcome,
+            };
+            writer
+                .write_host_call(&call)
+                .map_err(|error| Trap::TraceWriteFailed(error.to_string()))?;
+        }
+        results
+    }
+
+    fn replay_host_function(&mut self, trace: &Trace, call_index: u64) -> VMResult<Vec<Value>> {
+        let call = match trace.host_call(call_index) {
+            Some(call) if call.func_index == self.ip.func_index => call,
+            _ => return Err(Trap::ReplayDiverged(self.ip)),
+        };
+        for effect in &call.effects {
+            match effect {
+                HostEffect::MemoryWrite { memory, address, bytes } => {
+                    self.memories
+                        .get_mut(*memory as usize)
+                        .ok_or(Trap::NoMemory)?
+                        .write(*address, bytes)?;
+                }
+                HostEffect::MemoryResize { memory, pages } => {
+                    let memory = self.memories.get_mut(*memory as usize).ok_or(Trap::NoMemory)?;
+                    memory.grow(pages.saturating_sub(memory.page_count()));
+                }
+                HostEffect::SetGlobal { index, value } => self.set_global(*index, *value),
+            }
+        }
+        match &call.outcome {
+            HostOutcome::Results(results) => Ok(results.clone()),
+            HostOutcome::Exit(code) => Err(Trap::WasiExit(*code)),
+            HostOutcome::Error(message) => Err(Trap::HostError(message.clone())),
+        }
+    }
+
+    // The start function (if any) always runs first. Without an entry function execution pauses at its beginning.
+    pub fn start(&mut self, entry: Option<u32>) -> VMResult<()> {
+        match (self.module.start_func(), entry) {
+            (Some(start_function), Some(func_index)) => {
+                let entry = PendingEntry {
+                    func_index,
+                    pause: true,
+                };
+                match self.run_start_func(start_function, entry) {
+                    Trap::EntryReached(_) => Ok(()),
+                    trap => Err(trap),
+                }
+            }
+            (Some(func_index), None) | (None, Some(func_index)) => {
+                let result = self.run_func_paused(func_index, &[]);
+                self.clear_history();
+                result
+            }
+            (None, None) => Err(Trap::NoStartFunction),
         }
     }
 
     fn run_func_paused(&mut self, index: u32, args: &[Value]) -> VMResult<()> {
-        self.function_stack.clear();
-        self.label_stack.clear();
-        self.value_stack.clear();
+        while let Some(val) = self.value_stack.pop() {
+            self.record(Change::Pop(val));
+        }
+        while self.pop_label().is_some() {}
+        while !self.function_stack.is_empty() {
+            self.pop_frame();
+        }
         self.trap = None;
+        self.pending_entry = None;
         self.ip = CodePosition::default();
+        self.sync_profiler();
         for arg in args {
             self.push(*arg)?
         }
         self.call(index)
     }
 
-    pub fn run(&mut self) -> Trap {
-        if let Some(start_function) = self.module.start_func() {
-            self.run_func(start_function, &[])
-        } else {
-            Trap::NoStartFunction
+    pub fn run(&mut self, entry: Option<u32>) -> Trap {
+        match (self.module.start_func(), entry) {
+            (Some(start_function), Some(func_index)) => {
+                let entry = PendingEntry {
+                    func_index,
+                    pause: false,
+                };
+                self.run_start_func(start_function, entry)
+            }
+            (Some(func_index), None) | (None, Some(func_index)) => self.run_func(func_index, &[]),
+            (None, None) => Trap::NoStartFunction,
         }
     }
 
+    fn run_start_func(&mut self, start_function: u32, entry: PendingEntry) -> Trap {
+        let result = self.run_func_paused(start_function, &[]);
+        self.clear_history();
+        if let Err(trap) = result {
+            return trap;
+        }
+        self.pending_entry = Some(entry);
+        self.continue_from_func_start()
+    }
+
     pub fn run_func(&mut self, index: u32, args: &[Value]) -> Trap {
-        if let Err(trap) = self.run_func_paused(index, args) {
+        let result = self.run_func_paused(index, args);
+        self.clear_history();
+        if let Err(trap) = result {
             return trap;
         }
-        if let Some(index) = self.breakpoints.borrow().find_code(self.ip) {
-            return Trap::BreakpointReached(index);
+        self.continue_from_func_start()
+    }
+
+    fn continue_from_func_start(&mut self) -> Trap {
+        match self.find_breakpoint() {
+            Ok(Some(index)) => Trap::BreakpointReached(index),
+            Ok(None) => self.continue_execution(),
+            Err(trap) => trap,
         }
-        self.continue_execution()
+    }
+
+    fn enter_pending_entry(&mut self) -> VMResult<()> {
+        let entry = self.pending_entry.take().unwrap();
+        self.record(Change::TakeEntry(entry));
+        self.run_func_paused(entry.func_index, &[])?;
+        if entry.pause {
+            return Err(Trap::EntryReached(entry.func_index));
+        }
+        if let Some(index) = self.find_breakpoint()? {
+            return Err(Trap::BreakpointReached(index));
+        }
+        Ok(())
     }
 
     pub fn continue_execution(&mut self) -> Trap {
@@ -396,14 +805,315 @@ impl VM {
         }
     }
 
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            memories: self.memories.clone(),
+            tables: self.tables.clone(),
+            ip: self.ip,
+            globals: self.globals.clone(),
+            value_stack: self.value_stack.clone(),
+            label_stack: self.label_stack.clone(),
+            function_stack: self.function_stack.clone(),
+            trap: self.trap.clone(),
+            pending_entry: self.pending_entry,
+            step_count: self.step_count,
+            host_call_count: self.host_call_count,
+        }
+    }
+
+    // The trace only describes a single uninterrupted execution, so it has to be stopped first
+    pub fn restore(&mut self, snapshot: Snapshot) -> VMResult<()> {
+        if self.trace_writer.is_some() {
+            return Err(Trap::RewindWhileTracing);
+        }
+        self.memories = snapshot.memories;
+        self.tables = snapshot.tables;
+        self.ip = snapshot.ip;
+        self.globals = snapshot.globals;
+        self.value_stack = snapshot.value_stack;
+        self.label_stack = snapshot.label_stack;
+        self.function_stack = snapshot.function_stack;
+        self.trap = snapshot.trap;
+        self.pending_entry = snapshot.pending_entry;
+        self.step_count = snapshot.step_count;
+        self.host_call_count = snapshot.host_call_count;
+
+        self.update_journaling();
+        self.clear_history();
+        self.sync_profiler();
+        Ok(())
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.history.is_some()
+    }
+
+    // Recording keeps an undo log of every executed step so that execution can be reversed
+    pub fn set_recording(&mut self, recording: bool) {
+        if recording != self.is_recording() {
+            self.history = if recording { Some(History::new()) } else { None };
+            self.update_journaling();
+        }
+    }
+
+    pub fn is_recording_trace(&self) -> bool {
+        self.trace_writer.is_some()
+    }
+
+    pub fn set_trace_writer(&mut self, writer: Option<TraceWriter>) {
+        self.trace_writer = writer;
+        self.update_journaling();
+    }
+
+    pub fn replay(&self) -> Option<&Rc<Trace>> {
+        self.replay.as_ref()
+    }
+
+    // While replaying, host functions aren't called. Instead their recorded effects and results are used.
+    pub fn set_replay(&mut self, trace: Option<Rc<Trace>>) {
+        self.replay = trace;
+    }
+
+    pub fn set_instruction_tracer(&mut self, tracer: Option<Rc<RefCell<InstructionTracer>>>) {
+        self.instruction_tracer = tracer;
+    }
+
+    pub fn set_call_tracer(&mut self, tracer: Option<Rc<RefCell<CallTracer>>>) {
+        self.call_tracer = tracer;
+    }
+
+    pub fn set_profiler(&mut self, profiler: Option<Rc<RefCell<Profiler>>>) {
+        self.profiler = profiler;
+        self.sync_profiler();
+    }
+
+    pub fn set_coverage(&mut self, coverage: Option<Rc<RefCell<Coverage>>>) {
+        self.coverage = coverage;
+    }
+
+    // The indices of the functions on the call stack, starting with the outermost one
+    pub fn call_stack(&self) -> Vec<u32> {
+        if self.function_stack.is_empty() {
+            return Vec::new();
+        }
+        let mut stack: Vec<u32> = self
+            .function_stack
+            .iter()
+            .skip(1)
+            .map(|frame| frame.ret_addr.func_index)
+            .collect();
+        stack.push(self.ip.func_index);
+        stack
+    }
+
+    fn sync_profiler(&self) {
+        if let Some(profiler) = &self.profiler {
+            profiler.borrow_mut().sync(&self.call_stack());
+        }
+    }
+
+    // The number of executed steps since the instance was created
+    pub const fn step_count(&self) -> u64 {
+        self.step_count
+    }
+
+    fn update_journaling(&mut self) {
+        let journaling = self.history.is_some() || self.trace_writer.is_some();
+        for memory in &mut self.memories {
+            memory.set_journaling(journaling);
+        }
+    }
+
+    pub fn history_len(&self) -> usize {
+        self.history.as_ref().map_or(0, History::step_count)
+    }
+
+    // Changes from outside of execution can't be undone, so they end the recorded history
+    fn clear_history(&mut self) {
+        if let Some(history) = &mut self.history {
+            history.clear();
+        }
+    }
+
+    fn record(&mut self, change: Change) {
+        if let Some(history) = &mut self.history {
+            history.record(change);
+        }
+    }
+
+    fn begin_step(&mut self) {
+        if let Some(history) = &mut self.history {
+            history.begin_step(self.ip);
+        }
+        for memory in &mut self.memories {
+            memory.take_journal();
+        }
+    }
+
+    fn end_step(&mut self) {
+        if let Some(history) = &mut self.history {
+            for (index, memory) in self.memories.iter_mut().enumerate() {
+                for change in memory.take_journal() {
+                    history.record(Change::Memory(index as u32, change));
+                }
+            }
+        }
+    }
+
+    // Undoes the last recorded step and reports watchpoints on values it wrote as well as breakpoints at the
+    // instruction it executed
+    fn undo_step(&mut self) -> VMResult<()> {
+        if self.trace_writer.is_some() {
+            return Err(Trap::RewindWhileTracing);
+        }
+        let (ip, changes) = self
+            .history
+            .as_mut()
+            .and_then(History::pop_step)
+            .ok_or(Trap::NoMoreHistory)?;
+        let mut watchpoint = Ok(None);
+        for change in changes {
+            match change {
+                Change::Step(_) => unreachable!(),
+                Change::Push => {
+                    self.value_stack.pop();
+                }
+                Change::Pop(val) => self.value_stack.push(val),
+                Change::PushLabel => {
+                    self.label_stack.pop();
+                }
+                Change::PopLabel(label) => self.label_stack.push(label),
+                Change::PushFrame => {
+                    self.function_stack.pop();
+                }
+                Change::PopFrame(frame) => self.function_stack.push(frame),
+                Change::SetLocal(index, val) => {
+                    if let Some(frame) = self.function_stack.last_mut() {
+                        frame.locals[index as usize] = val;
+                    }
+                }
+                Change::SetGlobal(index, val) => {
+                    self.globals[index as usize] = val;
+                    if let Ok(None) = watchpoint {
+                        let candidates = self.breakpoints.borrow().global_matches(index, true);
+                        watchpoint = self.first_triggered_reverse(candidates);
+                    }
+                }
+                Change::Memory(index, change) => {
+                    if let MemoryChange::Write { address, old_bytes } = &change {
+                        if let (0, Ok(None)) = (index, &watchpoint) {
+                            let size = old_bytes.len() as u32;
+                            let candidates = self.breakpoints.borrow().memory_matches(*address, size, true);
+                            watchpoint = self.first_triggered_reverse(candidates);
+                        }
+                    }
+                    self.memories[index as usize].undo(change);
+                }
+                Change::TakeEntry(entry) => self.pending_entry = Some(entry),
+                Change::HostCall => self.host_call_count -= 1,
+            }
+        }
+        self.ip = ip;
+        self.trap = None;
+        self.step_count -= 1;
+        self.sync_profiler();
+
+        if let Some(index) = watchpoint? {
+            return Err(Trap::WatchpointReached(index));
+        }
+        let candidates = self.breakpoints.borrow().code_matches(self.ip);
+        if let Some(index) = self.first_triggered_reverse(candidates)? {
+            return Err(Trap::BreakpointReached(index));
+        }
+        Ok(())
+    }
+
+    pub fn reverse_step(&mut self) -> VMResult<()> {
+        self.undo_step()
+    }
+
+    pub fn reverse_step_over(&mut self) -> VMResult<()> {
+        let curr_frame_index = self.function_stack.len();
+        loop {
+            self.undo_step()?;
+            if curr_frame_index >= self.function_stack.len() {
+                return Ok(());
+            }
+        }
+    }
+
+    pub fn reverse_step_out(&mut self) -> VMResult<()> {
+        let curr_frame_index = self.function_stack.len();
+        loop {
+            self.undo_step()?;
+            if curr_frame_index > self.function_stack.len() {
+                return Ok(());
+            }
+        }
+    }
+
+    pub fn reverse_continue(&mut self) -> Trap {
+        loop {
+            if let Err(trap) = self.undo_step() {
+                return trap;
+            }
+        }
+    }
+
     pub fn execute_step(&mut self) -> VMResult<()> {
         if let Some(trap) = &self.trap {
             return Err(trap.to_owned());
         }
 
-        if let Err(trap) = self.execute_step_internal() {
+        if let Some(trace) = &self.replay {
+            match trace.position(self.step_count) {
+                Some(expected) if expected == self.ip => (),
+                Some(expected) => return Err(Trap::ReplayDiverged(expected)),
+                None => return Err(Trap::ReplayEnded),
+            }
+        }
+        if let Some(writer) = &mut self.trace_writer {
+            writer
+                .write_step(self.ip)
+                .map_err(|error| Trap::TraceWriteFailed(error.to_string()))?;
+        }
+        self.step_count += 1;
+
+        let ip = self.ip;
+        if let Some(profiler) = &self.profiler {
+            profiler.borrow_mut().step();
+        }
+        if let Some(coverage) = &self.coverage {
+            coverage.borrow_mut().hit(&self.module, ip);
+        }
+        self.begin_step();
+        let mut result = match self.execute_step_internal() {
+            Err(Trap::ExecutionFinished) if self.pending_entry.is_some() => self.enter_pending_entry(),
+            result => result,
+        };
+        self.end_step();
+
+        if let Some(tracer) = &self.instruction_tracer {
+            let mut tracer = tracer.borrow_mut();
+            let mut traced = tracer.trace(self, ip);
+            if result.is_err() {
+                traced = traced.and_then(|()| tracer.flush());
+            }
+            if let (Ok(()), Err(error)) = (&result, traced) {
+                result = Err(Trap::TraceWriteFailed(error.to_string()));
+            }
+        }
+        if let (Some(tracer), Err(_)) = (&self.call_tracer, &result) {
+            tracer.borrow_mut().flush().ok();
+        }
+
+        if let Err(trap) = result {
             match trap {
-                Trap::BreakpointReached(_) | Trap::WatchpointReached(_) => return Err(trap),
+                Trap::BreakpointReached(_)
+                | Trap::WatchpointReached(_)
+                | Trap::BreakpointConditionFailed { .. }
+                | Trap::EntryReached(_)
+                | Trap::TraceWriteFailed(_) => return Err(trap),
                 _ => {
                     self.trap = Some(trap.clone());
                     return Err(trap);
@@ -418,21 +1128,8 @@ impl VM {
     fn execute_step_internal(&mut self) -> VMResult<()> {
         let func = self.module.get_func(self.ip.func_index).unwrap();
         if func.is_imported() {
-            // TODO
-            // if let Some(wasi_func) = func.wasi_function() {
-            //     wasi_func.handle(self)?;
-            //     loop {
-            //         if let Some(Label::Return) = self.label_stack.pop() {
-            //             if !self.label_stack.is_empty() {
-            //                 let frame = self.function_stack.pop().unwrap();
-            //                 self.ip = frame.ret_addr;
-            //             }
-            //             break;
-            //         }
-            //     }
-            //     return Ok(());
-            // }
-            return Err(Trap::UnsupportedCallToImportedFunction(self.ip.func_index));
+            self.call_host_function()?;
+            return self.check_step_end();
         }
 
         let instr = func.instructions()[self.ip.instr_index as usize].clone();
@@ -441,21 +1138,25 @@ impl VM {
         match instr {
             Instruction::Unreachable => return Err(Trap::ReachedUnreachable),
             Instruction::Nop => (),
-            Instruction::Block(_) => self.label_stack.push(Label::Unbound),
-            Instruction::Loop(_) => self.label_stack.push(Label::Bound(self.ip.instr_index)),
-            Instruction::If(_) => {
-                self.label_stack.push(Label::Unbound);
-                if self.pop_as::<u32>()? == 0 {
+            Instruction::Block(block_type) => self.push_block_label(LabelKind::Unbound, &block_type)?,
+            Instruction::Loop(block_type) => {
+                self.push_block_label(LabelKind::Bound(self.ip.instr_index), &block_type)?
+            }
+            Instruction::If(block_type) => {
+                let condition = self.pop_as::<u32>()?;
+                self.push_block_label(LabelKind::Unbound, &block_type)?;
+                if condition == 0 {
                     self.branch_else()?;
                 }
             }
             Instruction::Else => self.branch(0)?,
             Instruction::End => {
-                if let Some(Label::Return) = self.label_stack.pop() {
-                    if !self.label_stack.is_empty() {
-                        let frame = self.function_stack.pop().unwrap();
-                        self.ip = frame.ret_addr;
-                    }
+                if let Some(Label {
+                    kind: LabelKind::Return,
+                    ..
+                }) = self.pop_label()
+                {
+                    self.leave_func()?;
                 }
             }
             Instruction::Br(index) => self.branch(index)?,
@@ -469,15 +1170,7 @@ impl VM {
                 let depth = table_data.table.get(index as usize).unwrap_or(&table_data.default);
                 self.branch(*depth)?;
             }
-            Instruction::Return => loop {
-                if let Some(Label::Return) = self.label_stack.pop() {
-                    if !self.label_stack.is_empty() {
-                        let frame = self.function_stack.pop().unwrap();
-                        self.ip = frame.ret_addr;
-                    }
-                    break;
-                }
-            },
+            Instruction::Return => self.return_from_func()?,
 
             // Calls
             Instruction::Call(index) => self.call(index)?,
@@ -490,7 +1183,7 @@ impl VM {
                 let func = self
                     .module
                     .get_func(func_index)
-                    .ok_or_else(|| Trap::NoFunctionWithIndex(func_index))?;
+                    .ok_or(Trap::NoFunctionWithIndex(func_index))?;
 
                 if func.func_type().type_ref() != signature {
                     return Err(Trap::IndirectCallTypeMismatch);
@@ -512,28 +1205,28 @@ impl VM {
                 }
             }
             Instruction::GetLocal(index) => {
-                let val = self.locals_mut()?[index as usize];
+                let val = self.locals()?[index as usize];
                 self.push(val)?;
             }
             Instruction::SetLocal(index) => {
                 let val = self.pop()?;
-                self.locals_mut()?[index as usize] = val;
+                self.set_local(index, val)?;
             }
             Instruction::TeeLocal(index) => {
-                let val = self.value_stack.last().ok_or(Trap::PopFromEmptyStack)?;
-                self.locals_mut()?[index as usize] = *val;
+                let val = *self.value_stack.last().ok_or(Trap::PopFromEmptyStack)?;
+                self.set_local(index, val)?;
             }
             Instruction::GetGlobal(index) => {
                 let val = self.globals[index as usize];
                 self.push(val)?;
-                if let Some(break_index) = self.breakpoints.borrow().find_global(index, false) {
+                if let Some(break_index) = self.find_global_watchpoint(index, false)? {
                     return Err(Trap::WatchpointReached(break_index));
                 }
             }
             Instruction::SetGlobal(index) => {
                 let val = self.pop()?;
-                self.globals[index as usize] = val;
-                if let Some(break_index) = self.breakpoints.borrow().find_global(index, true) {
+                self.set_global(index, val);
+                if let Some(break_index) = self.find_global_watchpoint(index, true)? {
                     return Err(Trap::WatchpointReached(break_index));
                 }
             }
@@ -568,7 +1261,7 @@ impl VM {
             Instruction::CurrentMemory(_) => self.push(Value::I32(self.default_memory()?.page_count() as i32))?,
             Instruction::GrowMemory(_) => {
                 let delta = self.pop_as::<u32>()?;
-                let result = self.default_memory_mut()?.grow(delta);
+                let result = self.memories.get_mut(0).ok_or(Trap::NoMemory)?.grow(delta);
                 self.push(Value::I32(result))?;
             }
 
@@ -580,8 +1273,8 @@ impl VM {
             Instruction::I32Eqz => self.unop(|x: u32| bool_val(x == 0))?,
             Instruction::I32Eq => self.binop(|a: u32, b: u32| bool_val(a == b))?,
             Instruction::I32Ne => self.binop(|a: u32, b: u32| bool_val(a != b))?,
-            Instruction::I32LtS => self.binop(|a: u32, b: u32| bool_val(a as i32 == b as i32))?,
-            Instruction::I32LtU => self.binop(|a: u32, b: u32| bool_val(a == b))?,
+            Instruction::I32LtS => self.binop(|a: u32, b: u32| bool_val((a as i32) < b as i32))?,
+            Instruction::I32LtU => self.binop(|a: u32, b: u32| bool_val(a < b))?,
             Instruction::I32GtS => self.binop(|a: u32, b: u32| bool_val(a as i32 > b as i32))?,
             Instruction::I32GtU => self.binop(|a: u32, b: u32| bool_val(a > b))?,
             Instruction::I32LeS => self.binop(|a: u32, b: u32| bool_val(a as i32 <= b as i32))?,
@@ -592,8 +1285,8 @@ impl VM {
             Instruction::I64Eqz => self.unop(|x: u64| bool_val(x == 0))?,
             Instruction::I64Eq => self.binop(|a: u64, b: u64| bool_val(a == b))?,
             Instruction::I64Ne => self.binop(|a: u64, b: u64| bool_val(a != b))?,
-            Instruction::I64LtS => self.binop(|a: u64, b: u64| bool_val(a as i64 == b as i64))?,
-            Instruction::I64LtU => self.binop(|a: u64, b: u64| bool_val(a == b))?,
+            Instruction::I64LtS => self.binop(|a: u64, b: u64| bool_val((a as i64) < b as i64))?,
+            Instruction::I64LtU => self.binop(|a: u64, b: u64| bool_val(a < b))?,
             Instruction::I64GtS => self.binop(|a: u64, b: u64| bool_val(a as i64 > b as i64))?,
             Instruction::I64GtU => self.binop(|a: u64, b: u64| bool_val(a > b))?,
             Instruction::I64LeS => self.binop(|a: u64, b: u64| bool_val(a as i64 <= b as i64))?,
@@ -727,11 +1420,80 @@ impl VM {
             Instruction::F64ReinterpretI64 => self.unop(F64::from_bits)?,
         }
 
+        self.check_step_end()
+    }
+
+    fn find_breakpoint(&self) -> VMResult<Option<u32>> {
+        let candidates = self.breakpoints.borrow().code_matches(self.ip);
+        self.first_triggered(candidates)
+    }
+
+    fn find_memory_watchpoint(&self, address: u32, size: u32, write: bool) -> VMResult<Option<u32>> {
+        let candidates = self.breakpoints.borrow().memory_matches(address, size, write);
+        self.first_triggered(candidates)
+    }
+
+    fn find_global_watchpoint(&self, index: u32, write: bool) -> VMResult<Option<u32>> {
+        let candidates = self.breakpoints.borrow().global_matches(index, write);
+        self.first_triggered(candidates)
+    }
+
+    // Counts a hit for every enabled candidate whose condition holds and triggers the first one that pauses
+    // execution. All conditions are evaluated first, so a failing one leaves the hit state unchanged.
+    fn first_triggered(&self, candidates: Vec<u32>) -> VMResult<Option<u32>> {
+        let mut holding = Vec::new();
+        for index in candidates {
+            if self.breakpoint_holds(index)? {
+                holding.push(index);
+            }
+        }
+        let mut triggered = None;
+        for index in holding {
+            if self.breakpoints.borrow_mut().register_hit(index) && triggered.is_none() {
+                triggered = Some(index);
+            }
+        }
+        if let Some(index) = triggered {
+            self.breakpoints.borrow_mut().trigger(index);
+        }
+        Ok(triggered)
+    }
+
+    // Reverse execution pauses at the same breakpoints but doesn't count hits, so hit and ignore counts and
+    // temporary breakpoints are left unchanged
+    fn first_triggered_reverse(&self, candidates: Vec<u32>) -> VMResult<Option<u32>> {
+        for index in candidates {
+            if self.breakpoint_holds(index)? && self.breakpoints.borrow().would_trigger(index) {
+                self.breakpoints.borrow_mut().set_last_hit(index);
+                return Ok(Some(index));
+            }
+        }
+        Ok(None)
+    }
+
+    // A condition that fails to evaluate pauses execution with the error
+    fn breakpoint_holds(&self, index: u32) -> VMResult<bool> {
+        let breakpoints = self.breakpoints.borrow();
+        if !breakpoints.is_enabled(index) {
+            return Ok(false);
+        }
+        match breakpoints.condition(index) {
+            Some(condition) => condition
+                .eval_condition(self)
+                .map_err(|error| Trap::BreakpointConditionFailed {
+                    index,
+                    error: error.to_string(),
+                }),
+            None => Ok(true),
+        }
+    }
+
+    fn check_step_end(&self) -> VMResult<()> {
         if self.label_stack.is_empty() {
             return Err(Trap::ExecutionFinished);
         }
 
-        if let Some(index) = self.breakpoints.borrow().find_code(self.ip) {
+        if let Some(index) = self.find_breakpoint()? {
             return Err(Trap::BreakpointReached(index));
         }
 
@@ -746,3 +1508,209 @@ fn bool_val(val: bool) -> u32 {
         false => 0,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::{load_wat, temp_path};
+    use crate::vm::{CodePosition, Trap};
+    use crate::{Breakpoint, Debugger, DebuggerError, Expr, Value};
+
+    #[test]
+    fn lt_comparisons() {
+        let mut dbg = load_wat(
+            r#"(module
+                (func (param i32 i32) (result i32) (i32.lt_s (local.get 0) (local.get 1)))
+                (func (param i32 i32) (result i32) (i32.lt_u (local.get 0) (local.get 1)))
+                (func (param i64 i64) (result i32) (i64.lt_s (local.get 0) (local.get 1)))
+                (func (param i64 i64) (result i32) (i64.lt_u (local.get 0) (local.get 1))))"#,
+        );
+        let cases = [
+            (0, Value::I32(1), Value::I32(2), 1),
+            (0, Value::I32(2), Value::I32(2), 0),
+            (0, Value::I32(-1), Value::I32(1), 1),
+            (1, Value::I32(1), Value::I32(2), 1),
+            (1, Value::I32(2), Value::I32(2), 0),
+            (1, Value::I32(-1), Value::I32(1), 0),
+            (2, Value::I64(1), Value::I64(2), 1),
+            (2, Value::I64(2), Value::I64(2), 0),
+            (2, Value::I64(-1), Value::I64(1), 1),
+            (3, Value::I64(1), Value::I64(2), 1),
+            (3, Value::I64(2), Value::I64(2), 0),
+            (3, Value::I64(-1), Value::I64(1), 0),
+        ];
+        for (func, a, b, expected) in cases.iter() {
+            assert!(matches!(dbg.call(*func, &[*a, *b]).unwrap(), Trap::ExecutionFinished));
+            assert_eq!(dbg.vm().unwrap().value_stack(), &[Value::I32(*expected)]);
+        }
+    }
+
+    #[test]
+    fn multi_value() {
+        let mut dbg = load_wat(
+            r#"(module
+                (type $pair (func (param i32 i32) (result i32 i32)))
+                (func $swap (type $pair) (local.get 1) (local.get 0))
+                ;; A block with params and several results
+                (func (type $pair)
+                    (local.get 0)
+                    (local.get 1)
+                    (block (type $pair) (call $swap)))
+                ;; Sums the numbers up to the param in a loop that takes the sum and counter as params
+                (func (param $n i32) (result i32)
+                    (i32.const 0)
+                    (local.get $n)
+                    (loop $loop (param i32 i32) (result i32)
+                        (local.set $n)
+                        (i32.add (local.get $n))
+                        (local.tee $n (i32.sub (local.get $n) (i32.const 1)))
+                        (br_if $loop (local.get $n))
+                        (drop)))
+                (func (param i32) (result i32)
+                    (i32.const 10)
+                    (if (param i32) (result i32) (local.get 0)
+                        (then (i32.add (i32.const 1)))
+                        (else (i32.sub (i32.const 1)))))
+                ;; Branches and returns drop the values below their results
+                (func (result i32 i32)
+                    (block (result i32 i32) (i32.const 7) (i32.const 1) (i32.const 2) (br 0)))
+                (func (result i32 i64)
+                    (i32.const 0)
+                    (block (i32.const 5) (i64.const 6) (return))
+                    (unreachable))
+                (func (result i32 i32) (i32.const 3) (i32.const 4) (br 0)))"#,
+        );
+        assert_eq!(
+            dbg.file().unwrap().module().func(0).to_string(),
+            "fn swap(i32, i32) -> (i32, i32)"
+        );
+        let cases = [
+            (
+                1,
+                vec![Value::I32(1), Value::I32(2)],
+                vec![Value::I32(2), Value::I32(1)],
+            ),
+            (2, vec![Value::I32(4)], vec![Value::I32(10)]),
+            (3, vec![Value::I32(1)], vec![Value::I32(11)]),
+            (3, vec![Value::I32(0)], vec![Value::I32(9)]),
+            (4, vec![], vec![Value::I32(1), Value::I32(2)]),
+            (5, vec![], vec![Value::I32(5), Value::I64(6)]),
+            (6, vec![], vec![Value::I32(3), Value::I32(4)]),
+        ];
+        for (func, args, results) in cases.iter() {
+            assert!(matches!(dbg.call(*func, args).unwrap(), Trap::ExecutionFinished));
+            assert_eq!(dbg.vm().unwrap().value_stack(), results.as_slice());
+        }
+    }
+
+    #[test]
+    fn failing_breakpoint_condition_pauses() {
+        let mut dbg = load_wat("(module (func (result i32) i32.const 1 i32.const 2 i32.add))");
+        let temporary = dbg
+            .add_temporary_breakpoint(Breakpoint::Code(CodePosition::new(0, 1)))
+            .unwrap();
+        let index = dbg.add_breakpoint(Breakpoint::Code(CodePosition::new(0, 1))).unwrap();
+        let condition = Expr::parse("local 5 == 0").unwrap();
+        dbg.set_breakpoint_condition(index, Some(condition)).unwrap();
+
+        match dbg.call(0, &[]).unwrap() {
+            Trap::BreakpointConditionFailed { index: failed, .. } => assert_eq!(failed, index),
+            trap => panic!("unexpected trap: {}", trap),
+        }
+        assert_eq!(dbg.vm().unwrap().ip(), CodePosition::new(0, 1));
+        {
+            // The breakpoint before the failing one at the same position is neither hit nor deleted
+            let breakpoints = dbg.breakpoints().unwrap();
+            assert!(breakpoints.is_temporary(temporary));
+            assert_eq!(breakpoints.hit_count(temporary), 0);
+            assert_eq!(breakpoints.hit_count(index), 0);
+        }
+        assert!(matches!(dbg.continue_execution().unwrap(), Trap::ExecutionFinished));
+    }
+
+    #[test]
+    fn breakpoints_at_the_same_position() {
+        let mut dbg = load_wat("(module (func i32.const 1 drop))");
+        let first = dbg.add_breakpoint(Breakpoint::Code(CodePosition::new(0, 1))).unwrap();
+        let temporary = dbg
+            .add_temporary_breakpoint(Breakpoint::Code(CodePosition::new(0, 1)))
+            .unwrap();
+
+        // Both are hit, but only the first one pauses execution
+        assert!(matches!(dbg.call(0, &[]).unwrap(), Trap::BreakpointReached(index) if index == first));
+        assert_eq!(dbg.take_breakpoint_hit(), Some(first));
+        {
+            let breakpoints = dbg.breakpoints().unwrap();
+            assert_eq!(breakpoints.len(), 2);
+            assert!(breakpoints.is_temporary(temporary));
+            assert_eq!(breakpoints.hit_count(first), 1);
+            assert_eq!(breakpoints.hit_count(temporary), 1);
+        }
+
+        dbg.set_breakpoint_ignore_count(first, 1).unwrap();
+        assert!(matches!(dbg.call(0, &[]).unwrap(), Trap::BreakpointReached(index) if index == temporary));
+        assert_eq!(dbg.take_breakpoint_hit(), Some(temporary));
+        let breakpoints = dbg.breakpoints().unwrap();
+        assert_eq!(breakpoints.len(), 1);
+        assert_eq!(breakpoints.hit_count(first), 2);
+    }
+
+    #[test]
+    fn reverse_execution_keeps_breakpoint_state() {
+        let mut dbg = load_wat("(module (func i32.const 1 drop i32.const 2 drop))");
+        dbg.set_recording(true);
+        assert!(dbg.start(Some(0)).unwrap().is_none());
+        assert!(matches!(dbg.continue_execution().unwrap(), Trap::ExecutionFinished));
+
+        let ignored = dbg.add_breakpoint(Breakpoint::Code(CodePosition::new(0, 1))).unwrap();
+        dbg.set_breakpoint_ignore_count(ignored, 1).unwrap();
+        let temporary = dbg
+            .add_temporary_breakpoint(Breakpoint::Code(CodePosition::new(0, 2)))
+            .unwrap();
+
+        assert!(matches!(dbg.reverse_continue().unwrap(), Trap::BreakpointReached(index) if index == temporary));
+        assert!(matches!(dbg.reverse_continue().unwrap(), Trap::NoMoreHistory));
+        {
+            let breakpoints = dbg.breakpoints().unwrap();
+            assert!(breakpoints.is_temporary(temporary));
+            assert_eq!(breakpoints.hit_count(temporary), 0);
+            assert_eq!(breakpoints.hit_count(ignored), 0);
+            assert_eq!(breakpoints.ignore_count(ignored), 1);
+        }
+
+        assert!(matches!(dbg.continue_execution().unwrap(), Trap::BreakpointReached(index) if index == temporary));
+        let breakpoints = dbg.breakpoints().unwrap();
+        assert_eq!(breakpoints.len(), 1);
+        assert_eq!(breakpoints.hit_count(ignored), 1);
+        assert_eq!(breakpoints.ignore_count(ignored), 0);
+    }
+
+    #[test]
+    fn rewinding_requires_stopping_the_trace() {
+        // The trace header is computed from the binary, so it has to stay on disk
+        let module = temp_path("rewind.wasm");
+        std::fs::write(&module, wat::parse_str("(module (func i32.const 1 drop))").unwrap()).unwrap();
+        let mut dbg = Debugger::new();
+        dbg.load_file(module.to_str().unwrap()).unwrap();
+        let path = temp_path("rewind.trace");
+        dbg.set_recording(true);
+        dbg.set_trace_output(Some(path.clone()));
+        assert!(dbg.start(Some(0)).unwrap().is_none());
+        let checkpoint = dbg.add_checkpoint().unwrap();
+        assert!(dbg.execute_step().unwrap().is_none());
+
+        assert!(matches!(
+            dbg.restore_checkpoint(checkpoint),
+            Err(DebuggerError::Trap(Trap::RewindWhileTracing))
+        ));
+        assert!(matches!(dbg.reverse_step().unwrap(), Some(Trap::RewindWhileTracing)));
+        assert!(dbg.vm().unwrap().is_recording_trace());
+        assert_eq!(dbg.vm().unwrap().ip(), CodePosition::new(0, 1));
+
+        dbg.set_trace_output(None);
+        assert!(dbg.reverse_step().unwrap().is_none());
+        dbg.restore_checkpoint(checkpoint).unwrap();
+        assert_eq!(dbg.vm().unwrap().ip(), CodePosition::new(0, 0));
+        std::fs::remove_file(path).unwrap();
+        std::fs::remove_file(module).unwrap();
+    }
+}
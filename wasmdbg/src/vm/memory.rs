@@ -1,29 +1,53 @@
-use bwasm::{ResizableLimits, PAGE_SIZE};
-
 use super::{eval_init_expr, InitError, Trap, VMResult};
 use crate::value::LittleEndianConvert;
+use crate::{Module, ResizableLimits, Value, PAGE_SIZE};
 
 pub const MEMORY_MAX_PAGES: u32 = 0x10000;
 
+// Undo information for a single change to a memory
+#[derive(Clone)]
+pub(crate) enum MemoryChange {
+    Write { address: u32, old_bytes: Vec<u8> },
+    Grow { old_len: usize },
+}
+
+#[derive(Clone)]
 pub struct Memory {
     data: Vec<u8>,
     limits: ResizableLimits,
+    journal: Option<Vec<MemoryChange>>,
 }
 
 impl Memory {
-    pub fn new(memory: &bwasm::Memory) -> Memory {
+    pub fn new(memory: &crate::Memory) -> Memory {
+        Memory::with_limits(memory.limits().initial(), memory.limits().maximum())
+    }
+
+    pub fn with_limits(initial: u32, maximum: Option<u32>) -> Memory {
         Memory {
-            data: vec![0; memory.limits().initial() as usize],
-            limits: *memory.limits(),
+            data: vec![0; initial as usize * PAGE_SIZE as usize],
+            limits: ResizableLimits::new(initial, maximum),
+            journal: None,
         }
     }
 
-    pub fn from_module(module: &bwasm::Module) -> Result<Vec<Memory>, InitError> {
-        let mut memories: Vec<_> = module.memories().iter().map(Memory::new).collect();
+    pub(crate) fn from_module(
+        module: &Module,
+        imported: Vec<Memory>,
+        globals: &[Value],
+    ) -> Result<Vec<Memory>, InitError> {
+        let mut memories = imported;
+        memories.extend(
+            module
+                .memories()
+                .iter()
+                .filter(|memory| !memory.is_imported())
+                .map(Memory::new),
+        );
 
         for init in module.memory_inits() {
             let memory = &mut memories[init.index() as usize];
-            let offset = eval_init_expr(init.offset())?;
+            let offset = eval_init_expr(init.offset(), globals)?;
             let offset = match offset.to::<u32>() {
                 Some(val) => val as usize,
                 None => return Err(InitError::OffsetInvalidType(offset.value_type())),
@@ -38,20 +62,27 @@ impl Memory {
         Ok(memories)
     }
 
+    pub const fn limits(&self) -> &ResizableLimits {
+        &self.limits
+    }
+
     pub fn page_count(&self) -> u32 {
-        (self.data.len() as u32 / PAGE_SIZE)
+        (self.data.len() / PAGE_SIZE as usize) as u32
     }
 
     pub fn grow(&mut self, delta: u32) -> i32 {
         let page_count = self.page_count();
-        if let Some(max) = self.limits.maximum() {
-            if page_count + delta > max {
-                return -1i32;
-            }
-        } else if page_count + delta > MEMORY_MAX_PAGES {
-            return -1i32;
+        let max = self.limits.maximum().unwrap_or(MEMORY_MAX_PAGES);
+        let new_page_count = match page_count.checked_add(delta) {
+            Some(new_page_count) if new_page_count <= max => new_page_count,
+            _ => return -1i32,
+        };
+        if let Some(journal) = &mut self.journal {
+            journal.push(MemoryChange::Grow {
+                old_len: self.data.len(),
+            });
         }
-        self.data.resize(((page_count + delta) * PAGE_SIZE) as usize, 0);
+        self.data.resize(new_page_count as usize * PAGE_SIZE as usize, 0);
         page_count as i32
     }
 
@@ -59,6 +90,31 @@ impl Memory {
         &self.data
     }
 
+    pub fn read(&self, address: u32, len: u32) -> VMResult<&[u8]> {
+        let start = address as usize;
+        let end = start + len as usize;
+        self.data
+            .get(start..end)
+            .ok_or(Trap::MemoryAccessOutOfRange(end as u32))
+    }
+
+    pub fn write(&mut self, address: u32, bytes: &[u8]) -> VMResult<()> {
+        let start = address as usize;
+        let end = start + bytes.len();
+        let data = self
+            .data
+            .get_mut(start..end)
+            .ok_or(Trap::MemoryAccessOutOfRange(end as u32))?;
+        if let Some(journal) = &mut self.journal {
+            journal.push(MemoryChange::Write {
+                address,
+                old_bytes: data.to_vec(),
+            });
+        }
+        data.copy_from_slice(bytes);
+        Ok(())
+    }
+
     pub fn load<T: LittleEndianConvert>(&self, address: u32) -> VMResult<T> {
         let size = core::mem::size_of::<T>();
         let address = address as usize;
@@ -76,7 +132,35 @@ impl Memory {
             .data
             .get_mut(address..address + size)
             .ok_or_else(|| Trap::MemoryAccessOutOfRange((address + size) as u32))?;
+        if let Some(journal) = &mut self.journal {
+            journal.push(MemoryChange::Write {
+                address: address as u32,
+                old_bytes: bytes.to_vec(),
+            });
+        }
         value.to_little_endian(bytes);
         Ok(())
     }
+
+    // While journaling, every write and grow is logged so that it can be undone later
+    pub(crate) fn set_journaling(&mut self, journaling: bool) {
+        self.journal = if journaling { Some(Vec::new()) } else { None };
+    }
+
+    pub(crate) fn take_journal(&mut self) -> Vec<MemoryChange> {
+        match &mut self.journal {
+            Some(journal) => std::mem::take(journal),
+            None => Vec::new(),
+        }
+    }
+
+    pub(crate) fn undo(&mut self, change: MemoryChange) {
+        match change {
+            MemoryChange::Write { address, old_bytes } => {
+                let start = address as usize;
+                self.data[start..start + old_bytes.len()].copy_from_slice(&old_bytes);
+            }
+            MemoryChange::Grow { old_len } => self.data.truncate(old_len),
+        }
+    }
 }
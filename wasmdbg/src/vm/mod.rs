@@ -1,20 +1,42 @@
-use bwasm::{InitExpr, ValueType};
 use thiserror::Error;
 
-use crate::Value;
+use crate::{InitExpr, Value, ValueType, F32, F64};
 
+mod history;
+mod imports;
 mod instance;
 mod memory;
 mod table;
 
+pub use history::*;
+pub use imports::*;
 pub use instance::*;
 pub use memory::*;
 pub use table::*;
 
 #[derive(Error, Clone, Debug)]
 pub enum InitError {
-    #[error("Initalizer contains global.get which requires imports (unimplemented)")]
-    GlobalGetUnimplemented,
+    #[error("Initializer references unknown global {0}")]
+    UnknownGlobal(u32),
+    #[error("No definition for imported {kind} \"{module}.{field}\"")]
+    MissingImport {
+        kind: &'static str,
+        module: String,
+        field: String,
+    },
+    #[error("Definition for imported {kind} \"{name}\" has limits {found} but the import requires {expected}")]
+    IncompatibleLimits {
+        kind: &'static str,
+        name: String,
+        expected: String,
+        found: String,
+    },
+    #[error("Definition for imported global \"{name}\" has type \"{found}\" but the import requires \"{expected}\"")]
+    ImportTypeMismatch {
+        name: String,
+        expected: ValueType,
+        found: ValueType,
+    },
     #[error("Initializer type mismatch. Expected \"{expected}\", found \"{found}\"")]
     MismatchedType { expected: ValueType, found: ValueType },
     #[error("Offset expr has invalid type. Expected \"i32\", found \"{0}\"")]
@@ -55,12 +77,30 @@ pub enum Trap {
     BreakpointReached(u32),
     #[error("Reached watchpoint {0}")]
     WatchpointReached(u32),
+    #[error("Error in condition of breakpoint {index}: {error}")]
+    BreakpointConditionFailed { index: u32, error: String },
+    #[error("Reached entry function {0}")]
+    EntryReached(u32),
+    #[error("No more reverse-execution history")]
+    NoMoreHistory,
+    #[error("Execution can't be rewound while a trace is being written")]
+    RewindWhileTracing,
+    #[error("Execution diverged from the replayed trace, which continues at {0}")]
+    ReplayDiverged(CodePosition),
+    #[error("Reached the end of the replayed trace")]
+    ReplayEnded,
+    #[error("Failed to write trace: {0}")]
+    TraceWriteFailed(String),
     #[error("Invalid branch index")]
     InvalidBranchIndex,
     #[error("Out of range memory access at address {0:#08x}")]
     MemoryAccessOutOfRange(u32),
     #[error("Tried to call unsupported imported function: {0}")]
     UnsupportedCallToImportedFunction(u32),
+    #[error("Host function returned {found} values but {expected} were expected")]
+    InvalidHostResultCount { expected: usize, found: usize },
+    #[error("Host function failed: {0}")]
+    HostError(String),
     #[error("Value stack overflow")]
     ValueStackOverflow,
     #[error("Label stack overflow")]
@@ -73,7 +113,7 @@ pub enum Trap {
 
 pub type VMResult<T> = Result<T, Trap>;
 
-#[derive(Default, Copy, Clone, PartialEq, Eq, Hash)]
+#[derive(Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
 pub struct CodePosition {
     pub func_index: u32,
     pub instr_index: u32,
@@ -94,13 +134,31 @@ impl std::fmt::Display for CodePosition {
     }
 }
 
-fn eval_init_expr(init_expr: &InitExpr) -> Result<Value, InitError> {
+fn eval_init_expr(init_expr: &InitExpr, globals: &[Value]) -> Result<Value, InitError> {
     let val = match init_expr {
         InitExpr::I32Const(val) => Value::from(*val),
         InitExpr::I64Const(val) => Value::from(*val),
-        InitExpr::F32Const(val) => Value::from(*val),
-        InitExpr::F64Const(val) => Value::from(*val),
-        InitExpr::Global(_) => return Err(InitError::GlobalGetUnimplemented),
+        InitExpr::F32Const(bits) => Value::F32(F32::from_bits(*bits)),
+        InitExpr::F64Const(bits) => Value::F64(F64::from_bits(*bits)),
+        InitExpr::Global(index) => *globals.get(*index as usize).ok_or(InitError::UnknownGlobal(*index))?,
     };
     Ok(val)
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::load_wat;
+    use crate::Value;
+
+    #[test]
+    fn float_globals() {
+        let mut dbg = load_wat(
+            r#"(module
+                (global f32 (f32.const 1.5))
+                (global f64 (f64.const -2.25))
+                (func))"#,
+        );
+        dbg.start(Some(0)).unwrap();
+        assert_eq!(dbg.globals().unwrap(), &[Value::from(1.5f32), Value::from(-2.25f64)]);
+    }
+}
@@ -1,39 +1,60 @@
-use bwasm;
-
 use super::{eval_init_expr, InitError};
+use crate::{Module, ResizableLimits, Value};
 
-#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
 pub enum TableElement {
+    #[default]
     Null,
     Func(u32),
 }
 
-impl Default for TableElement {
-    fn default() -> Self {
-        TableElement::Null
-    }
-}
-
+#[derive(Clone)]
 pub struct Table {
     elements: Vec<TableElement>,
+    limits: ResizableLimits,
 }
 
 impl Table {
-    pub fn new(table: &bwasm::Table) -> Self {
-        let elements = vec![TableElement::Null; table.limits().initial() as usize];
-        Table { elements }
+    pub fn new(table: &crate::Table) -> Self {
+        Table::with_limits(table.limits().initial(), table.limits().maximum())
+    }
+
+    pub fn with_limits(initial: u32, maximum: Option<u32>) -> Self {
+        Table {
+            elements: vec![TableElement::Null; initial as usize],
+            limits: ResizableLimits::new(initial, maximum),
+        }
+    }
+
+    pub const fn limits(&self) -> &ResizableLimits {
+        &self.limits
+    }
+
+    pub fn size(&self) -> u32 {
+        self.elements.len() as u32
     }
 
     pub fn get(&self, index: u32) -> TableElement {
         self.elements.get(index as usize).copied().unwrap_or_default()
     }
 
-    pub fn from_module(module: &bwasm::Module) -> Result<Vec<Table>, InitError> {
-        let mut tables: Vec<_> = module.tables().iter().map(Table::new).collect();
+    pub(crate) fn from_module(
+        module: &Module,
+        imported: Vec<Table>,
+        globals: &[Value],
+    ) -> Result<Vec<Table>, InitError> {
+        let mut tables = imported;
+        tables.extend(
+            module
+                .tables()
+                .iter()
+                .filter(|table| !table.is_imported())
+                .map(Table::new),
+        );
 
         for init in module.table_inits() {
             let table = &mut tables[init.index() as usize];
-            let offset = eval_init_expr(init.offset())?;
+            let offset = eval_init_expr(init.offset(), globals)?;
             let offset = match offset.to::<i32>() {
                 Some(val) => val as usize,
                 None => return Err(InitError::OffsetInvalidType(offset.value_type())),
@@ -0,0 +1,220 @@
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::io::{self, Read, SeekFrom};
+use std::rc::Rc;
+
+use super::fs::Filesystem;
+use super::output::{Output, OutputFile, OutputStream};
+use super::types::*;
+
+pub trait WasiFile {
+    fn filetype(&self) -> Filetype;
+
+    fn read(&mut self, _buf: &mut [u8]) -> Result<usize, Errno> {
+        Err(Errno::Badf)
+    }
+
+    fn write(&mut self, _buf: &[u8]) -> Result<usize, Errno> {
+        Err(Errno::Badf)
+    }
+
+    fn seek(&mut self, _pos: SeekFrom) -> Result<u64, Errno> {
+        Err(Errno::Spipe)
+    }
+
+    fn read_at(&mut self, _buf: &mut [u8], _offset: u64) -> Result<usize, Errno> {
+        Err(Errno::Spipe)
+    }
+
+    fn write_at(&mut self, _buf: &[u8], _offset: u64) -> Result<usize, Errno> {
+        Err(Errno::Spipe)
+    }
+
+    fn tell(&self) -> Option<u64> {
+        None
+    }
+
+    fn filestat(&self) -> Result<Filestat, Errno> {
+        Ok(Filestat {
+            filetype: Some(self.filetype()),
+            nlink: 1,
+            ..Filestat::default()
+        })
+    }
+
+    fn set_size(&mut self, _size: u64) -> Result<(), Errno> {
+        Err(Errno::Inval)
+    }
+
+    fn set_times(&mut self, _atim: Option<u64>, _mtim: Option<u64>) -> Result<(), Errno> {
+        Err(Errno::Notsup)
+    }
+
+    fn sync(&mut self) -> Result<(), Errno> {
+        Ok(())
+    }
+}
+
+pub struct Stdin;
+
+impl WasiFile for Stdin {
+    fn filetype(&self) -> Filetype {
+        Filetype::CharacterDevice
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Errno> {
+        io::stdin().read(buf).map_err(|_| Errno::Io)
+    }
+}
+
+pub struct Directory {
+    pub fs: Rc<dyn Filesystem>,
+    pub root: String,
+    pub path: Vec<String>,
+}
+
+impl Directory {
+    pub fn guest_path(&self) -> String {
+        if self.path.is_empty() {
+            self.root.clone()
+        } else {
+            format!("{}/{}", self.root.trim_end_matches('/'), self.path.join("/"))
+        }
+    }
+}
+
+pub enum Handle {
+    File(Box<dyn WasiFile>),
+    Dir(Directory),
+}
+
+pub struct FileDescriptor {
+    pub handle: Handle,
+    pub name: String,
+    pub preopen: Option<String>,
+    pub rights_base: u64,
+    pub rights_inheriting: u64,
+    pub flags: u16,
+}
+
+impl FileDescriptor {
+    pub fn new(handle: Handle, name: String, rights_base: u64, rights_inheriting: u64) -> Self {
+        FileDescriptor {
+            handle,
+            name,
+            preopen: None,
+            rights_base,
+            rights_inheriting,
+            flags: 0,
+        }
+    }
+
+    pub fn check_rights(&self, rights: u64) -> Result<(), Errno> {
+        if self.rights_base & rights == rights {
+            Ok(())
+        } else {
+            Err(Errno::Notcapable)
+        }
+    }
+
+    pub fn file_mut(&mut self) -> Result<&mut dyn WasiFile, Errno> {
+        match self.handle {
+            Handle::File(ref mut file) => Ok(file.as_mut()),
+            Handle::Dir(_) => Err(Errno::Isdir),
+        }
+    }
+
+    pub fn dir(&self) -> Result<&Directory, Errno> {
+        match self.handle {
+            Handle::Dir(ref dir) => Ok(dir),
+            Handle::File(_) => Err(Errno::Notdir),
+        }
+    }
+
+    pub fn filetype(&self) -> Filetype {
+        match self.handle {
+            Handle::File(ref file) => file.filetype(),
+            Handle::Dir(_) => Filetype::Directory,
+        }
+    }
+
+    pub fn filestat(&self) -> Result<Filestat, Errno> {
+        match self.handle {
+            Handle::File(ref file) => file.filestat(),
+            Handle::Dir(ref dir) => dir.fs.filestat(&dir.path, true),
+        }
+    }
+
+    pub fn set_times(&mut self, atim: Option<u64>, mtim: Option<u64>) -> Result<(), Errno> {
+        match self.handle {
+            Handle::File(ref mut file) => file.set_times(atim, mtim),
+            Handle::Dir(ref dir) => dir.fs.set_times(&dir.path, true, atim, mtim),
+        }
+    }
+
+    pub fn offset(&self) -> Option<u64> {
+        match self.handle {
+            Handle::File(ref file) => file.tell(),
+            Handle::Dir(_) => None,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct FdTable {
+    fds: BTreeMap<u32, FileDescriptor>,
+}
+
+impl FdTable {
+    pub fn new() -> Self {
+        FdTable::default()
+    }
+
+    pub fn with_stdio(output: &Rc<RefCell<Output>>) -> Self {
+        let mut fds = FdTable::new();
+        let stdin = Handle::File(Box::new(Stdin));
+        let stdout = Handle::File(Box::new(OutputFile::new(OutputStream::Stdout, Rc::clone(output))));
+        let stderr = Handle::File(Box::new(OutputFile::new(OutputStream::Stderr, Rc::clone(output))));
+        fds.insert_at(0, FileDescriptor::new(stdin, "<stdin>".to_owned(), RIGHTS_STDIN, 0));
+        fds.insert_at(1, FileDescriptor::new(stdout, "<stdout>".to_owned(), RIGHTS_STDOUT, 0));
+        fds.insert_at(2, FileDescriptor::new(stderr, "<stderr>".to_owned(), RIGHTS_STDOUT, 0));
+        fds
+    }
+
+    pub fn get(&self, fd: u32) -> Result<&FileDescriptor, Errno> {
+        self.fds.get(&fd).ok_or(Errno::Badf)
+    }
+
+    pub fn get_mut(&mut self, fd: u32) -> Result<&mut FileDescriptor, Errno> {
+        self.fds.get_mut(&fd).ok_or(Errno::Badf)
+    }
+
+    pub fn insert(&mut self, entry: FileDescriptor) -> u32 {
+        let mut fd = 0;
+        while self.fds.contains_key(&fd) {
+            fd += 1;
+        }
+        self.fds.insert(fd, entry);
+        fd
+    }
+
+    pub fn insert_at(&mut self, fd: u32, entry: FileDescriptor) -> Option<FileDescriptor> {
+        self.fds.insert(fd, entry)
+    }
+
+    pub fn remove(&mut self, fd: u32) -> Result<FileDescriptor, Errno> {
+        self.fds.remove(&fd).ok_or(Errno::Badf)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fds.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.fds.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &FileDescriptor)> {
+        self.fds.iter().map(|(fd, entry)| (*fd, entry))
+    }
+}
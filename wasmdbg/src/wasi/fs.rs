@@ -0,0 +1,840 @@
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::fd::WasiFile;
+use super::types::*;
+
+pub struct DirEntry {
+    pub name: String,
+    pub filetype: Filetype,
+    pub ino: u64,
+}
+
+#[derive(Clone, Copy, Default, Debug)]
+pub struct OpenOptions {
+    pub read: bool,
+    pub write: bool,
+    pub create: bool,
+    pub excl: bool,
+    pub truncate: bool,
+}
+
+// Paths passed to a `Filesystem` are already resolved by `resolve_path` and
+// given as components relative to the root of that filesystem.
+pub trait Filesystem {
+    fn describe(&self) -> String;
+    fn open(&self, path: &[String], options: OpenOptions) -> Result<Box<dyn WasiFile>, Errno>;
+    fn filestat(&self, path: &[String], follow: bool) -> Result<Filestat, Errno>;
+    fn set_times(&self, path: &[String], follow: bool, atim: Option<u64>, mtim: Option<u64>) -> Result<(), Errno>;
+    fn read_dir(&self, path: &[String]) -> Result<Vec<DirEntry>, Errno>;
+    fn create_dir(&self, path: &[String]) -> Result<(), Errno>;
+    fn remove_dir(&self, path: &[String]) -> Result<(), Errno>;
+    fn unlink_file(&self, path: &[String]) -> Result<(), Errno>;
+    fn rename(&self, from: &[String], to: &[String]) -> Result<(), Errno>;
+
+    fn link(&self, _from: &[String], _to: &[String]) -> Result<(), Errno> {
+        Err(Errno::Notsup)
+    }
+
+    fn symlink(&self, _target: &str, _path: &[String]) -> Result<(), Errno> {
+        Err(Errno::Notsup)
+    }
+
+    fn readlink(&self, _path: &[String]) -> Result<String, Errno> {
+        Err(Errno::Inval)
+    }
+}
+
+// Absolute paths and paths leaving the root of the filesystem are rejected.
+pub fn resolve_path(base: &[String], path: &str) -> Result<Vec<String>, Errno> {
+    if path.is_empty() {
+        return Err(Errno::Noent);
+    }
+    if path.starts_with('/') {
+        return Err(Errno::Notcapable);
+    }
+    let mut result = base.to_vec();
+    for component in path.split('/') {
+        match component {
+            "" | "." => (),
+            ".." => {
+                result.pop().ok_or(Errno::Notcapable)?;
+            }
+            name => result.push(name.to_owned()),
+        }
+    }
+    Ok(result)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_nanos() as u64)
+}
+
+fn io_errno(error: io::Error) -> Errno {
+    match error.kind() {
+        io::ErrorKind::NotFound => Errno::Noent,
+        io::ErrorKind::PermissionDenied => Errno::Acces,
+        io::ErrorKind::AlreadyExists => Errno::Exist,
+        io::ErrorKind::InvalidInput => Errno::Inval,
+        io::ErrorKind::NotADirectory => Errno::Notdir,
+        io::ErrorKind::IsADirectory => Errno::Isdir,
+        io::ErrorKind::DirectoryNotEmpty => Errno::Notempty,
+        io::ErrorKind::ReadOnlyFilesystem => Errno::Rofs,
+        io::ErrorKind::StorageFull => Errno::Nospc,
+        io::ErrorKind::CrossesDevices => Errno::Xdev,
+        _ => Errno::Io,
+    }
+}
+
+fn system_time_nanos(time: io::Result<SystemTime>) -> u64 {
+    time.ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |time| time.as_nanos() as u64)
+}
+
+fn host_filetype(file_type: fs::FileType) -> Filetype {
+    if file_type.is_dir() {
+        Filetype::Directory
+    } else if file_type.is_file() {
+        Filetype::RegularFile
+    } else if file_type.is_symlink() {
+        Filetype::SymbolicLink
+    } else {
+        Filetype::Unknown
+    }
+}
+
+fn host_filestat(metadata: &fs::Metadata) -> Filestat {
+    let mut filestat = Filestat {
+        filetype: Some(host_filetype(metadata.file_type())),
+        nlink: 1,
+        size: metadata.len(),
+        atim: system_time_nanos(metadata.accessed()),
+        mtim: system_time_nanos(metadata.modified()),
+        ctim: system_time_nanos(metadata.modified()),
+        ..Filestat::default()
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        filestat.dev = metadata.dev();
+        filestat.ino = metadata.ino();
+        filestat.nlink = metadata.nlink();
+        filestat.ctim = (metadata.ctime() as u64) * 1_000_000_000 + metadata.ctime_nsec() as u64;
+    }
+    filestat
+}
+
+fn host_set_times(file: &fs::File, atim: Option<u64>, mtim: Option<u64>) -> Result<(), Errno> {
+    let mut times = fs::FileTimes::new();
+    if let Some(atim) = atim {
+        times = times.set_accessed(UNIX_EPOCH + Duration::from_nanos(atim));
+    }
+    if let Some(mtim) = mtim {
+        times = times.set_modified(UNIX_EPOCH + Duration::from_nanos(mtim));
+    }
+    file.set_times(times).map_err(io_errno)
+}
+
+pub struct HostFs {
+    root: PathBuf,
+}
+
+impl HostFs {
+    pub fn new<P: AsRef<Path>>(root: P) -> io::Result<Self> {
+        let root = fs::canonicalize(root)?;
+        if !root.is_dir() {
+            return Err(io::Error::new(io::ErrorKind::NotADirectory, "Not a directory"));
+        }
+        Ok(HostFs { root })
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn check_inside(&self, path: &Path) -> Result<(), Errno> {
+        if path.starts_with(&self.root) {
+            Ok(())
+        } else {
+            Err(Errno::Notcapable)
+        }
+    }
+
+    // Symlinks on the host could point outside of the root directory, so the
+    // parent directory (and the target itself when following links) is
+    // canonicalized and checked against the root.
+    fn host_path(&self, path: &[String], follow: bool) -> Result<PathBuf, Errno> {
+        let (name, parent) = match path.split_last() {
+            Some(split) => split,
+            None => return Ok(self.root.clone()),
+        };
+        let parent = fs::canonicalize(self.root.join(parent.join("/"))).map_err(io_errno)?;
+        self.check_inside(&parent)?;
+        let target = parent.join(name);
+        if follow {
+            if let Ok(metadata) = fs::symlink_metadata(&target) {
+                if metadata.file_type().is_symlink() {
+                    let resolved = fs::canonicalize(&target).map_err(io_errno)?;
+                    self.check_inside(&resolved)?;
+                    return Ok(resolved);
+                }
+            }
+        }
+        Ok(target)
+    }
+}
+
+impl Filesystem for HostFs {
+    fn describe(&self) -> String {
+        self.root.display().to_string()
+    }
+
+    fn open(&self, path: &[String], options: OpenOptions) -> Result<Box<dyn WasiFile>, Errno> {
+        let host_path = self.host_path(path, true)?;
+        let file = fs::OpenOptions::new()
+            .read(options.read || !options.write)
+            .write(options.write)
+            .create(options.create && !options.excl)
+            .create_new(options.create && options.excl)
+            .truncate(options.truncate)
+            .open(host_path)
+            .map_err(io_errno)?;
+        Ok(Box::new(HostFile::new(file)))
+    }
+
+    fn filestat(&self, path: &[String], follow: bool) -> Result<Filestat, Errno> {
+        let host_path = self.host_path(path, follow)?;
+        let metadata = fs::symlink_metadata(host_path).map_err(io_errno)?;
+        Ok(host_filestat(&metadata))
+    }
+
+    fn set_times(&self, path: &[String], follow: bool, atim: Option<u64>, mtim: Option<u64>) -> Result<(), Errno> {
+        let host_path = self.host_path(path, follow)?;
+        // Opening a symlink follows it, and std can't set the times of the link itself
+        if !follow
+            && fs::symlink_metadata(&host_path)
+                .map_err(io_errno)?
+                .file_type()
+                .is_symlink()
+        {
+            return Err(Errno::Notsup);
+        }
+        let file = fs::File::open(host_path).map_err(io_errno)?;
+        host_set_times(&file, atim, mtim)
+    }
+
+    fn read_dir(&self, path: &[String]) -> Result<Vec<DirEntry>, Errno> {
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(self.host_path(path, true)?).map_err(io_errno)? {
+            let entry = entry.map_err(io_errno)?;
+            let file_type = entry.file_type().map_err(io_errno)?;
+            #[cfg(unix)]
+            let ino = std::os::unix::fs::DirEntryExt::ino(&entry);
+            #[cfg(not(unix))]
+            let ino = 0;
+            entries.push(DirEntry {
+                name: entry.file_name().to_string_lossy().into_owned(),
+                filetype: host_filetype(file_type),
+                ino,
+            });
+        }
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(entries)
+    }
+
+    fn create_dir(&self, path: &[String]) -> Result<(), Errno> {
+        fs::create_dir(self.host_path(path, false)?).map_err(io_errno)
+    }
+
+    fn remove_dir(&self, path: &[String]) -> Result<(), Errno> {
+        if path.is_empty() {
+            return Err(Errno::Busy);
+        }
+        fs::remove_dir(self.host_path(path, false)?).map_err(io_errno)
+    }
+
+    fn unlink_file(&self, path: &[String]) -> Result<(), Errno> {
+        let host_path = self.host_path(path, false)?;
+        if fs::symlink_metadata(&host_path).map_err(io_errno)?.is_dir() {
+            return Err(Errno::Isdir);
+        }
+        fs::remove_file(host_path).map_err(io_errno)
+    }
+
+    fn rename(&self, from: &[String], to: &[String]) -> Result<(), Errno> {
+        if from.is_empty() || to.is_empty() {
+            return Err(Errno::Busy);
+        }
+        fs::rename(self.host_path(from, false)?, self.host_path(to, false)?).map_err(io_errno)
+    }
+
+    fn link(&self, from: &[String], to: &[String]) -> Result<(), Errno> {
+        fs::hard_link(self.host_path(from, false)?, self.host_path(to, false)?).map_err(io_errno)
+    }
+
+    fn symlink(&self, target: &str, path: &[String]) -> Result<(), Errno> {
+        // The link target is interpreted relative to the directory containing the link
+        // and must stay inside of the sandbox as well.
+        let (_, parent) = path.split_last().ok_or(Errno::Exist)?;
+        resolve_path(parent, target)?;
+        let link_path = self.host_path(path, false)?;
+        #[cfg(unix)]
+        return std::os::unix::fs::symlink(target, link_path).map_err(io_errno);
+        #[cfg(not(unix))]
+        return Err(Errno::Notsup);
+    }
+
+    fn readlink(&self, path: &[String]) -> Result<String, Errno> {
+        let target = fs::read_link(self.host_path(path, false)?).map_err(io_errno)?;
+        Ok(target.to_string_lossy().into_owned())
+    }
+}
+
+pub struct HostFile {
+    file: fs::File,
+}
+
+impl HostFile {
+    pub fn new(file: fs::File) -> Self {
+        HostFile { file }
+    }
+}
+
+impl WasiFile for HostFile {
+    fn filetype(&self) -> Filetype {
+        Filetype::RegularFile
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Errno> {
+        self.file.read(buf).map_err(io_errno)
+    }
+
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Errno> {
+        self.file.write(buf).map_err(io_errno)
+    }
+
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Errno> {
+        self.file.seek(pos).map_err(io_errno)
+    }
+
+    fn read_at(&mut self, buf: &mut [u8], offset: u64) -> Result<usize, Errno> {
+        let pos = self.seek(SeekFrom::Current(0))?;
+        self.seek(SeekFrom::Start(offset))?;
+        let result = self.read(buf);
+        self.seek(SeekFrom::Start(pos))?;
+        result
+    }
+
+    fn write_at(&mut self, buf: &[u8], offset: u64) -> Result<usize, Errno> {
+        let pos = self.seek(SeekFrom::Current(0))?;
+        self.seek(SeekFrom::Start(offset))?;
+        let result = self.write(buf);
+        self.seek(SeekFrom::Start(pos))?;
+        result
+    }
+
+    fn tell(&self) -> Option<u64> {
+        (&self.file).stream_position().ok()
+    }
+
+    fn filestat(&self) -> Result<Filestat, Errno> {
+        Ok(host_filestat(&self.file.metadata().map_err(io_errno)?))
+    }
+
+    fn set_size(&mut self, size: u64) -> Result<(), Errno> {
+        self.file.set_len(size).map_err(io_errno)
+    }
+
+    fn set_times(&mut self, atim: Option<u64>, mtim: Option<u64>) -> Result<(), Errno> {
+        host_set_times(&self.file, atim, mtim)
+    }
+
+    fn sync(&mut self) -> Result<(), Errno> {
+        self.file.sync_all().map_err(io_errno)
+    }
+}
+
+struct MemoryFileData {
+    stat: Filestat,
+    data: Vec<u8>,
+}
+
+struct MemoryDir {
+    stat: Filestat,
+    entries: BTreeMap<String, MemoryNode>,
+}
+
+#[derive(Clone)]
+enum MemoryNode {
+    File(Rc<RefCell<MemoryFileData>>),
+    Dir(Rc<RefCell<MemoryDir>>),
+}
+
+impl MemoryNode {
+    fn filestat(&self) -> Filestat {
+        match self {
+            MemoryNode::File(file) => {
+                let file = file.borrow();
+                Filestat {
+                    size: file.data.len() as u64,
+                    ..file.stat
+                }
+            }
+            MemoryNode::Dir(dir) => dir.borrow().stat,
+        }
+    }
+
+    fn as_dir(&self) -> Result<&Rc<RefCell<MemoryDir>>, Errno> {
+        match self {
+            MemoryNode::Dir(dir) => Ok(dir),
+            MemoryNode::File(_) => Err(Errno::Notdir),
+        }
+    }
+}
+
+pub struct MemoryFs {
+    root: Rc<RefCell<MemoryDir>>,
+    next_ino: Cell<u64>,
+}
+
+impl Default for MemoryFs {
+    fn default() -> Self {
+        MemoryFs::new()
+    }
+}
+
+impl MemoryFs {
+    pub fn new() -> Self {
+        let fs = MemoryFs {
+            root: Rc::new(RefCell::new(MemoryDir {
+                stat: Filestat::default(),
+                entries: BTreeMap::new(),
+            })),
+            next_ino: Cell::new(1),
+        };
+        fs.root.borrow_mut().stat = fs.new_stat(Filetype::Directory);
+        fs
+    }
+
+    pub fn add_file(&self, path: &str, contents: &[u8]) -> Result<(), Errno> {
+        let path = resolve_path(&[], path.trim_start_matches('/'))?;
+        if let Some((_, parent)) = path.split_last() {
+            self.add_dirs(parent)?;
+        }
+        let mut file = self.open(
+            &path,
+            OpenOptions {
+                write: true,
+                create: true,
+                truncate: true,
+                ..OpenOptions::default()
+            },
+        )?;
+        file.write(contents)?;
+        Ok(())
+    }
+
+    pub fn add_dir(&self, path: &str) -> Result<(), Errno> {
+        self.add_dirs(&resolve_path(&[], path.trim_start_matches('/'))?)
+    }
+
+    pub fn read_file(&self, path: &str) -> Result<Vec<u8>, Errno> {
+        match self.lookup(&resolve_path(&[], path.trim_start_matches('/'))?)? {
+            MemoryNode::File(file) => Ok(file.borrow().data.clone()),
+            MemoryNode::Dir(_) => Err(Errno::Isdir),
+        }
+    }
+
+    fn add_dirs(&self, path: &[String]) -> Result<(), Errno> {
+        for i in 1..=path.len() {
+            match self.create_dir(&path[..i]) {
+                Ok(()) | Err(Errno::Exist) => (),
+                Err(errno) => return Err(errno),
+            }
+        }
+        Ok(())
+    }
+
+    fn new_stat(&self, filetype: Filetype) -> Filestat {
+        let ino = self.next_ino.get();
+        self.next_ino.set(ino + 1);
+        let time = now();
+        Filestat {
+            ino,
+            filetype: Some(filetype),
+            nlink: 1,
+            atim: time,
+            mtim: time,
+            ctim: time,
+            ..Filestat::default()
+        }
+    }
+
+    fn lookup(&self, path: &[String]) -> Result<MemoryNode, Errno> {
+        let mut node = MemoryNode::Dir(Rc::clone(&self.root));
+        for name in path {
+            let next = node.as_dir()?.borrow().entries.get(name).cloned();
+            node = next.ok_or(Errno::Noent)?;
+        }
+        Ok(node)
+    }
+
+    fn parent<'a>(&self, path: &'a [String]) -> Result<(Rc<RefCell<MemoryDir>>, &'a String), Errno> {
+        let (name, parent) = path.split_last().ok_or(Errno::Busy)?;
+        let parent = Rc::clone(self.lookup(parent)?.as_dir()?);
+        Ok((parent, name))
+    }
+}
+
+impl Filesystem for MemoryFs {
+    fn describe(&self) -> String {
+        String::from("<memory>")
+    }
+
+    fn open(&self, path: &[String], options: OpenOptions) -> Result<Box<dyn WasiFile>, Errno> {
+        let (parent, name) = self.parent(path)?;
+        let existing = parent.borrow().entries.get(name).cloned();
+        let data = match existing {
+            Some(_) if options.create && options.excl => return Err(Errno::Exist),
+            Some(MemoryNode::Dir(_)) => return Err(Errno::Isdir),
+            Some(MemoryNode::File(data)) => data,
+            None if options.create => {
+                let data = Rc::new(RefCell::new(MemoryFileData {
+                    stat: self.new_stat(Filetype::RegularFile),
+                    data: Vec::new(),
+                }));
+                parent
+                    .borrow_mut()
+                    .entries
+                    .insert(name.clone(), MemoryNode::File(Rc::clone(&data)));
+                data
+            }
+            None => return Err(Errno::Noent),
+        };
+        if options.truncate {
+            data.borrow_mut().data.clear();
+        }
+        Ok(Box::new(MemoryFile {
+            data,
+            offset: 0,
+            readable: options.read || !options.write,
+            writable: options.write,
+        }))
+    }
+
+    fn filestat(&self, path: &[String], _follow: bool) -> Result<Filestat, Errno> {
+        Ok(self.lookup(path)?.filestat())
+    }
+
+    fn set_times(&self, path: &[String], _follow: bool, atim: Option<u64>, mtim: Option<u64>) -> Result<(), Errno> {
+        match self.lookup(path)? {
+            MemoryNode::File(file) => update_times(&mut file.borrow_mut().stat, atim, mtim),
+            MemoryNode::Dir(dir) => update_times(&mut dir.borrow_mut().stat, atim, mtim),
+        }
+        Ok(())
+    }
+
+    fn read_dir(&self, path: &[String]) -> Result<Vec<DirEntry>, Errno> {
+        let node = self.lookup(path)?;
+        let dir = node.as_dir()?.borrow();
+        Ok(dir
+            .entries
+            .iter()
+            .map(|(name, node)| {
+                let stat = node.filestat();
+                DirEntry {
+                    name: name.clone(),
+                    filetype: stat.filetype.unwrap_or(Filetype::Unknown),
+                    ino: stat.ino,
+                }
+            })
+            .collect())
+    }
+
+    fn create_dir(&self, path: &[String]) -> Result<(), Errno> {
+        let (parent, name) = self.parent(path).map_err(|errno| match errno {
+            Errno::Busy => Errno::Exist,
+            errno => errno,
+        })?;
+        let mut parent = parent.borrow_mut();
+        if parent.entries.contains_key(name) {
+            return Err(Errno::Exist);
+        }
+        let dir = MemoryDir {
+            stat: self.new_stat(Filetype::Directory),
+            entries: BTreeMap::new(),
+        };
+        parent
+            .entries
+            .insert(name.clone(), MemoryNode::Dir(Rc::new(RefCell::new(dir))));
+        Ok(())
+    }
+
+    fn remove_dir(&self, path: &[String]) -> Result<(), Errno> {
+        let (parent, name) = self.parent(path)?;
+        let mut parent = parent.borrow_mut();
+        match parent.entries.get(name).ok_or(Errno::Noent)? {
+            MemoryNode::File(_) => return Err(Errno::Notdir),
+            MemoryNode::Dir(dir) if !dir.borrow().entries.is_empty() => return Err(Errno::Notempty),
+            MemoryNode::Dir(_) => (),
+        }
+        parent.entries.remove(name);
+        Ok(())
+    }
+
+    fn unlink_file(&self, path: &[String]) -> Result<(), Errno> {
+        let (parent, name) = self.parent(path)?;
+        let mut parent = parent.borrow_mut();
+        match parent.entries.get(name).ok_or(Errno::Noent)? {
+            MemoryNode::File(file) => file.borrow_mut().stat.nlink -= 1,
+            MemoryNode::Dir(_) => return Err(Errno::Isdir),
+        }
+        parent.entries.remove(name);
+        Ok(())
+    }
+
+    fn rename(&self, from: &[String], to: &[String]) -> Result<(), Errno> {
+        if to.starts_with(from) && to.len() > from.len() {
+            return Err(Errno::Inval);
+        }
+        let (from_parent, from_name) = self.parent(from)?;
+        let (to_parent, to_name) = self.parent(to)?;
+        let node = from_parent
+            .borrow()
+            .entries
+            .get(from_name)
+            .cloned()
+            .ok_or(Errno::Noent)?;
+        let existing = to_parent.borrow().entries.get(to_name).cloned();
+        match (&node, existing) {
+            (MemoryNode::Dir(_), Some(MemoryNode::File(_))) => return Err(Errno::Notdir),
+            (MemoryNode::File(_), Some(MemoryNode::Dir(_))) => return Err(Errno::Isdir),
+            (MemoryNode::Dir(_), Some(MemoryNode::Dir(dir))) if !dir.borrow().entries.is_empty() => {
+                return Err(Errno::Notempty)
+            }
+            _ => (),
+        }
+        from_parent.borrow_mut().entries.remove(from_name);
+        to_parent.borrow_mut().entries.insert(to_name.clone(), node);
+        Ok(())
+    }
+
+    fn link(&self, from: &[String], to: &[String]) -> Result<(), Errno> {
+        let file = match self.lookup(from)? {
+            MemoryNode::File(file) => file,
+            MemoryNode::Dir(_) => return Err(Errno::Perm),
+        };
+        let (to_parent, to_name) = self.parent(to)?;
+        let mut to_parent = to_parent.borrow_mut();
+        if to_parent.entries.contains_key(to_name) {
+            return Err(Errno::Exist);
+        }
+        file.borrow_mut().stat.nlink += 1;
+        to_parent.entries.insert(to_name.clone(), MemoryNode::File(file));
+        Ok(())
+    }
+}
+
+// Memory files are kept in host memory, so guests can't grow them without bound
+const MEMORY_FILE_MAX_SIZE: u64 = 1 << 30;
+
+pub struct MemoryFile {
+    data: Rc<RefCell<MemoryFileData>>,
+    offset: u64,
+    readable: bool,
+    writable: bool,
+}
+
+impl WasiFile for MemoryFile {
+    fn filetype(&self) -> Filetype {
+        Filetype::RegularFile
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Errno> {
+        let nread = self.read_at(buf, self.offset)?;
+        self.offset += nread as u64;
+        Ok(nread)
+    }
+
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Errno> {
+        let nwritten = self.write_at(buf, self.offset)?;
+        self.offset += nwritten as u64;
+        Ok(nwritten)
+    }
+
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Errno> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(offset) => {
+                self.offset = offset;
+                return Ok(offset);
+            }
+            SeekFrom::Current(offset) => (self.offset, offset),
+            SeekFrom::End(offset) => (self.data.borrow().data.len() as u64, offset),
+        };
+        let new_offset = (base as i64).checked_add(offset).ok_or(Errno::Overflow)?;
+        if new_offset < 0 {
+            return Err(Errno::Inval);
+        }
+        self.offset = new_offset as u64;
+        Ok(self.offset)
+    }
+
+    fn read_at(&mut self, buf: &mut [u8], offset: u64) -> Result<usize, Errno> {
+        if !self.readable {
+            return Err(Errno::Badf);
+        }
+        let mut file = self.data.borrow_mut();
+        file.stat.atim = now();
+        let start = (offset as usize).min(file.data.len());
+        let len = buf.len().min(file.data.len() - start);
+        buf[..len].copy_from_slice(&file.data[start..start + len]);
+        Ok(len)
+    }
+
+    fn write_at(&mut self, buf: &[u8], offset: u64) -> Result<usize, Errno> {
+        if !self.writable {
+            return Err(Errno::Badf);
+        }
+        let end = offset
+            .checked_add(buf.len() as u64)
+            .filter(|&end| end <= MEMORY_FILE_MAX_SIZE)
+            .ok_or(Errno::Fbig)?;
+        let (start, end) = (offset as usize, end as usize);
+        let mut file = self.data.borrow_mut();
+        file.stat.mtim = now();
+        if file.data.len() < end {
+            file.data.resize(end, 0);
+        }
+        file.data[start..end].copy_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn tell(&self) -> Option<u64> {
+        Some(self.offset)
+    }
+
+    fn filestat(&self) -> Result<Filestat, Errno> {
+        Ok(MemoryNode::File(Rc::clone(&self.data)).filestat())
+    }
+
+    fn set_size(&mut self, size: u64) -> Result<(), Errno> {
+        if !self.writable {
+            return Err(Errno::Badf);
+        }
+        if size > MEMORY_FILE_MAX_SIZE {
+            return Err(Errno::Fbig);
+        }
+        let mut file = self.data.borrow_mut();
+        file.data.resize(size as usize, 0);
+        file.stat.mtim = now();
+        Ok(())
+    }
+
+    fn set_times(&mut self, atim: Option<u64>, mtim: Option<u64>) -> Result<(), Errno> {
+        update_times(&mut self.data.borrow_mut().stat, atim, mtim);
+        Ok(())
+    }
+}
+
+fn update_times(stat: &mut Filestat, atim: Option<u64>, mtim: Option<u64>) {
+    if let Some(atim) = atim {
+        stat.atim = atim;
+    }
+    if let Some(mtim) = mtim {
+        stat.mtim = mtim;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::time::UNIX_EPOCH;
+
+    use super::{resolve_path, Errno, Filesystem, HostFs, MemoryFs, OpenOptions, MEMORY_FILE_MAX_SIZE};
+    use crate::test_utils::temp_path;
+
+    fn components(path: &[&str]) -> Vec<String> {
+        path.iter().map(|component| component.to_string()).collect()
+    }
+
+    #[test]
+    fn resolve_path_stays_inside_root() {
+        assert_eq!(resolve_path(&[], "a/./b/../c"), Ok(components(&["a", "c"])));
+        assert_eq!(resolve_path(&components(&["a"]), "../b"), Ok(components(&["b"])));
+        assert_eq!(resolve_path(&[], ".."), Err(Errno::Notcapable));
+        assert_eq!(resolve_path(&[], "a/../../b"), Err(Errno::Notcapable));
+        assert_eq!(resolve_path(&components(&["a"]), "../../b"), Err(Errno::Notcapable));
+        assert_eq!(resolve_path(&[], "/etc/passwd"), Err(Errno::Notcapable));
+        assert_eq!(resolve_path(&[], ""), Err(Errno::Noent));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn host_fs_rejects_symlinks_leaving_root() {
+        use std::os::unix::fs::symlink;
+
+        let base = temp_path("fs");
+        let root = base.join("root");
+        let outside = base.join("outside");
+        fs::create_dir_all(&root).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        fs::write(root.join("file"), b"inside").unwrap();
+        fs::write(outside.join("secret"), b"outside").unwrap();
+        symlink(outside.join("secret"), root.join("secret_link")).unwrap();
+        symlink(&outside, root.join("outside_dir")).unwrap();
+        symlink("file", root.join("file_link")).unwrap();
+
+        let host_fs = HostFs::new(&root).unwrap();
+        let read = OpenOptions {
+            read: true,
+            ..OpenOptions::default()
+        };
+        let escape = |path: &[&str]| host_fs.open(&components(path), read).err();
+        assert_eq!(escape(&["secret_link"]), Some(Errno::Notcapable));
+        assert_eq!(escape(&["outside_dir", "secret"]), Some(Errno::Notcapable));
+        assert_eq!(escape(&["file_link"]), None);
+        assert_eq!(
+            host_fs.filestat(&components(&["outside_dir", "secret"]), false).err(),
+            Some(Errno::Notcapable)
+        );
+        assert_eq!(
+            host_fs.symlink("../../outside/secret", &components(&["new_link"])),
+            Err(Errno::Notcapable)
+        );
+        let link = components(&["secret_link"]);
+        assert_eq!(host_fs.set_times(&link, false, Some(0), Some(0)), Err(Errno::Notsup));
+        assert_eq!(host_fs.set_times(&link, true, Some(0), Some(0)), Err(Errno::Notcapable));
+        assert_ne!(
+            fs::metadata(outside.join("secret")).unwrap().modified().unwrap(),
+            UNIX_EPOCH
+        );
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn memory_file_size_is_capped() {
+        let memory_fs = MemoryFs::new();
+        let options = OpenOptions {
+            write: true,
+            create: true,
+            ..OpenOptions::default()
+        };
+        let mut file = memory_fs.open(&components(&["file"]), options).unwrap();
+        assert_eq!(file.write_at(b"x", u64::MAX), Err(Errno::Fbig));
+        assert_eq!(file.write_at(b"x", MEMORY_FILE_MAX_SIZE), Err(Errno::Fbig));
+        assert_eq!(file.set_size(MEMORY_FILE_MAX_SIZE + 1), Err(Errno::Fbig));
+        assert_eq!(file.write_at(b"abc", 2), Ok(3));
+        assert_eq!(file.filestat().unwrap().size, 5);
+        file.set_size(1).unwrap();
+        assert_eq!(file.filestat().unwrap().size, 1);
+    }
+}
@@ -0,0 +1,1098 @@
+use std::cell::RefCell;
+use std::io::SeekFrom;
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use rand::RngCore;
+
+use crate::value::Number;
+use crate::vm::{HostContext, Imports, Memory, Trap, VMResult};
+use crate::Value;
+
+mod fd;
+mod fs;
+mod output;
+mod types;
+
+pub use fd::*;
+pub use fs::*;
+pub use output::*;
+pub use types::*;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WasiVersion {
+    Unstable,
+    Preview1,
+}
+
+impl WasiVersion {
+    pub const ALL: [WasiVersion; 2] = [WasiVersion::Unstable, WasiVersion::Preview1];
+
+    pub const fn module_name(self) -> &'static str {
+        match self {
+            WasiVersion::Unstable => "wasi_unstable",
+            WasiVersion::Preview1 => "wasi_snapshot_preview1",
+        }
+    }
+
+    pub fn from_module_name(name: &str) -> Option<Self> {
+        WasiVersion::ALL
+            .iter()
+            .copied()
+            .find(|version| version.module_name() == name)
+    }
+}
+
+macro_rules! wasi_functions {
+    ($($variant:ident => $name:expr,)*) => {
+        #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+        pub enum WasiFunction {
+            $($variant,)*
+        }
+
+        impl WasiFunction {
+            pub const ALL: &'static [WasiFunction] = &[$(WasiFunction::$variant,)*];
+
+            pub const fn name(self) -> &'static str {
+                match self {
+                    $(WasiFunction::$variant => $name,)*
+                }
+            }
+
+            pub fn from_name(name: &str) -> Option<Self> {
+                Some(match name {
+                    $($name => WasiFunction::$variant,)*
+                    _ => return None,
+                })
+            }
+        }
+    };
+}
+
+wasi_functions! {
+    ArgsGet => "args_get",
+    ArgsSizesGet => "args_sizes_get",
+    EnvironGet => "environ_get",
+    EnvironSizesGet => "environ_sizes_get",
+    ClockResGet => "clock_res_get",
+    ClockTimeGet => "clock_time_get",
+    FdAdvise => "fd_advise",
+    FdAllocate => "fd_allocate",
+    FdClose => "fd_close",
+    FdDatasync => "fd_datasync",
+    FdFdstatGet => "fd_fdstat_get",
+    FdFdstatSetFlags => "fd_fdstat_set_flags",
+    FdFdstatSetRights => "fd_fdstat_set_rights",
+    FdFilestatGet => "fd_filestat_get",
+    FdFilestatSetSize => "fd_filestat_set_size",
+    FdFilestatSetTimes => "fd_filestat_set_times",
+    FdPread => "fd_pread",
+    FdPrestatGet => "fd_prestat_get",
+    FdPrestatDirName => "fd_prestat_dir_name",
+    FdPwrite => "fd_pwrite",
+    FdRead => "fd_read",
+    FdReaddir => "fd_readdir",
+    FdRenumber => "fd_renumber",
+    FdSeek => "fd_seek",
+    FdSync => "fd_sync",
+    FdTell => "fd_tell",
+    FdWrite => "fd_write",
+    PathCreateDirectory => "path_create_directory",
+    PathFilestatGet => "path_filestat_get",
+    PathFilestatSetTimes => "path_filestat_set_times",
+    PathLink => "path_link",
+    PathOpen => "path_open",
+    PathReadlink => "path_readlink",
+    PathRemoveDirectory => "path_remove_directory",
+    PathRename => "path_rename",
+    PathSymlink => "path_symlink",
+    PathUnlinkFile => "path_unlink_file",
+    PollOneoff => "poll_oneoff",
+    ProcExit => "proc_exit",
+    ProcRaise => "proc_raise",
+    SchedYield => "sched_yield",
+    RandomGet => "random_get",
+    SockAccept => "sock_accept",
+    SockRecv => "sock_recv",
+    SockSend => "sock_send",
+    SockShutdown => "sock_shutdown",
+}
+
+enum WasiError {
+    Errno(Errno),
+    Trap(Trap),
+}
+
+impl From<Errno> for WasiError {
+    fn from(errno: Errno) -> Self {
+        WasiError::Errno(errno)
+    }
+}
+
+impl From<Trap> for WasiError {
+    fn from(trap: Trap) -> Self {
+        match trap {
+            Trap::MemoryAccessOutOfRange(_) | Trap::NoMemory => WasiError::Errno(Errno::Fault),
+            trap => WasiError::Trap(trap),
+        }
+    }
+}
+
+type WasiResult<T> = Result<T, WasiError>;
+
+struct Args<'a>(&'a [Value]);
+
+impl<'a> Args<'a> {
+    fn get<T: Number>(&self, index: usize) -> VMResult<T> {
+        let val = self
+            .0
+            .get(index)
+            .ok_or_else(|| Trap::HostError(format!("Missing WASI argument {}", index)))?;
+        val.to::<T>().ok_or_else(|| Trap::TypeError {
+            expected: T::value_type(),
+            found: val.value_type(),
+        })
+    }
+
+    fn u32(&self, index: usize) -> VMResult<u32> {
+        self.get(index)
+    }
+
+    fn u64(&self, index: usize) -> VMResult<u64> {
+        self.get(index)
+    }
+}
+
+pub fn add_to_imports(imports: &mut Imports, wasi: &Rc<RefCell<Wasi>>) {
+    for &version in &WasiVersion::ALL {
+        for &func in WasiFunction::ALL {
+            let wasi = Rc::clone(wasi);
+            imports.add_function(
+                version.module_name(),
+                func.name(),
+                move |ctx: &mut HostContext<'_>, args: &[Value]| wasi.borrow_mut().handle(func, version, ctx, args),
+            );
+        }
+    }
+}
+
+pub struct Preopen {
+    pub guest_path: String,
+    pub fs: Rc<dyn Filesystem>,
+}
+
+// Longer sleeps in poll_oneoff advance the clocks instead of blocking the debugger
+const MAX_POLL_SLEEP: u64 = 100_000_000;
+
+pub struct Wasi {
+    args: Vec<String>,
+    env: Vec<String>,
+    preopens: Vec<Preopen>,
+    output: Rc<RefCell<Output>>,
+    fds: FdTable,
+    start_time: Instant,
+    skipped_time: u64,
+}
+
+impl Default for Wasi {
+    fn default() -> Self {
+        Wasi::new()
+    }
+}
+
+impl Wasi {
+    pub fn new() -> Self {
+        let output = Rc::new(RefCell::new(Output::default()));
+        Wasi {
+            args: Vec::new(),
+            env: Vec::new(),
+            preopens: Vec::new(),
+            fds: FdTable::with_stdio(&output),
+            output,
+            start_time: Instant::now(),
+            skipped_time: 0,
+        }
+    }
+
+    pub fn reset(&mut self, args: Vec<String>, env: Vec<String>) {
+        self.args = args;
+        self.env = env;
+        self.output.borrow_mut().take_captured();
+        self.fds = FdTable::with_stdio(&self.output);
+        for preopen in &self.preopens {
+            let dir = Directory {
+                fs: Rc::clone(&preopen.fs),
+                root: preopen.guest_path.clone(),
+                path: Vec::new(),
+            };
+            let mut entry = FileDescriptor::new(
+                Handle::Dir(dir),
+                preopen.guest_path.clone(),
+                RIGHTS_DIR_BASE,
+                RIGHTS_ALL,
+            );
+            entry.preopen = Some(preopen.guest_path.clone());
+            self.fds.insert(entry);
+        }
+        self.start_time = Instant::now();
+        self.skipped_time = 0;
+    }
+
+    pub fn output(&self) -> &Rc<RefCell<Output>> {
+        &self.output
+    }
+
+    pub fn set_stdin(&mut self, file: Box<dyn WasiFile>) {
+        if let Ok(entry) = self.fds.get_mut(0) {
+            entry.handle = Handle::File(file);
+        }
+    }
+
+    pub fn preopens(&self) -> &[Preopen] {
+        &self.preopens
+    }
+
+    pub fn add_preopen(&mut self, guest_path: &str, fs: Rc<dyn Filesystem>) {
+        self.preopens.retain(|preopen| preopen.guest_path != guest_path);
+        self.preopens.push(Preopen {
+            guest_path: guest_path.to_owned(),
+            fs,
+        });
+    }
+
+    pub fn clear_preopens(&mut self) {
+        self.preopens.clear();
+    }
+
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    pub fn env(&self) -> &[String] {
+        &self.env
+    }
+
+    pub const fn fds(&self) -> &FdTable {
+        &self.fds
+    }
+
+    pub fn fds_mut(&mut self) -> &mut FdTable {
+        &mut self.fds
+    }
+
+    pub fn handle(
+        &mut self,
+        func: WasiFunction,
+        version: WasiVersion,
+        ctx: &mut HostContext<'_>,
+        args: &[Value],
+    ) -> VMResult<Vec<Value>> {
+        let args = Args(args);
+        let result = match func {
+            WasiFunction::ArgsGet => Self::strings_get(&self.args, ctx, &args),
+            WasiFunction::ArgsSizesGet => Self::strings_sizes_get(&self.args, ctx, &args),
+            WasiFunction::EnvironGet => Self::strings_get(&self.env, ctx, &args),
+            WasiFunction::EnvironSizesGet => Self::strings_sizes_get(&self.env, ctx, &args),
+            WasiFunction::ClockResGet => self.clock_res_get(ctx, &args),
+            WasiFunction::ClockTimeGet => self.clock_time_get(ctx, &args),
+            WasiFunction::FdAdvise => self.fd_advise(&args),
+            WasiFunction::FdAllocate => self.fd_allocate(&args),
+            WasiFunction::FdClose => self.fd_close(&args),
+            WasiFunction::FdDatasync => self.fd_sync(&args, RIGHT_FD_DATASYNC),
+            WasiFunction::FdFdstatGet => self.fd_fdstat_get(ctx, &args),
+            WasiFunction::FdFdstatSetFlags => self.fd_fdstat_set_flags(&args),
+            WasiFunction::FdFdstatSetRights => self.fd_fdstat_set_rights(&args),
+            WasiFunction::FdFilestatGet => self.fd_filestat_get(version, ctx, &args),
+            WasiFunction::FdFilestatSetSize => self.fd_filestat_set_size(&args),
+            WasiFunction::FdFilestatSetTimes => self.fd_filestat_set_times(&args),
+            WasiFunction::FdPread => self.fd_pread(ctx, &args),
+            WasiFunction::FdPrestatGet => self.fd_prestat_get(ctx, &args),
+            WasiFunction::FdPrestatDirName => self.fd_prestat_dir_name(ctx, &args),
+            WasiFunction::FdPwrite => self.fd_pwrite(ctx, &args),
+            WasiFunction::FdRead => self.fd_read(ctx, &args),
+            WasiFunction::FdReaddir => self.fd_readdir(ctx, &args),
+            WasiFunction::FdRenumber => self.fd_renumber(&args),
+            WasiFunction::FdSeek => self.fd_seek(version, ctx, &args),
+            WasiFunction::FdSync => self.fd_sync(&args, RIGHT_FD_SYNC),
+            WasiFunction::FdTell => self.fd_tell(ctx, &args),
+            WasiFunction::FdWrite => self.fd_write(ctx, &args),
+            WasiFunction::PathCreateDirectory => self.path_create_directory(ctx, &args),
+            WasiFunction::PathFilestatGet => self.path_filestat_get(version, ctx, &args),
+            WasiFunction::PathFilestatSetTimes => self.path_filestat_set_times(ctx, &args),
+            WasiFunction::PathLink => self.path_link(ctx, &args),
+            WasiFunction::PathOpen => self.path_open(ctx, &args),
+            WasiFunction::PathReadlink => self.path_readlink(ctx, &args),
+            WasiFunction::PathRemoveDirectory => self.path_remove_directory(ctx, &args),
+            WasiFunction::PathRename => self.path_rename(ctx, &args),
+            WasiFunction::PathSymlink => self.path_symlink(ctx, &args),
+            WasiFunction::PathUnlinkFile => self.path_unlink_file(ctx, &args),
+            WasiFunction::PollOneoff => self.poll_oneoff(version, ctx, &args),
+            WasiFunction::ProcExit => return Err(Trap::WasiExit(args.u32(0)?)),
+            WasiFunction::ProcRaise => Err(Errno::Nosys.into()),
+            WasiFunction::SchedYield => {
+                thread::yield_now();
+                Ok(())
+            }
+            WasiFunction::RandomGet => self.random_get(ctx, &args),
+            WasiFunction::SockAccept | WasiFunction::SockRecv | WasiFunction::SockSend | WasiFunction::SockShutdown => {
+                self.sock_unsupported(args.u32(0)?)
+            }
+        };
+        let errno = match result {
+            Ok(()) => Errno::Success,
+            Err(WasiError::Errno(errno)) => errno,
+            Err(WasiError::Trap(trap)) => return Err(trap),
+        };
+        Ok(vec![Value::I32(errno as i32)])
+    }
+
+    fn strings_get(strings: &[String], ctx: &mut HostContext<'_>, args: &Args<'_>) -> WasiResult<()> {
+        let mut ptrs = args.u32(0)?;
+        let mut buf = args.u32(1)?;
+        let memory = ctx.memory_mut()?;
+        for string in strings {
+            memory.store(ptrs, buf)?;
+            memory.write(buf, string.as_bytes())?;
+            let end = ptr_add(buf, string.len() as u32)?;
+            memory.store(end, 0u8)?;
+            ptrs = ptr_add(ptrs, 4)?;
+            buf = ptr_add(end, 1)?;
+        }
+        Ok(())
+    }
+
+    fn strings_sizes_get(strings: &[String], ctx: &mut HostContext<'_>, args: &Args<'_>) -> WasiResult<()> {
+        let count_out = args.u32(0)?;
+        let buf_size_out = args.u32(1)?;
+        let buf_size: usize = strings.iter().map(|s| s.len() + 1).sum();
+        let memory = ctx.memory_mut()?;
+        memory.store(count_out, strings.len() as u32)?;
+        memory.store(buf_size_out, buf_size as u32)?;
+        Ok(())
+    }
+
+    fn clock_res_get(&mut self, ctx: &mut HostContext<'_>, args: &Args<'_>) -> WasiResult<()> {
+        let resolution: u64 = match args.u32(0)? {
+            CLOCK_REALTIME | CLOCK_MONOTONIC | CLOCK_PROCESS_CPUTIME_ID | CLOCK_THREAD_CPUTIME_ID => 1,
+            _ => return Err(Errno::Inval.into()),
+        };
+        ctx.memory_mut()?.store(args.u32(1)?, resolution)?;
+        Ok(())
+    }
+
+    fn clock_time_get(&mut self, ctx: &mut HostContext<'_>, args: &Args<'_>) -> WasiResult<()> {
+        let time = self.clock_time(args.u32(0)?)?;
+        ctx.memory_mut()?.store(args.u32(2)?, time)?;
+        Ok(())
+    }
+
+    fn clock_time(&self, clock_id: u32) -> Result<u64, Errno> {
+        let time = match clock_id {
+            CLOCK_REALTIME => {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).map_err(|_| Errno::Io)?;
+                now.as_nanos() as u64
+            }
+            CLOCK_MONOTONIC | CLOCK_PROCESS_CPUTIME_ID | CLOCK_THREAD_CPUTIME_ID => {
+                self.start_time.elapsed().as_nanos() as u64
+            }
+            _ => return Err(Errno::Inval),
+        };
+        Ok(time.saturating_add(self.skipped_time))
+    }
+
+    fn fd_advise(&mut self, args: &Args<'_>) -> WasiResult<()> {
+        self.fds.get(args.u32(0)?)?.check_rights(RIGHT_FD_ADVISE)?;
+        Ok(())
+    }
+
+    fn fd_allocate(&mut self, args: &Args<'_>) -> WasiResult<()> {
+        let entry = self.fds.get_mut(args.u32(0)?)?;
+        entry.check_rights(RIGHT_FD_ALLOCATE)?;
+        let end = args.u64(1)?.checked_add(args.u64(2)?).ok_or(Errno::Fbig)?;
+        let file = entry.file_mut()?;
+        if file.filestat()?.size < end {
+            file.set_size(end)?;
+        }
+        Ok(())
+    }
+
+    fn fd_close(&mut self, args: &Args<'_>) -> WasiResult<()> {
+        self.fds.remove(args.u32(0)?)?;
+        Ok(())
+    }
+
+    fn fd_sync(&mut self, args: &Args<'_>, right: u64) -> WasiResult<()> {
+        let entry = self.fds.get_mut(args.u32(0)?)?;
+        entry.check_rights(right)?;
+        entry.file_mut()?.sync()?;
+        Ok(())
+    }
+
+    fn fd_fdstat_get(&mut self, ctx: &mut HostContext<'_>, args: &Args<'_>) -> WasiResult<()> {
+        let entry = self.fds.get(args.u32(0)?)?;
+        let out = args.u32(1)?;
+        let memory = ctx.memory_mut()?;
+        memory.store(out, entry.filetype() as u8)?;
+        memory.store(ptr_add(out, 2)?, entry.flags)?;
+        memory.store(ptr_add(out, 8)?, entry.rights_base)?;
+        memory.store(ptr_add(out, 16)?, entry.rights_inheriting)?;
+        Ok(())
+    }
+
+    fn fd_fdstat_set_flags(&mut self, args: &Args<'_>) -> WasiResult<()> {
+        let entry = self.fds.get_mut(args.u32(0)?)?;
+        entry.check_rights(RIGHT_FD_FDSTAT_SET_FLAGS)?;
+        entry.flags = args.u32(1)? as u16;
+        Ok(())
+    }
+
+    fn fd_fdstat_set_rights(&mut self, args: &Args<'_>) -> WasiResult<()> {
+        let entry = self.fds.get_mut(args.u32(0)?)?;
+        let rights_base = args.u64(1)?;
+        let rights_inheriting = args.u64(2)?;
+        if rights_base & !entry.rights_base != 0 || rights_inheriting & !entry.rights_inheriting != 0 {
+            return Err(Errno::Notcapable.into());
+        }
+        entry.rights_base = rights_base;
+        entry.rights_inheriting = rights_inheriting;
+        Ok(())
+    }
+
+    fn fd_filestat_get(&mut self, version: WasiVersion, ctx: &mut HostContext<'_>, args: &Args<'_>) -> WasiResult<()> {
+        let entry = self.fds.get(args.u32(0)?)?;
+        entry.check_rights(RIGHT_FD_FILESTAT_GET)?;
+        let filestat = entry.filestat()?;
+        store_filestat(version, ctx.memory_mut()?, args.u32(1)?, &filestat)
+    }
+
+    fn fd_filestat_set_size(&mut self, args: &Args<'_>) -> WasiResult<()> {
+        let entry = self.fds.get_mut(args.u32(0)?)?;
+        entry.check_rights(RIGHT_FD_FILESTAT_SET_SIZE)?;
+        entry.file_mut()?.set_size(args.u64(1)?)?;
+        Ok(())
+    }
+
+    fn fd_filestat_set_times(&mut self, args: &Args<'_>) -> WasiResult<()> {
+        let (atim, mtim) = self.filestat_times(args.u64(1)?, args.u64(2)?, args.u32(3)? as u16)?;
+        let entry = self.fds.get_mut(args.u32(0)?)?;
+        entry.check_rights(RIGHT_FD_FILESTAT_SET_TIMES)?;
+        entry.set_times(atim, mtim)?;
+        Ok(())
+    }
+
+    fn filestat_times(&self, atim: u64, mtim: u64, fst_flags: u16) -> Result<(Option<u64>, Option<u64>), Errno> {
+        if fst_flags & FSTFLAG_ATIM != 0 && fst_flags & FSTFLAG_ATIM_NOW != 0
+            || fst_flags & FSTFLAG_MTIM != 0 && fst_flags & FSTFLAG_MTIM_NOW != 0
+        {
+            return Err(Errno::Inval);
+        }
+        let now = self.clock_time(CLOCK_REALTIME)?;
+        let atim = match fst_flags {
+            f if f & FSTFLAG_ATIM != 0 => Some(atim),
+            f if f & FSTFLAG_ATIM_NOW != 0 => Some(now),
+            _ => None,
+        };
+        let mtim = match fst_flags {
+            f if f & FSTFLAG_MTIM != 0 => Some(mtim),
+            f if f & FSTFLAG_MTIM_NOW != 0 => Some(now),
+            _ => None,
+        };
+        Ok((atim, mtim))
+    }
+
+    fn fd_read(&mut self, ctx: &mut HostContext<'_>, args: &Args<'_>) -> WasiResult<()> {
+        let entry = self.fds.get_mut(args.u32(0)?)?;
+        entry.check_rights(RIGHT_FD_READ)?;
+        let file = entry.file_mut()?;
+        let nread = read_iovs(ctx.memory_mut()?, args.u32(1)?, args.u32(2)?, |buf| file.read(buf))?;
+        ctx.memory_mut()?.store(args.u32(3)?, nread)?;
+        Ok(())
+    }
+
+    fn fd_pread(&mut self, ctx: &mut HostContext<'_>, args: &Args<'_>) -> WasiResult<()> {
+        let entry = self.fds.get_mut(args.u32(0)?)?;
+        entry.check_rights(RIGHT_FD_READ | RIGHT_FD_SEEK)?;
+        let file = entry.file_mut()?;
+        let mut offset = args.u64(3)?;
+        let nread = read_iovs(ctx.memory_mut()?, args.u32(1)?, args.u32(2)?, |buf| {
+            let nread = file.read_at(buf, offset)?;
+            offset += nread as u64;
+            Ok(nread)
+        })?;
+        ctx.memory_mut()?.store(args.u32(4)?, nread)?;
+        Ok(())
+    }
+
+    fn fd_write(&mut self, ctx: &mut HostContext<'_>, args: &Args<'_>) -> WasiResult<()> {
+        let entry = self.fds.get_mut(args.u32(0)?)?;
+        entry.check_rights(RIGHT_FD_WRITE)?;
+        let data = gather_iovs(ctx.memory()?, args.u32(1)?, args.u32(2)?)?;
+        let append = entry.flags & FDFLAG_APPEND != 0;
+        let file = entry.file_mut()?;
+        if append {
+            file.seek(SeekFrom::End(0))?;
+        }
+        let nwritten = file.write(&data)?;
+        ctx.memory_mut()?.store(args.u32(3)?, nwritten as u32)?;
+        Ok(())
+    }
+
+    fn fd_pwrite(&mut self, ctx: &mut HostContext<'_>, args: &Args<'_>) -> WasiResult<()> {
+        let entry = self.fds.get_mut(args.u32(0)?)?;
+        entry.check_rights(RIGHT_FD_WRITE | RIGHT_FD_SEEK)?;
+        let data = gather_iovs(ctx.memory()?, args.u32(1)?, args.u32(2)?)?;
+        let nwritten = entry.file_mut()?.write_at(&data, args.u64(3)?)?;
+        ctx.memory_mut()?.store(args.u32(4)?, nwritten as u32)?;
+        Ok(())
+    }
+
+    fn fd_prestat_get(&mut self, ctx: &mut HostContext<'_>, args: &Args<'_>) -> WasiResult<()> {
+        let name = self.preopen_name(args.u32(0)?)?;
+        let out = args.u32(1)?;
+        let memory = ctx.memory_mut()?;
+        memory.store(out, PREOPENTYPE_DIR)?;
+        memory.store(ptr_add(out, 4)?, name.len() as u32)?;
+        Ok(())
+    }
+
+    fn fd_prestat_dir_name(&mut self, ctx: &mut HostContext<'_>, args: &Args<'_>) -> WasiResult<()> {
+        let name = self.preopen_name(args.u32(0)?)?;
+        if (args.u32(2)? as usize) < name.len() {
+            return Err(Errno::Nametoolong.into());
+        }
+        ctx.memory_mut()?.write(args.u32(1)?, name.as_bytes())?;
+        Ok(())
+    }
+
+    fn preopen_name(&self, fd: u32) -> Result<&str, Errno> {
+        self.fds.get(fd)?.preopen.as_deref().ok_or(Errno::Badf)
+    }
+
+    fn fd_readdir(&mut self, ctx: &mut HostContext<'_>, args: &Args<'_>) -> WasiResult<()> {
+        let entry = self.fds.get(args.u32(0)?)?;
+        entry.check_rights(RIGHT_FD_READDIR)?;
+        let dir = entry.dir()?;
+        let buf = args.u32(1)?;
+        let buf_len = args.u32(2)? as usize;
+        let cookie = args.u64(3)?;
+
+        let ino = dir.fs.filestat(&dir.path, true)?.ino;
+        let mut entries = vec![
+            DirEntry {
+                name: String::from("."),
+                filetype: Filetype::Directory,
+                ino,
+            },
+            DirEntry {
+                name: String::from(".."),
+                filetype: Filetype::Directory,
+                ino: 0,
+            },
+        ];
+        entries.extend(dir.fs.read_dir(&dir.path)?);
+
+        let mut data = Vec::new();
+        for (i, dir_entry) in entries.iter().enumerate().skip(cookie as usize) {
+            if data.len() >= buf_len {
+                break;
+            }
+            data.extend_from_slice(&(i as u64 + 1).to_le_bytes());
+            data.extend_from_slice(&dir_entry.ino.to_le_bytes());
+            data.extend_from_slice(&(dir_entry.name.len() as u32).to_le_bytes());
+            data.extend_from_slice(&[dir_entry.filetype as u8, 0, 0, 0]);
+            data.extend_from_slice(dir_entry.name.as_bytes());
+        }
+        data.truncate(buf_len);
+        let memory = ctx.memory_mut()?;
+        memory.write(buf, &data)?;
+        memory.store(args.u32(4)?, data.len() as u32)?;
+        Ok(())
+    }
+
+    fn fd_renumber(&mut self, args: &Args<'_>) -> WasiResult<()> {
+        let from = args.u32(0)?;
+        let to = args.u32(1)?;
+        self.fds.get(to)?;
+        let entry = self.fds.remove(from)?;
+        self.fds.insert_at(to, entry);
+        Ok(())
+    }
+
+    fn fd_seek(&mut self, version: WasiVersion, ctx: &mut HostContext<'_>, args: &Args<'_>) -> WasiResult<()> {
+        let entry = self.fds.get_mut(args.u32(0)?)?;
+        let offset: i64 = args.get(1)?;
+        let whence = args.u32(2)? as u8;
+        let (set, cur, end) = match version {
+            WasiVersion::Unstable => (2, 0, 1),
+            WasiVersion::Preview1 => (0, 1, 2),
+        };
+        let pos = match whence {
+            w if w == set => SeekFrom::Start(offset as u64),
+            w if w == cur => SeekFrom::Current(offset),
+            w if w == end => SeekFrom::End(offset),
+            _ => return Err(Errno::Inval.into()),
+        };
+        if let SeekFrom::Current(0) = pos {
+            entry.check_rights(RIGHT_FD_TELL)?;
+        } else {
+            entry.check_rights(RIGHT_FD_SEEK)?;
+        }
+        let new_offset = entry.file_mut()?.seek(pos)?;
+        ctx.memory_mut()?.store(args.u32(3)?, new_offset)?;
+        Ok(())
+    }
+
+    fn fd_tell(&mut self, ctx: &mut HostContext<'_>, args: &Args<'_>) -> WasiResult<()> {
+        let entry = self.fds.get_mut(args.u32(0)?)?;
+        entry.check_rights(RIGHT_FD_TELL)?;
+        let offset = entry.file_mut()?.seek(SeekFrom::Current(0))?;
+        ctx.memory_mut()?.store(args.u32(1)?, offset)?;
+        Ok(())
+    }
+
+    // Looks up the directory `fd` and resolves the path stored at `path_ptr` relative to it.
+    // The returned `Directory` points at the resolved path, which doesn't have to be a directory.
+    fn resolve(
+        &self,
+        ctx: &HostContext<'_>,
+        fd: u32,
+        rights: u64,
+        path_ptr: u32,
+        path_len: u32,
+    ) -> WasiResult<Directory> {
+        let entry = self.fds.get(fd)?;
+        entry.check_rights(rights)?;
+        let dir = entry.dir()?;
+        let path = std::str::from_utf8(ctx.memory()?.read(path_ptr, path_len)?).map_err(|_| Errno::Ilseq)?;
+        Ok(Directory {
+            fs: Rc::clone(&dir.fs),
+            root: dir.root.clone(),
+            path: resolve_path(&dir.path, path)?,
+        })
+    }
+
+    fn path_open(&mut self, ctx: &mut HostContext<'_>, args: &Args<'_>) -> WasiResult<()> {
+        let fd = args.u32(0)?;
+        let follow = args.u32(1)? & LOOKUPFLAG_SYMLINK_FOLLOW != 0;
+        let oflags = args.u32(4)? as u16;
+        let fdflags = args.u32(7)? as u16;
+
+        let mut rights = RIGHT_PATH_OPEN;
+        if oflags & OFLAG_CREAT != 0 {
+            rights |= RIGHT_PATH_CREATE_FILE;
+        }
+        if oflags & OFLAG_TRUNC != 0 {
+            rights |= RIGHT_PATH_FILESTAT_SET_SIZE;
+        }
+        let target = self.resolve(ctx, fd, rights, args.u32(2)?, args.u32(3)?)?;
+        let parent = self.fds.get(fd)?;
+        let mut rights_base = args.u64(5)? & parent.rights_inheriting;
+        let rights_inheriting = args.u64(6)? & parent.rights_inheriting;
+        let name = target.guest_path();
+
+        let is_dir = match target.fs.filestat(&target.path, follow) {
+            Ok(filestat) => filestat.filetype == Some(Filetype::Directory),
+            Err(errno) if oflags & OFLAG_DIRECTORY != 0 => return Err(errno.into()),
+            Err(_) => false,
+        };
+        let handle = if is_dir {
+            if oflags & (OFLAG_CREAT | OFLAG_EXCL) == OFLAG_CREAT | OFLAG_EXCL {
+                return Err(Errno::Exist.into());
+            }
+            if oflags & OFLAG_DIRECTORY != 0 {
+                rights_base &= RIGHTS_DIR_BASE;
+            }
+            if oflags & OFLAG_TRUNC != 0 || rights_base & RIGHT_FD_WRITE != 0 {
+                return Err(Errno::Isdir.into());
+            }
+            Handle::Dir(target)
+        } else {
+            if oflags & OFLAG_DIRECTORY != 0 {
+                return Err(Errno::Notdir.into());
+            }
+            let options = OpenOptions {
+                read: rights_base & RIGHT_FD_READ != 0,
+                write: rights_base & RIGHT_FD_WRITE != 0 || oflags & OFLAG_TRUNC != 0,
+                create: oflags & OFLAG_CREAT != 0,
+                excl: oflags & OFLAG_EXCL != 0,
+                truncate: oflags & OFLAG_TRUNC != 0,
+            };
+            Handle::File(target.fs.open(&target.path, options)?)
+        };
+
+        let mut entry = FileDescriptor::new(handle, name, rights_base, rights_inheriting);
+        entry.flags = fdflags;
+        let new_fd = self.fds.insert(entry);
+        ctx.memory_mut()?.store(args.u32(8)?, new_fd)?;
+        Ok(())
+    }
+
+    fn path_create_directory(&mut self, ctx: &mut HostContext<'_>, args: &Args<'_>) -> WasiResult<()> {
+        let target = self.resolve(
+            ctx,
+            args.u32(0)?,
+            RIGHT_PATH_CREATE_DIRECTORY,
+            args.u32(1)?,
+            args.u32(2)?,
+        )?;
+        target.fs.create_dir(&target.path)?;
+        Ok(())
+    }
+
+    fn path_filestat_get(
+        &mut self,
+        version: WasiVersion,
+        ctx: &mut HostContext<'_>,
+        args: &Args<'_>,
+    ) -> WasiResult<()> {
+        let follow = args.u32(1)? & LOOKUPFLAG_SYMLINK_FOLLOW != 0;
+        let target = self.resolve(ctx, args.u32(0)?, RIGHT_PATH_FILESTAT_GET, args.u32(2)?, args.u32(3)?)?;
+        let filestat = target.fs.filestat(&target.path, follow)?;
+        store_filestat(version, ctx.memory_mut()?, args.u32(4)?, &filestat)
+    }
+
+    fn path_filestat_set_times(&mut self, ctx: &mut HostContext<'_>, args: &Args<'_>) -> WasiResult<()> {
+        let follow = args.u32(1)? & LOOKUPFLAG_SYMLINK_FOLLOW != 0;
+        let (atim, mtim) = self.filestat_times(args.u64(4)?, args.u64(5)?, args.u32(6)? as u16)?;
+        let target = self.resolve(
+            ctx,
+            args.u32(0)?,
+            RIGHT_PATH_FILESTAT_SET_TIMES,
+            args.u32(2)?,
+            args.u32(3)?,
+        )?;
+        target.fs.set_times(&target.path, follow, atim, mtim)?;
+        Ok(())
+    }
+
+    fn path_link(&mut self, ctx: &mut HostContext<'_>, args: &Args<'_>) -> WasiResult<()> {
+        let old = self.resolve(ctx, args.u32(0)?, RIGHT_PATH_LINK_SOURCE, args.u32(2)?, args.u32(3)?)?;
+        let new = self.resolve(ctx, args.u32(4)?, RIGHT_PATH_LINK_TARGET, args.u32(5)?, args.u32(6)?)?;
+        if !Rc::ptr_eq(&old.fs, &new.fs) {
+            return Err(Errno::Xdev.into());
+        }
+        old.fs.link(&old.path, &new.path)?;
+        Ok(())
+    }
+
+    fn path_readlink(&mut self, ctx: &mut HostContext<'_>, args: &Args<'_>) -> WasiResult<()> {
+        let target = self.resolve(ctx, args.u32(0)?, RIGHT_PATH_READLINK, args.u32(1)?, args.u32(2)?)?;
+        let link = target.fs.readlink(&target.path)?;
+        let len = link.len().min(args.u32(4)? as usize);
+        let memory = ctx.memory_mut()?;
+        memory.write(args.u32(3)?, &link.as_bytes()[..len])?;
+        memory.store(args.u32(5)?, len as u32)?;
+        Ok(())
+    }
+
+    fn path_remove_directory(&mut self, ctx: &mut HostContext<'_>, args: &Args<'_>) -> WasiResult<()> {
+        let target = self.resolve(
+            ctx,
+            args.u32(0)?,
+            RIGHT_PATH_REMOVE_DIRECTORY,
+            args.u32(1)?,
+            args.u32(2)?,
+        )?;
+        target.fs.remove_dir(&target.path)?;
+        Ok(())
+    }
+
+    fn path_rename(&mut self, ctx: &mut HostContext<'_>, args: &Args<'_>) -> WasiResult<()> {
+        let old = self.resolve(ctx, args.u32(0)?, RIGHT_PATH_RENAME_SOURCE, args.u32(1)?, args.u32(2)?)?;
+        let new = self.resolve(ctx, args.u32(3)?, RIGHT_PATH_RENAME_TARGET, args.u32(4)?, args.u32(5)?)?;
+        if !Rc::ptr_eq(&old.fs, &new.fs) {
+            return Err(Errno::Xdev.into());
+        }
+        old.fs.rename(&old.path, &new.path)?;
+        Ok(())
+    }
+
+    fn path_symlink(&mut self, ctx: &mut HostContext<'_>, args: &Args<'_>) -> WasiResult<()> {
+        let link = ctx.memory()?.read(args.u32(0)?, args.u32(1)?)?;
+        let link = String::from_utf8(link.to_vec()).map_err(|_| Errno::Ilseq)?;
+        let target = self.resolve(ctx, args.u32(2)?, RIGHT_PATH_SYMLINK, args.u32(3)?, args.u32(4)?)?;
+        target.fs.symlink(&link, &target.path)?;
+        Ok(())
+    }
+
+    fn path_unlink_file(&mut self, ctx: &mut HostContext<'_>, args: &Args<'_>) -> WasiResult<()> {
+        let target = self.resolve(ctx, args.u32(0)?, RIGHT_PATH_UNLINK_FILE, args.u32(1)?, args.u32(2)?)?;
+        target.fs.unlink_file(&target.path)?;
+        Ok(())
+    }
+
+    fn sock_unsupported(&mut self, fd: u32) -> WasiResult<()> {
+        self.fds.get(fd)?;
+        Err(Errno::Notsock.into())
+    }
+
+    fn poll_oneoff(&mut self, version: WasiVersion, ctx: &mut HostContext<'_>, args: &Args<'_>) -> WasiResult<()> {
+        let subscriptions = args.u32(0)?;
+        let events = args.u32(1)?;
+        let count = args.u32(2)?;
+        let nevents_out = args.u32(3)?;
+        if count == 0 {
+            return Err(Errno::Inval.into());
+        }
+
+        let (sub_size, clock_offset) = match version {
+            WasiVersion::Unstable => (56, 24),
+            WasiVersion::Preview1 => (48, 16),
+        };
+        let memory = ctx.memory_mut()?;
+        let mut ready = Vec::new();
+        let mut timeouts = Vec::new();
+        for i in 0..count {
+            let sub = ptr_index(subscriptions, i, sub_size)?;
+            let userdata: u64 = memory.load(sub)?;
+            let event_type: u8 = memory.load(ptr_add(sub, 8)?)?;
+            match event_type {
+                EVENTTYPE_CLOCK => {
+                    let clock = ptr_add(sub, clock_offset)?;
+                    let clock_id: u32 = memory.load(clock)?;
+                    let timeout: u64 = memory.load(ptr_add(clock, 8)?)?;
+                    let flags: u16 = memory.load(ptr_add(clock, 24)?)?;
+                    let timeout = if flags & SUBCLOCKFLAG_ABSTIME != 0 {
+                        timeout.saturating_sub(self.clock_time(clock_id)?)
+                    } else {
+                        timeout
+                    };
+                    timeouts.push((userdata, timeout));
+                }
+                EVENTTYPE_FD_READ | EVENTTYPE_FD_WRITE => {
+                    let fd: u32 = memory.load(ptr_add(sub, 16)?)?;
+                    let errno = match self.fds.get(fd) {
+                        Ok(entry) if event_type == EVENTTYPE_FD_READ => entry.check_rights(RIGHT_FD_READ).err(),
+                        Ok(entry) => entry.check_rights(RIGHT_FD_WRITE).err(),
+                        Err(errno) => Some(errno),
+                    };
+                    ready.push((userdata, errno.unwrap_or(Errno::Success), event_type));
+                }
+                _ => return Err(Errno::Inval.into()),
+            }
+        }
+
+        if ready.is_empty() {
+            if let Some(min_timeout) = timeouts.iter().map(|(_, timeout)| *timeout).min() {
+                let sleep = min_timeout.min(MAX_POLL_SLEEP);
+                thread::sleep(Duration::from_nanos(sleep));
+                self.skipped_time = self.skipped_time.saturating_add(min_timeout - sleep);
+                for (userdata, timeout) in timeouts {
+                    if timeout <= min_timeout {
+                        ready.push((userdata, Errno::Success, EVENTTYPE_CLOCK));
+                    }
+                }
+            }
+        }
+
+        for (i, (userdata, errno, event_type)) in ready.iter().enumerate() {
+            let event = ptr_index(events, i as u32, 32)?;
+            memory.store(event, *userdata)?;
+            memory.store(ptr_add(event, 8)?, *errno as u16)?;
+            memory.store(ptr_add(event, 10)?, *event_type)?;
+            memory.store(ptr_add(event, 16)?, 0u64)?;
+            memory.store(ptr_add(event, 24)?, 0u16)?;
+        }
+        memory.store(nevents_out, ready.len() as u32)?;
+        Ok(())
+    }
+
+    fn random_get(&mut self, ctx: &mut HostContext<'_>, args: &Args<'_>) -> WasiResult<()> {
+        let ptr = args.u32(0)?;
+        let len = args.u32(1)?;
+        let memory = ctx.memory_mut()?;
+        check_range(memory, ptr, len)?;
+        let mut buf = vec![0; len as usize];
+        rand::thread_rng().fill_bytes(&mut buf);
+        memory.write(ptr, &buf)?;
+        Ok(())
+    }
+}
+
+fn gather_iovs(memory: &Memory, iovs: u32, iovs_len: u32) -> WasiResult<Vec<u8>> {
+    let mut data = Vec::new();
+    for i in 0..iovs_len {
+        let iov = ptr_index(iovs, i, 8)?;
+        let buf: u32 = memory.load(iov)?;
+        let buf_len: u32 = memory.load(ptr_add(iov, 4)?)?;
+        data.extend_from_slice(memory.read(buf, buf_len)?);
+    }
+    Ok(data)
+}
+
+fn read_iovs<F>(memory: &mut Memory, iovs: u32, iovs_len: u32, mut read: F) -> WasiResult<u32>
+where
+    F: FnMut(&mut [u8]) -> Result<usize, Errno>,
+{
+    let mut nread = 0;
+    for i in 0..iovs_len {
+        let iov = ptr_index(iovs, i, 8)?;
+        let buf: u32 = memory.load(iov)?;
+        let buf_len: u32 = memory.load(ptr_add(iov, 4)?)?;
+        check_range(memory, buf, buf_len)?;
+        let mut data = vec![0; buf_len as usize];
+        let len = read(&mut data)?;
+        memory.write(buf, &data[..len])?;
+        nread += len as u32;
+        if len < buf_len as usize {
+            break;
+        }
+    }
+    Ok(nread)
+}
+
+fn store_filestat(version: WasiVersion, memory: &mut Memory, out: u32, filestat: &Filestat) -> WasiResult<()> {
+    let filetype = filestat.filetype.unwrap_or(Filetype::Unknown) as u8;
+    memory.store(out, filestat.dev)?;
+    memory.store(ptr_add(out, 8)?, filestat.ino)?;
+    memory.store(ptr_add(out, 16)?, filetype)?;
+    let times = match version {
+        WasiVersion::Unstable => {
+            memory.store(ptr_add(out, 20)?, filestat.nlink as u32)?;
+            memory.store(ptr_add(out, 24)?, filestat.size)?;
+            ptr_add(out, 32)?
+        }
+        WasiVersion::Preview1 => {
+            memory.store(ptr_add(out, 24)?, filestat.nlink)?;
+            memory.store(ptr_add(out, 32)?, filestat.size)?;
+            ptr_add(out, 40)?
+        }
+    };
+    memory.store(times, filestat.atim)?;
+    memory.store(ptr_add(times, 8)?, filestat.mtim)?;
+    memory.store(ptr_add(times, 16)?, filestat.ctim)?;
+    Ok(())
+}
+
+// Guest pointers are untrusted, so arithmetic on them must not wrap around
+fn ptr_add(ptr: u32, offset: u32) -> Result<u32, Errno> {
+    ptr.checked_add(offset).ok_or(Errno::Fault)
+}
+
+fn ptr_index(base: u32, index: u32, size: u32) -> Result<u32, Errno> {
+    index
+        .checked_mul(size)
+        .and_then(|offset| base.checked_add(offset))
+        .ok_or(Errno::Fault)
+}
+
+// Checked before allocating host buffers with a guest-chosen length
+fn check_range(memory: &Memory, ptr: u32, len: u32) -> Result<(), Errno> {
+    if ptr as usize + len as usize > memory.data().len() {
+        return Err(Errno::Fault);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::{Errno, OutputSink};
+    use crate::test_utils::load_wat;
+    use crate::vm::Trap;
+    use crate::Value;
+
+    #[test]
+    fn fd_write() {
+        let mut dbg = load_wat(
+            r#"(module
+                (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 0) "\10\00\00\00\03\00\00\00\20\00\00\00\04\00\00\00")
+                (data (i32.const 16) "foo")
+                (data (i32.const 32) "bar\n")
+                (func (param $fd i32) (result i32)
+                    (call $fd_write (local.get $fd) (i32.const 0) (i32.const 2) (i32.const 64))))"#,
+        );
+        dbg.set_output_sink(OutputSink::Buffer);
+        assert!(matches!(
+            dbg.call(1, &[Value::I32(1)]).unwrap(),
+            Trap::ExecutionFinished
+        ));
+        assert_eq!(dbg.vm().unwrap().value_stack(), &[Value::I32(Errno::Success as i32)]);
+        assert_eq!(dbg.memory().unwrap().load::<u32>(64).unwrap(), 7);
+        assert_eq!(dbg.captured_output().stdout, b"foobar\n");
+
+        assert!(matches!(
+            dbg.call(1, &[Value::I32(9)]).unwrap(),
+            Trap::ExecutionFinished
+        ));
+        assert_eq!(dbg.vm().unwrap().value_stack(), &[Value::I32(Errno::Badf as i32)]);
+    }
+
+    #[test]
+    fn args_and_environ_get() {
+        let mut dbg = load_wat(
+            r#"(module
+                (import "wasi_snapshot_preview1" "args_sizes_get" (func $args_sizes_get (param i32 i32) (result i32)))
+                (import "wasi_snapshot_preview1" "args_get" (func $args_get (param i32 i32) (result i32)))
+                (import "wasi_snapshot_preview1" "environ_sizes_get" (func $environ_sizes_get (param i32 i32) (result i32)))
+                (import "wasi_snapshot_preview1" "environ_get" (func $environ_get (param i32 i32) (result i32)))
+                (memory (export "memory") 1)
+                (func (export "_start")
+                    (drop (call $args_sizes_get (i32.const 0) (i32.const 4)))
+                    (drop (call $args_get (i32.const 16) (i32.const 256)))
+                    (drop (call $environ_sizes_get (i32.const 8) (i32.const 12)))
+                    (drop (call $environ_get (i32.const 32) (i32.const 512)))))"#,
+        );
+        dbg.launch_config_mut()
+            .set_args(vec![String::from("first"), String::from("")]);
+        dbg.launch_config_mut().set_env("KEY", "value");
+        assert!(matches!(dbg.run(None).unwrap(), Trap::ExecutionFinished));
+
+        let memory = dbg.memory().unwrap();
+        let read_string = |ptr: u32| {
+            let ptr: u32 = memory.load(ptr).unwrap();
+            let bytes = &memory.data()[ptr as usize..];
+            String::from_utf8(bytes[..bytes.iter().position(|&b| b == 0).unwrap()].to_vec()).unwrap()
+        };
+        let program_name = read_string(16);
+        assert!(program_name.ends_with("module.wasm"));
+        assert_eq!(memory.load::<u32>(0).unwrap(), 3);
+        assert_eq!(
+            memory.load::<u32>(4).unwrap() as usize,
+            program_name.len() + "first".len() + 3
+        );
+        assert_eq!(read_string(20), "first");
+        assert_eq!(read_string(24), "");
+        assert_eq!(memory.load::<u32>(8).unwrap(), 1);
+        assert_eq!(memory.load::<u32>(12).unwrap(), 10);
+        assert_eq!(memory.load::<u32>(32).unwrap(), 512);
+        assert_eq!(read_string(32), "KEY=value");
+    }
+
+    #[test]
+    fn poll_oneoff_skips_long_sleeps() {
+        // Sleeps for an hour and returns the monotonic time that passed for the guest
+        let mut dbg = load_wat(
+            r#"(module
+                (import "wasi_snapshot_preview1" "clock_time_get" (func $clock_time_get (param i32 i64 i32) (result i32)))
+                (import "wasi_snapshot_preview1" "poll_oneoff" (func $poll_oneoff (param i32 i32 i32 i32) (result i32)))
+                (memory (export "memory") 1)
+                (func (result i32 i64)
+                    (drop (call $clock_time_get (i32.const 1) (i64.const 1) (i32.const 256)))
+                    (i64.store (i32.const 0) (i64.const 42))
+                    (i32.store8 (i32.const 8) (i32.const 0))
+                    (i32.store (i32.const 16) (i32.const 1))
+                    (i64.store (i32.const 24) (i64.const 3600000000000))
+                    (i32.store16 (i32.const 40) (i32.const 0))
+                    (call $poll_oneoff (i32.const 0) (i32.const 64) (i32.const 1) (i32.const 128))
+                    (drop (call $clock_time_get (i32.const 1) (i64.const 1) (i32.const 264)))
+                    (i64.sub (i64.load (i32.const 264)) (i64.load (i32.const 256)))))"#,
+        );
+        let start = Instant::now();
+        assert!(matches!(dbg.call(2, &[]).unwrap(), Trap::ExecutionFinished));
+        assert!(start.elapsed().as_secs() < 10);
+
+        let stack = dbg.vm().unwrap().value_stack();
+        assert_eq!(stack[0], Value::I32(Errno::Success as i32));
+        assert!(matches!(stack[1], Value::I64(elapsed) if elapsed >= 3_600_000_000_000));
+        let memory = dbg.memory().unwrap();
+        assert_eq!(memory.load::<u32>(128).unwrap(), 1);
+        assert_eq!(memory.load::<u64>(64).unwrap(), 42);
+        assert_eq!(memory.load::<u16>(72).unwrap(), Errno::Success as u16);
+        assert_eq!(memory.load::<u8>(74).unwrap(), 0);
+    }
+}
@@ -0,0 +1,151 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use super::fd::WasiFile;
+use super::types::*;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+impl fmt::Display for OutputStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputStream::Stdout => write!(f, "stdout"),
+            OutputStream::Stderr => write!(f, "stderr"),
+        }
+    }
+}
+
+pub type OutputCallback = Box<dyn FnMut(OutputStream, &[u8]) -> io::Result<()>>;
+
+#[derive(Default)]
+pub enum OutputSink {
+    #[default]
+    Terminal,
+    File(fs::File),
+    Buffer,
+    Custom(OutputCallback),
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct CapturedOutput {
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+#[derive(Default)]
+pub struct Output {
+    sink: OutputSink,
+    captured: CapturedOutput,
+}
+
+impl Output {
+    pub fn new(sink: OutputSink) -> Self {
+        Output {
+            sink,
+            captured: CapturedOutput::default(),
+        }
+    }
+
+    pub fn set_sink(&mut self, sink: OutputSink) {
+        self.sink = sink;
+    }
+
+    pub const fn captured(&self) -> &CapturedOutput {
+        &self.captured
+    }
+
+    pub fn take_captured(&mut self) -> CapturedOutput {
+        std::mem::take(&mut self.captured)
+    }
+
+    pub fn write(&mut self, stream: OutputStream, data: &[u8]) -> io::Result<()> {
+        match self.sink {
+            OutputSink::Terminal => match stream {
+                OutputStream::Stdout => {
+                    let mut stdout = io::stdout();
+                    stdout.write_all(data)?;
+                    stdout.flush()
+                }
+                OutputStream::Stderr => io::stderr().write_all(data),
+            },
+            OutputSink::File(ref mut file) => file.write_all(data),
+            OutputSink::Buffer => {
+                match stream {
+                    OutputStream::Stdout => self.captured.stdout.extend_from_slice(data),
+                    OutputStream::Stderr => self.captured.stderr.extend_from_slice(data),
+                }
+                Ok(())
+            }
+            OutputSink::Custom(ref mut write) => write(stream, data),
+        }
+    }
+}
+
+pub struct OutputFile {
+    stream: OutputStream,
+    output: Rc<RefCell<Output>>,
+}
+
+impl OutputFile {
+    pub fn new(stream: OutputStream, output: Rc<RefCell<Output>>) -> Self {
+        OutputFile { stream, output }
+    }
+}
+
+impl WasiFile for OutputFile {
+    fn filetype(&self) -> Filetype {
+        Filetype::CharacterDevice
+    }
+
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Errno> {
+        self.output
+            .borrow_mut()
+            .write(self.stream, buf)
+            .map_err(|_| Errno::Io)?;
+        Ok(buf.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OutputSink;
+    use crate::test_utils::load_wat;
+    use crate::vm::Trap;
+
+    #[test]
+    fn capture_output() {
+        let mut dbg = load_wat(
+            r#"(module
+                (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 16) "hello\n")
+                (data (i32.const 32) "oops\n")
+                (func $write (param $fd i32) (param $buf i32) (param $len i32)
+                    (i32.store (i32.const 0) (local.get $buf))
+                    (i32.store (i32.const 4) (local.get $len))
+                    (drop (call $fd_write (local.get $fd) (i32.const 0) (i32.const 1) (i32.const 8))))
+                (func (export "_start")
+                    (call $write (i32.const 1) (i32.const 16) (i32.const 6))
+                    (call $write (i32.const 2) (i32.const 32) (i32.const 5))
+                    (call $write (i32.const 1) (i32.const 16) (i32.const 6))))"#,
+        );
+        dbg.set_output_sink(OutputSink::Buffer);
+        assert!(matches!(dbg.run(None).unwrap(), Trap::ExecutionFinished));
+
+        let output = dbg.captured_output();
+        assert_eq!(output.stdout, b"hello\nhello\n");
+        assert_eq!(output.stderr, b"oops\n");
+
+        let output = dbg.take_captured_output();
+        assert_eq!(output.stdout, b"hello\nhello\n");
+        assert!(dbg.captured_output().stdout.is_empty());
+        assert!(dbg.captured_output().stderr.is_empty());
+    }
+}
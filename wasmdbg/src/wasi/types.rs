@@ -0,0 +1,219 @@
+use std::fmt;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(u16)]
+pub enum Errno {
+    Success = 0,
+    TooBig = 1,
+    Acces = 2,
+    Addrinuse = 3,
+    Addrnotavail = 4,
+    Afnosupport = 5,
+    Again = 6,
+    Already = 7,
+    Badf = 8,
+    Badmsg = 9,
+    Busy = 10,
+    Canceled = 11,
+    Child = 12,
+    Connaborted = 13,
+    Connrefused = 14,
+    Connreset = 15,
+    Deadlk = 16,
+    Destaddrreq = 17,
+    Dom = 18,
+    Dquot = 19,
+    Exist = 20,
+    Fault = 21,
+    Fbig = 22,
+    Hostunreach = 23,
+    Idrm = 24,
+    Ilseq = 25,
+    Inprogress = 26,
+    Intr = 27,
+    Inval = 28,
+    Io = 29,
+    Isconn = 30,
+    Isdir = 31,
+    Loop = 32,
+    Mfile = 33,
+    Mlink = 34,
+    Msgsize = 35,
+    Multihop = 36,
+    Nametoolong = 37,
+    Netdown = 38,
+    Netreset = 39,
+    Netunreach = 40,
+    Nfile = 41,
+    Nobufs = 42,
+    Nodev = 43,
+    Noent = 44,
+    Noexec = 45,
+    Nolck = 46,
+    Nolink = 47,
+    Nomem = 48,
+    Nomsg = 49,
+    Noprotoopt = 50,
+    Nospc = 51,
+    Nosys = 52,
+    Notconn = 53,
+    Notdir = 54,
+    Notempty = 55,
+    Notrecoverable = 56,
+    Notsock = 57,
+    Notsup = 58,
+    Notty = 59,
+    Nxio = 60,
+    Overflow = 61,
+    Ownerdead = 62,
+    Perm = 63,
+    Pipe = 64,
+    Proto = 65,
+    Protonosupport = 66,
+    Prototype = 67,
+    Range = 68,
+    Rofs = 69,
+    Spipe = 70,
+    Srch = 71,
+    Stale = 72,
+    Timedout = 73,
+    Txtbsy = 74,
+    Xdev = 75,
+    Notcapable = 76,
+}
+
+impl fmt::Display for Errno {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} ({})", self, *self as u16)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[repr(u8)]
+pub enum Filetype {
+    Unknown = 0,
+    BlockDevice = 1,
+    CharacterDevice = 2,
+    Directory = 3,
+    RegularFile = 4,
+    SocketDgram = 5,
+    SocketStream = 6,
+    SymbolicLink = 7,
+}
+
+impl fmt::Display for Filetype {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Filetype::Unknown => "unknown",
+            Filetype::BlockDevice => "block device",
+            Filetype::CharacterDevice => "character device",
+            Filetype::Directory => "directory",
+            Filetype::RegularFile => "regular file",
+            Filetype::SocketDgram => "datagram socket",
+            Filetype::SocketStream => "stream socket",
+            Filetype::SymbolicLink => "symbolic link",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Filestat {
+    pub dev: u64,
+    pub ino: u64,
+    pub filetype: Option<Filetype>,
+    pub nlink: u64,
+    pub size: u64,
+    pub atim: u64,
+    pub mtim: u64,
+    pub ctim: u64,
+}
+
+pub const CLOCK_REALTIME: u32 = 0;
+pub const CLOCK_MONOTONIC: u32 = 1;
+pub const CLOCK_PROCESS_CPUTIME_ID: u32 = 2;
+pub const CLOCK_THREAD_CPUTIME_ID: u32 = 3;
+
+pub const RIGHT_FD_DATASYNC: u64 = 1 << 0;
+pub const RIGHT_FD_READ: u64 = 1 << 1;
+pub const RIGHT_FD_SEEK: u64 = 1 << 2;
+pub const RIGHT_FD_FDSTAT_SET_FLAGS: u64 = 1 << 3;
+pub const RIGHT_FD_SYNC: u64 = 1 << 4;
+pub const RIGHT_FD_TELL: u64 = 1 << 5;
+pub const RIGHT_FD_WRITE: u64 = 1 << 6;
+pub const RIGHT_FD_ADVISE: u64 = 1 << 7;
+pub const RIGHT_FD_ALLOCATE: u64 = 1 << 8;
+pub const RIGHT_PATH_CREATE_DIRECTORY: u64 = 1 << 9;
+pub const RIGHT_PATH_CREATE_FILE: u64 = 1 << 10;
+pub const RIGHT_PATH_LINK_SOURCE: u64 = 1 << 11;
+pub const RIGHT_PATH_LINK_TARGET: u64 = 1 << 12;
+pub const RIGHT_PATH_OPEN: u64 = 1 << 13;
+pub const RIGHT_FD_READDIR: u64 = 1 << 14;
+pub const RIGHT_PATH_READLINK: u64 = 1 << 15;
+pub const RIGHT_PATH_RENAME_SOURCE: u64 = 1 << 16;
+pub const RIGHT_PATH_RENAME_TARGET: u64 = 1 << 17;
+pub const RIGHT_PATH_FILESTAT_GET: u64 = 1 << 18;
+pub const RIGHT_PATH_FILESTAT_SET_SIZE: u64 = 1 << 19;
+pub const RIGHT_PATH_FILESTAT_SET_TIMES: u64 = 1 << 20;
+pub const RIGHT_FD_FILESTAT_GET: u64 = 1 << 21;
+pub const RIGHT_FD_FILESTAT_SET_SIZE: u64 = 1 << 22;
+pub const RIGHT_FD_FILESTAT_SET_TIMES: u64 = 1 << 23;
+pub const RIGHT_PATH_SYMLINK: u64 = 1 << 24;
+pub const RIGHT_PATH_REMOVE_DIRECTORY: u64 = 1 << 25;
+pub const RIGHT_PATH_UNLINK_FILE: u64 = 1 << 26;
+pub const RIGHT_POLL_FD_READWRITE: u64 = 1 << 27;
+pub const RIGHT_SOCK_SHUTDOWN: u64 = 1 << 28;
+pub const RIGHT_SOCK_ACCEPT: u64 = 1 << 29;
+
+pub const RIGHTS_ALL: u64 = (1 << 30) - 1;
+pub const RIGHTS_STDIN: u64 =
+    RIGHT_FD_READ | RIGHT_FD_FDSTAT_SET_FLAGS | RIGHT_FD_FILESTAT_GET | RIGHT_POLL_FD_READWRITE;
+pub const RIGHTS_STDOUT: u64 =
+    RIGHT_FD_WRITE | RIGHT_FD_FDSTAT_SET_FLAGS | RIGHT_FD_FILESTAT_GET | RIGHT_POLL_FD_READWRITE;
+pub const RIGHTS_DIR_BASE: u64 = RIGHT_FD_FDSTAT_SET_FLAGS
+    | RIGHT_FD_SYNC
+    | RIGHT_FD_ADVISE
+    | RIGHT_PATH_CREATE_DIRECTORY
+    | RIGHT_PATH_CREATE_FILE
+    | RIGHT_PATH_LINK_SOURCE
+    | RIGHT_PATH_LINK_TARGET
+    | RIGHT_PATH_OPEN
+    | RIGHT_FD_READDIR
+    | RIGHT_PATH_READLINK
+    | RIGHT_PATH_RENAME_SOURCE
+    | RIGHT_PATH_RENAME_TARGET
+    | RIGHT_PATH_FILESTAT_GET
+    | RIGHT_PATH_FILESTAT_SET_SIZE
+    | RIGHT_PATH_FILESTAT_SET_TIMES
+    | RIGHT_FD_FILESTAT_GET
+    | RIGHT_FD_FILESTAT_SET_TIMES
+    | RIGHT_PATH_SYMLINK
+    | RIGHT_PATH_REMOVE_DIRECTORY
+    | RIGHT_PATH_UNLINK_FILE
+    | RIGHT_POLL_FD_READWRITE;
+
+pub const FDFLAG_APPEND: u16 = 1 << 0;
+pub const FDFLAG_DSYNC: u16 = 1 << 1;
+pub const FDFLAG_NONBLOCK: u16 = 1 << 2;
+pub const FDFLAG_RSYNC: u16 = 1 << 3;
+pub const FDFLAG_SYNC: u16 = 1 << 4;
+
+pub const OFLAG_CREAT: u16 = 1 << 0;
+pub const OFLAG_DIRECTORY: u16 = 1 << 1;
+pub const OFLAG_EXCL: u16 = 1 << 2;
+pub const OFLAG_TRUNC: u16 = 1 << 3;
+
+pub const LOOKUPFLAG_SYMLINK_FOLLOW: u32 = 1 << 0;
+
+pub const FSTFLAG_ATIM: u16 = 1 << 0;
+pub const FSTFLAG_ATIM_NOW: u16 = 1 << 1;
+pub const FSTFLAG_MTIM: u16 = 1 << 2;
+pub const FSTFLAG_MTIM_NOW: u16 = 1 << 3;
+
+pub const EVENTTYPE_CLOCK: u8 = 0;
+pub const EVENTTYPE_FD_READ: u8 = 1;
+pub const EVENTTYPE_FD_WRITE: u8 = 2;
+
+pub const SUBCLOCKFLAG_ABSTIME: u16 = 1 << 0;
+
+pub const PREOPENTYPE_DIR: u8 = 0;
@@ -1,5 +1,7 @@
+mod module;
 mod nan_preserving_float;
 pub mod value;
 
+pub use module::*;
 pub use nan_preserving_float::{F32, F64};
 pub use value::Value;
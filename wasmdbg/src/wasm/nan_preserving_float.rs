@@ -150,7 +150,7 @@ macro_rules! float {
             #[allow(clippy::cast_lossless)]
             pub fn trunc_to_i32(self) -> Option<i32> {
                 let self_float = self.to_float();
-                if self.is_nan() || self_float >= -(i32::min_value() as $is) || self_float < i32::min_value() as $is {
+                if self.is_nan() || self_float >= -(i32::MIN as $is) || self_float < i32::MIN as $is {
                     None
                 } else {
                     Some(self_float.trunc() as i32)
@@ -159,7 +159,7 @@ macro_rules! float {
 
             pub fn trunc_to_i64(self) -> Option<i64> {
                 let self_float = self.to_float();
-                if self.is_nan() || self_float >= -(i64::min_value() as $is) || self_float < i64::min_value() as $is {
+                if self.is_nan() || self_float >= -(i64::MIN as $is) || self_float < i64::MIN as $is {
                     None
                 } else {
                     Some(self_float.trunc() as i64)
@@ -169,7 +169,7 @@ macro_rules! float {
             #[allow(clippy::cast_lossless)]
             pub fn trunc_to_u32(self) -> Option<u32> {
                 let self_float = self.to_float();
-                if self.is_nan() || self_float >= -(u32::min_value() as $is) * 2.0 || self_float <= -1.0 {
+                if self.is_nan() || self_float >= -(u32::MIN as $is) * 2.0 || self_float <= -1.0 {
                     None
                 } else {
                     Some(self_float.trunc() as u32)
@@ -178,7 +178,7 @@ macro_rules! float {
 
             pub fn trunc_to_u64(self) -> Option<u64> {
                 let self_float = self.to_float();
-                if self.is_nan() || self_float >= -(u64::min_value() as $is) * 2.0 || self_float <= -1.0 {
+                if self.is_nan() || self_float >= -(u64::MIN as $is) * 2.0 || self_float <= -1.0 {
                     None
                 } else {
                     Some(self_float.trunc() as u64)
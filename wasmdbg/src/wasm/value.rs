@@ -1,10 +1,8 @@
 use std::fmt;
 use std::str::FromStr;
 
-use bwasm::ValueType;
-
 use crate::vm::{Trap, VMResult};
-use crate::{F32, F64};
+use crate::{ValueType, F32, F64};
 
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum Value {
@@ -51,15 +49,15 @@ impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match *self {
             Value::I32(val) => {
-                if (val as i32) < 0 {
-                    write!(f, "i32 : 0x{0:08x} = {0} = {1}", val, val as i32)
+                if val < 0 {
+                    write!(f, "i32 : 0x{0:08x} = {0} = {1}", val as u32, val)
                 } else {
                     write!(f, "i32 : 0x{0:08x} = {0}", val)
                 }
             }
             Value::I64(val) => {
-                if (val as i64) < 0 {
-                    write!(f, "i64 : 0x{0:016x} = {0} = {1}", val, val as i64)
+                if val < 0 {
+                    write!(f, "i64 : 0x{0:016x} = {0} = {1}", val as u64, val)
                 } else {
                     write!(f, "i64 : 0x{0:016x} = {0}", val)
                 }
@@ -218,7 +216,7 @@ macro_rules! impl_integer {
                 if let Some(radix) = radix {
                     $type::from_str_radix(&s[2..], radix)
                 } else {
-                    $type::from_str_radix(s, 10)
+                    s.parse()
                 }
             }
         }